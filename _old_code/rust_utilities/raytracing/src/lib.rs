@@ -1,5 +1,5 @@
 pub mod camera;
+pub mod color;
 pub mod hittable;
 pub mod math;
 pub mod ray;
-pub mod renderer;
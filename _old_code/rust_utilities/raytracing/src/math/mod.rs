@@ -17,7 +17,7 @@ pub fn random_normalized() -> R {
 
 pub fn random_range(min: R, max: R) -> R {
     let mut rng = rand::thread_rng();
-    rng.gen_range(min, max)
+    rng.gen_range(min..max)
 }
 
 pub fn clamp(n: R, min: R, max: R) -> R {
@@ -0,0 +1,13 @@
+//! Renders the original FM bass demo to `demo.wav`.
+
+use wavrender::output::{OutputFormat, SampleFormat, WavOutput};
+
+fn main() -> Result<(), hound::Error> {
+    let sample_rate = 44100;
+    let samples = wavrender::demo(sample_rate as f32, 8.);
+
+    let format = OutputFormat::new(1, sample_rate, SampleFormat::Int16);
+    let mut writer = WavOutput::create("demo.wav", format)?;
+    writer.write_mono(&samples)?;
+    writer.finalize()
+}
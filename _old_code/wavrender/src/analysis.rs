@@ -0,0 +1,514 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use alloc::{vec, vec::Vec};
+use core::{
+    f64::consts::PI,
+    ops::{Add, Mul, Sub},
+};
+
+pub fn gain_to_db(gain: f32) -> f32 {
+    20. * gain.max(1e-6).log10()
+}
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10_f32.powf(db / 20.)
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// A unit vector at an angle in radians.
+    pub fn from_angle(angle: f64) -> Self {
+        Self::new(angle.cos(), angle.sin())
+    }
+
+    pub fn norm_squared(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn norm(self) -> f64 {
+        self.norm_squared().sqrt()
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+/// An in place radix-2 FFT. The length must be a power of two.
+pub fn fft(buffer: &mut [Complex]) {
+    transform(buffer, false);
+}
+
+/// The inverse of `fft`, including the 1 / n scaling.
+pub fn ifft(buffer: &mut [Complex]) {
+    transform(buffer, true);
+    let scale = 1. / buffer.len() as f64;
+    for value in buffer.iter_mut() {
+        *value = Complex::new(value.re * scale, value.im * scale);
+    }
+}
+
+fn transform(buffer: &mut [Complex], inverse: bool) {
+    let n = buffer.len();
+    assert!(
+        n.is_power_of_two(),
+        "FFT length {} is not a power of two",
+        n
+    );
+
+    // Bit reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buffer.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1. } else { -1. };
+    let mut size = 2;
+    while size <= n {
+        let step = Complex::from_angle(sign * 2. * PI / size as f64);
+        for start in (0..n).step_by(size) {
+            let mut twiddle = Complex::new(1., 0.);
+            for k in 0..size / 2 {
+                let even = buffer[start + k];
+                let odd = buffer[start + k + size / 2] * twiddle;
+                buffer[start + k] = even + odd;
+                buffer[start + k + size / 2] = even - odd;
+                twiddle = twiddle * step;
+            }
+        }
+        size *= 2;
+    }
+}
+
+/// A window applied before taking a spectrum, to reduce leakage between bins.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    /// Four term Blackman-Harris, with sidelobes below -92 dB.
+    BlackmanHarris,
+}
+
+impl Window {
+    /// The window's value at sample `i` of `n`.
+    pub fn value(self, i: usize, n: usize) -> f64 {
+        let x = 2. * PI * i as f64 / n as f64;
+        match self {
+            Window::Rectangular => 1.,
+            Window::Hann => 0.5 - 0.5 * x.cos(),
+            Window::BlackmanHarris => {
+                0.35875 - 0.48829 * x.cos() + 0.14128 * (2. * x).cos() - 0.01168 * (3. * x).cos()
+            }
+        }
+    }
+}
+
+/// The magnitude spectrum of a block of samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spectrum {
+    /// Amplitudes for each bin from 0 Hz to Nyquist, scaled so a full scale sine on a bin is 1.
+    magnitudes: Vec<f32>,
+    size: usize,
+    sample_rate: f32,
+}
+
+impl Spectrum {
+    /// Takes the spectrum of the samples, zero padded to a power of two.
+    pub fn new(samples: &[f32], sample_rate: f32, window: Window) -> Self {
+        let size = samples.len().next_power_of_two().max(2);
+        let n = samples.len();
+        let mut buffer: Vec<Complex> = samples
+            .iter()
+            .enumerate()
+            .map(|(i, s)| Complex::new(*s as f64 * window.value(i, n), 0.))
+            .collect();
+        buffer.resize(size, Complex::default());
+        fft(&mut buffer);
+
+        // The window's gain, so amplitudes don't depend on it
+        let gain: f64 = (0..n).map(|i| window.value(i, n)).sum::<f64>().max(1e-12);
+        let magnitudes = buffer[..=size / 2]
+            .iter()
+            .map(|bin| (2. * bin.norm() / gain) as f32)
+            .collect();
+
+        Self {
+            magnitudes,
+            size,
+            sample_rate,
+        }
+    }
+
+    pub fn magnitudes(&self) -> &[f32] {
+        &self.magnitudes
+    }
+
+    /// The width of a bin in Hz.
+    pub fn resolution(&self) -> f32 {
+        self.sample_rate / self.size as f32
+    }
+
+    pub fn bin_frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.resolution()
+    }
+
+    /// The magnitude of the bin nearest the frequency.
+    pub fn magnitude_at(&self, frequency: f32) -> f32 {
+        let bin = (frequency / self.resolution()).round() as usize;
+        self.magnitudes.get(bin).copied().unwrap_or_default()
+    }
+
+    /// The sum of squared magnitudes of bins within a range of frequencies.
+    pub fn band_power(&self, low: f32, high: f32) -> f32 {
+        self.magnitudes
+            .iter()
+            .enumerate()
+            .filter(|(bin, _)| {
+                let frequency = self.bin_frequency(*bin);
+                frequency >= low && frequency < high
+            })
+            .map(|(_, m)| m * m)
+            .sum()
+    }
+
+    /// The frequency of the loudest bin above DC, refined by fitting a parabola to its neighbours.
+    pub fn peak_frequency(&self) -> f32 {
+        let bin = (1..self.magnitudes.len())
+            .max_by(|a, b| self.magnitudes[*a].total_cmp(&self.magnitudes[*b]))
+            .unwrap_or(0);
+        if bin == 0 || bin + 1 >= self.magnitudes.len() {
+            return self.bin_frequency(bin);
+        }
+
+        let db = |bin: usize| gain_to_db(self.magnitudes[bin]);
+        let (left, center, right) = (db(bin - 1), db(bin), db(bin + 1));
+        let denominator = left - 2. * center + right;
+        let offset = if denominator == 0. {
+            0.
+        } else {
+            0.5 * (left - right) / denominator
+        };
+
+        (bin as f32 + offset) * self.resolution()
+    }
+}
+
+pub fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0., |peak, s| peak.max(s.abs()))
+}
+
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.;
+    }
+
+    let sum: f64 = samples.iter().map(|s| (*s as f64).powi(2)).sum();
+    (sum / samples.len() as f64).sqrt() as f32
+}
+
+/// Integrated loudness of a mono signal in LUFS, following ITU-R BS.1770 with K-weighting and
+/// gating. Returns negative infinity for silence.
+pub fn lufs(samples: &[f32], sample_rate: f32) -> f32 {
+    let mut stages = k_weighting(sample_rate as f64);
+    let weighted: Vec<f64> = samples
+        .iter()
+        .map(|s| {
+            stages
+                .iter_mut()
+                .fold(*s as f64, |x, stage| stage.process(x))
+        })
+        .collect();
+
+    // 400 ms blocks overlapping by 75%
+    let block = (0.4 * sample_rate) as usize;
+    let hop = (block / 4).max(1);
+    let loudness = |power: f64| -0.691 + 10. * power.log10();
+    let powers: Vec<f64> = if weighted.len() < block {
+        vec![weighted.iter().map(|s| s * s).sum::<f64>() / weighted.len().max(1) as f64]
+    } else {
+        (0..=(weighted.len() - block) / hop)
+            .map(|i| {
+                weighted[i * hop..i * hop + block]
+                    .iter()
+                    .map(|s| s * s)
+                    .sum::<f64>()
+            })
+            .map(|sum| sum / block as f64)
+            .collect()
+    };
+
+    let mean = |threshold: f64| {
+        let gated: Vec<f64> = powers
+            .iter()
+            .copied()
+            .filter(|p| loudness(*p) > threshold)
+            .collect();
+        if gated.is_empty() {
+            None
+        } else {
+            Some(gated.iter().sum::<f64>() / gated.len() as f64)
+        }
+    };
+
+    let absolute = match mean(-70.) {
+        Some(power) => power,
+        None => return f32::NEG_INFINITY,
+    };
+    let relative = mean(loudness(absolute) - 10.).unwrap_or(absolute);
+    loudness(relative) as f32
+}
+
+/// A biquad in f64, since the K-weighting high pass sits very low relative to the sample rate.
+struct Stage {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Stage {
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.z[0];
+        self.z[0] = self.b[1] * input - self.a[0] * output + self.z[1];
+        self.z[1] = self.b[2] * input - self.a[1] * output;
+        output
+    }
+}
+
+/// The BS.1770 shelf and high pass, derived for any sample rate. The standard only lists
+/// coefficients for 48 kHz; these parameters reproduce them.
+fn k_weighting(sample_rate: f64) -> [Stage; 2] {
+    let shelf = {
+        let k = (PI * 1681.974450955533 / sample_rate).tan();
+        let q = 0.7071752369554196;
+        let vh = 10_f64.powf(3.999843853973347 / 20.);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1. + k / q + k * k;
+        Stage {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2. * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            z: [0.; 2],
+        }
+    };
+    let high_pass = {
+        let k = (PI * 38.13547087602444 / sample_rate).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1. + k / q + k * k;
+        Stage {
+            b: [1., -2., 1.],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            z: [0.; 2],
+        }
+    };
+
+    [shelf, high_pass]
+}
+
+/// Estimates pitch from the average spacing of upward zero crossings. Only reliable for simple
+/// waveforms with two crossings per cycle.
+pub fn zero_crossing_pitch(samples: &[f32], sample_rate: f32) -> Option<f32> {
+    // Crossing times interpolated between samples
+    let crossings: Vec<f64> = samples
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| w[0] <= 0. && w[1] > 0.)
+        .map(|(i, w)| i as f64 + (-w[0] / (w[1] - w[0])) as f64)
+        .collect();
+
+    if crossings.len() < 2 {
+        return None;
+    }
+
+    let cycles = (crossings.len() - 1) as f64;
+    let period = (crossings[crossings.len() - 1] - crossings[0]) / cycles;
+    Some((sample_rate as f64 / period) as f32)
+}
+
+/// Estimates pitch between two frequencies with the normalized difference function from the
+/// YIN algorithm, which copes with harmonics and noise. Returns `None` when nothing is periodic.
+pub fn autocorrelation_pitch(
+    samples: &[f32],
+    sample_rate: f32,
+    min_frequency: f32,
+    max_frequency: f32,
+) -> Option<f32> {
+    let min_lag = ((sample_rate / max_frequency).floor() as usize).max(2);
+    let max_lag = (sample_rate / min_frequency).ceil() as usize;
+    if samples.len() < max_lag * 2 {
+        return None;
+    }
+
+    let window = samples.len() - max_lag - 1;
+    let difference = |lag: usize| -> f64 {
+        (0..window)
+            .map(|i| (samples[i] as f64 - samples[i + lag] as f64).powi(2))
+            .sum()
+    };
+
+    // Cumulative mean normalized difference for lags 1 ..= max_lag + 1
+    let mut normalized = vec![1.; max_lag + 2];
+    let mut total = 0.;
+    for (lag, value) in normalized.iter_mut().enumerate().skip(1) {
+        let d = difference(lag);
+        total += d;
+        *value = if total > 0. {
+            d * lag as f64 / total
+        } else {
+            1.
+        };
+    }
+
+    const THRESHOLD: f64 = 0.15;
+    let mut lag = (min_lag..=max_lag).find(|lag| normalized[*lag] < THRESHOLD)?;
+    while lag < max_lag && normalized[lag + 1] < normalized[lag] {
+        lag += 1;
+    }
+
+    let (left, center, right) = (normalized[lag - 1], normalized[lag], normalized[lag + 1]);
+    let denominator = left - 2. * center + right;
+    let offset = if denominator == 0. {
+        0.
+    } else {
+        0.5 * (left - right) / denominator
+    };
+
+    Some((sample_rate as f64 / (lag as f64 + offset)) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oscillator::Oscillator;
+
+    const SAMPLE_RATE: f32 = 44100.;
+
+    fn render(oscillator: Oscillator, seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE) as usize)
+            .map(|i| oscillator.sample(i as f32 / SAMPLE_RATE))
+            .collect()
+    }
+
+    #[test]
+    fn fft_round_trips_and_matches_dft() {
+        let input: Vec<Complex> = (0..16)
+            .map(|i| Complex::new((i as f64 * 0.7).sin(), (i as f64 * 0.3).cos()))
+            .collect();
+        let mut buffer = input.clone();
+        fft(&mut buffer);
+
+        for (k, bin) in buffer.iter().enumerate() {
+            let dft = input
+                .iter()
+                .enumerate()
+                .fold(Complex::default(), |sum, (i, x)| {
+                    sum + *x * Complex::from_angle(-2. * PI * (i * k) as f64 / 16.)
+                });
+            assert!((*bin - dft).norm() < 1e-9);
+        }
+
+        ifft(&mut buffer);
+        for (a, b) in buffer.iter().zip(input.iter()) {
+            assert!((*a - *b).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn sine_spectrum_peaks_at_its_frequency() {
+        let samples = render(Oscillator::Sine { frequency: 440. }, 0.5);
+        let spectrum = Spectrum::new(&samples, SAMPLE_RATE, Window::BlackmanHarris);
+
+        assert!((spectrum.peak_frequency() - 440.).abs() < 0.5);
+        assert!((spectrum.magnitude_at(440.) - 1.).abs() < 0.05);
+        assert!(spectrum.magnitude_at(880.) < 1e-3);
+    }
+
+    #[test]
+    fn meters_measure_sine() {
+        let samples = render(Oscillator::Sine { frequency: 1000. }, 2.);
+
+        assert!((peak(&samples) - 1.).abs() < 1e-3);
+        assert!((rms(&samples) - core::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+        // A full scale 1 kHz sine reads the same as its RMS level
+        let lufs = lufs(&samples, SAMPLE_RATE);
+        assert!((lufs + 3.01).abs() < 0.1, "{}", lufs);
+
+        let quiet: Vec<f32> = samples.iter().map(|s| s * 0.1).collect();
+        assert!((super::lufs(&quiet, SAMPLE_RATE) - lufs + 20.).abs() < 0.01);
+        assert_eq!(f32::NEG_INFINITY, super::lufs(&[0.; 44100], SAMPLE_RATE));
+    }
+
+    #[test]
+    fn pitch_detectors_find_fundamental() {
+        let sine = render(Oscillator::Sine { frequency: 440. }, 0.2);
+        let pitch = zero_crossing_pitch(&sine, SAMPLE_RATE).unwrap();
+        assert!((pitch - 440.).abs() < 0.1, "{}", pitch);
+
+        // A strong second harmonic adds zero crossings but keeps the period
+        let rich: Vec<f32> = (0..8820)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE;
+                let sample = |f: f32| (2. * core::f32::consts::PI * f * t).sin();
+                0.4 * sample(220.) + 0.6 * sample(440.) + 0.3 * sample(660.)
+            })
+            .collect();
+        let pitch = autocorrelation_pitch(&rich, SAMPLE_RATE, 50., 1000.).unwrap();
+        assert!((pitch - 220.).abs() < 1., "{}", pitch);
+
+        let noise = render(
+            Oscillator::Noise {
+                kind: crate::oscillator::NoiseKind::White,
+                frequency: SAMPLE_RATE,
+                seed: 1,
+            },
+            0.2,
+        );
+        assert_eq!(None, autocorrelation_pitch(&noise, SAMPLE_RATE, 50., 1000.));
+    }
+}
@@ -0,0 +1,634 @@
+use super::{Chip, DcBlocker};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{
+    oscillator::{NoiseKind, Oscillator},
+    pitch::midi_to_hz,
+    voice::Voice,
+};
+
+// https://www.nesdev.org/wiki/APU
+const CPU_CLOCK: f64 = 1_789_773.;
+/// The frame counter's envelope and linear counter clock, in Hz. Every second one also clocks
+/// the length counters and sweeps.
+const QUARTER_FRAME: f64 = 240.;
+const DUTIES: [f32; 4] = [0.125, 0.25, 0.5, 0.75];
+const LENGTHS: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+const NOISE_PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const TRIANGLE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+/// The decaying volume shared by the pulse and noise channels.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct VolumeEnvelope {
+    start: bool,
+    looping: bool,
+    constant: bool,
+    /// The constant volume, or the envelope's divider period.
+    period: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl VolumeEnvelope {
+    /// Writes the `--LC VVVV` bits of a channel's first register.
+    fn write(&mut self, value: u8) {
+        self.looping = value & 0x20 != 0;
+        self.constant = value & 0x10 != 0;
+        self.period = value & 0x0F;
+    }
+
+    fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.period;
+        } else if self.divider == 0 {
+            self.divider = self.period;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    fn volume(&self) -> u8 {
+        if self.constant {
+            self.period
+        } else {
+            self.decay
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Pulse {
+    /// Pulse 1 subtracts one more when sweeping down.
+    ones_complement: bool,
+    enabled: bool,
+    duty: usize,
+    envelope: VolumeEnvelope,
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_reload: bool,
+    sweep_divider: u8,
+    timer: u16,
+    length: u8,
+    phase: f64,
+}
+
+impl Pulse {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.duty = (value >> 6) as usize;
+                self.envelope.write(value);
+            }
+            1 => {
+                self.sweep_enabled = value & 0x80 != 0;
+                self.sweep_period = (value >> 4) & 0x07;
+                self.sweep_negate = value & 0x08 != 0;
+                self.sweep_shift = value & 0x07;
+                self.sweep_reload = true;
+            }
+            2 => self.timer = (self.timer & 0x700) | value as u16,
+            _ => {
+                self.timer = (self.timer & 0xFF) | ((value as u16 & 0x07) << 8);
+                if self.enabled {
+                    self.length = LENGTHS[(value >> 3) as usize];
+                }
+                self.envelope.start = true;
+                self.phase = 0.;
+            }
+        }
+    }
+
+    /// The timer the sweep is moving towards.
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer >> self.sweep_shift;
+        if self.sweep_negate {
+            let extra = if self.ones_complement { 1 } else { 0 };
+            self.timer.saturating_sub(change + extra)
+        } else {
+            self.timer + change
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.timer < 8 || self.sweep_target() > 0x7FF
+    }
+
+    fn clock_half_frame(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.muted() {
+            self.timer = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+
+        if !self.envelope.looping && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    fn frequency(&self) -> f64 {
+        CPU_CLOCK / (16. * (self.timer as f64 + 1.))
+    }
+
+    /// The output level, 0 .. 15.
+    fn tick(&mut self, sample_rate: f64) -> u8 {
+        let oscillator = Oscillator::Pulse {
+            frequency: self.frequency() as f32,
+            duty: DUTIES[self.duty],
+        };
+        let high = oscillator.sample_phase(self.phase as f32) > 0.;
+        self.phase = (self.phase + self.frequency() / sample_rate).fract();
+
+        if high && self.length > 0 && !self.muted() {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Triangle {
+    enabled: bool,
+    /// Halts the length counter and keeps the linear counter reloading.
+    control: bool,
+    linear_reload: u8,
+    linear: u8,
+    reload: bool,
+    timer: u16,
+    length: u8,
+    /// In cycles of the 32 step sequence.
+    phase: f64,
+}
+
+impl Triangle {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.control = value & 0x80 != 0;
+                self.linear_reload = value & 0x7F;
+            }
+            2 => self.timer = (self.timer & 0x700) | value as u16,
+            3 => {
+                self.timer = (self.timer & 0xFF) | ((value as u16 & 0x07) << 8);
+                if self.enabled {
+                    self.length = LENGTHS[(value >> 3) as usize];
+                }
+                self.reload = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        if self.reload {
+            self.linear = self.linear_reload;
+        } else if self.linear > 0 {
+            self.linear -= 1;
+        }
+        if !self.control {
+            self.reload = false;
+        }
+    }
+
+    fn clock_half_frame(&mut self) {
+        if !self.control && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    /// The output level, 0 .. 15. When silenced the sequence stops where it is, as on hardware.
+    fn tick(&mut self, sample_rate: f64) -> u8 {
+        let level = TRIANGLE[(self.phase * 32.) as usize % 32];
+        // Very short periods are inaudible and are skipped to avoid aliasing
+        if self.linear > 0 && self.length > 0 && self.timer >= 2 {
+            let frequency = CPU_CLOCK / (32. * (self.timer as f64 + 1.));
+            self.phase = (self.phase + frequency / sample_rate).fract();
+        }
+
+        level
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Noise {
+    enabled: bool,
+    envelope: VolumeEnvelope,
+    short: bool,
+    period: u16,
+    length: u8,
+    /// In shift register steps.
+    phase: f64,
+}
+
+impl Noise {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => self.envelope.write(value),
+            2 => {
+                self.short = value & 0x80 != 0;
+                self.period = NOISE_PERIODS[(value & 0x0F) as usize];
+            }
+            3 => {
+                if self.enabled {
+                    self.length = LENGTHS[(value >> 3) as usize];
+                }
+                self.envelope.start = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn clock_half_frame(&mut self) {
+        if !self.envelope.looping && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    fn tick(&mut self, sample_rate: f64) -> u8 {
+        let oscillator = Oscillator::Noise {
+            kind: NoiseKind::Periodic { short: self.short },
+            frequency: (CPU_CLOCK / self.period.max(1) as f64) as f32,
+            seed: 0,
+        };
+        let high = oscillator.sample_phase(self.phase as f32) > 0.;
+        let delta = oscillator.frequency() as f64 / sample_rate;
+        self.phase = (self.phase + delta).rem_euclid(oscillator.period());
+
+        if high && self.length > 0 {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+}
+
+/// The NES 2A03 sound chip: two pulse channels, a triangle and noise.
+/// Registers are addressed from 0x4000 as on the console.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Apu {
+    pulses: [Pulse; 2],
+    triangle: Triangle,
+    noise: Noise,
+    sample_rate: f64,
+    frame_timer: f64,
+    quarter_frames: u64,
+    dc: DcBlocker,
+}
+
+impl Apu {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            pulses: [
+                Pulse {
+                    ones_complement: true,
+                    ..Pulse::default()
+                },
+                Pulse::default(),
+            ],
+            triangle: Triangle::default(),
+            noise: Noise {
+                period: NOISE_PERIODS[0],
+                ..Noise::default()
+            },
+            sample_rate: sample_rate as f64,
+            frame_timer: 0.,
+            quarter_frames: 0,
+            // The idle triangle rests at its top step, so start settled there instead of popping
+            dc: DcBlocker::new(90., sample_rate).settled(mix(0., TRIANGLE[0] as f32, 0.)),
+        }
+    }
+
+    fn clock_frame_counter(&mut self) {
+        self.frame_timer += QUARTER_FRAME / self.sample_rate;
+        while self.frame_timer >= 1. {
+            self.frame_timer -= 1.;
+            self.quarter_frames += 1;
+
+            self.pulses[0].envelope.clock();
+            self.pulses[1].envelope.clock();
+            self.noise.envelope.clock();
+            self.triangle.clock_quarter_frame();
+
+            if self.quarter_frames & 1 == 0 {
+                self.pulses[0].clock_half_frame();
+                self.pulses[1].clock_half_frame();
+                self.triangle.clock_half_frame();
+                self.noise.clock_half_frame();
+            }
+        }
+    }
+}
+
+impl Chip for Apu {
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x4000..=0x4003 => self.pulses[0].write(address - 0x4000, value),
+            0x4004..=0x4007 => self.pulses[1].write(address - 0x4004, value),
+            0x4008..=0x400B => self.triangle.write(address - 0x4008, value),
+            0x400C..=0x400F => self.noise.write(address - 0x400C, value),
+            0x4015 => {
+                self.pulses[0].enabled = value & 0x01 != 0;
+                self.pulses[1].enabled = value & 0x02 != 0;
+                self.triangle.enabled = value & 0x04 != 0;
+                self.noise.enabled = value & 0x08 != 0;
+                // Disabling a channel silences it immediately
+                if !self.pulses[0].enabled {
+                    self.pulses[0].length = 0;
+                }
+                if !self.pulses[1].enabled {
+                    self.pulses[1].length = 0;
+                }
+                if !self.triangle.enabled {
+                    self.triangle.length = 0;
+                }
+                if !self.noise.enabled {
+                    self.noise.length = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self) -> f32 {
+        self.clock_frame_counter();
+
+        let sample_rate = self.sample_rate;
+        let pulse =
+            self.pulses[0].tick(sample_rate) as f32 + self.pulses[1].tick(sample_rate) as f32;
+        let triangle = self.triangle.tick(sample_rate) as f32;
+        let noise = self.noise.tick(sample_rate) as f32;
+
+        self.dc.process(mix(pulse, triangle, noise))
+    }
+}
+
+/// The console's nonlinear mixer, from channel levels to 0 .. 1.
+fn mix(pulse: f32, triangle: f32, noise: f32) -> f32 {
+    let pulse_out = if pulse > 0. {
+        95.88 / (8128. / pulse + 100.)
+    } else {
+        0.
+    };
+    let tnd = triangle / 8227. + noise / 12241.;
+    let tnd_out = if tnd > 0. {
+        159.79 / (1. / tnd + 100.)
+    } else {
+        0.
+    };
+
+    pulse_out + tnd_out
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ApuChannel {
+    /// A pulse channel with the duty cycle, 0 .. 3 for 12.5%, 25%, 50% and 75%.
+    Pulse1 {
+        duty: u8,
+    },
+    Pulse2 {
+        duty: u8,
+    },
+    Triangle,
+    /// Notes pick one of the 16 noise periods.
+    Noise {
+        short: bool,
+    },
+}
+
+/// Plays notes on one channel of its own `Apu` by writing registers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApuVoice {
+    apu: Apu,
+    channel: ApuChannel,
+    note: Option<u8>,
+}
+
+impl ApuVoice {
+    pub fn new(channel: ApuChannel, sample_rate: f32) -> Self {
+        let mut apu = Apu::new(sample_rate);
+        apu.write(0x4015, 0x0F);
+        Self {
+            apu,
+            channel,
+            note: None,
+        }
+    }
+
+    /// The register the channel's registers start at.
+    fn base(&self) -> u16 {
+        match self.channel {
+            ApuChannel::Pulse1 { .. } => 0x4000,
+            ApuChannel::Pulse2 { .. } => 0x4004,
+            ApuChannel::Triangle => 0x4008,
+            ApuChannel::Noise { .. } => 0x400C,
+        }
+    }
+}
+
+impl Voice for ApuVoice {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        let base = self.base();
+        let frequency = midi_to_hz(note as f32) as f64;
+        let volume = (velocity.clamp(0., 1.) * 15.).round() as u8;
+        // Length counters are halted so notes last until released
+        let length = 0x08;
+
+        match self.channel {
+            ApuChannel::Pulse1 { duty } | ApuChannel::Pulse2 { duty } => {
+                let timer = (CPU_CLOCK / (16. * frequency) - 1.)
+                    .round()
+                    .clamp(8., 2047.) as u16;
+                self.apu.write(base, (duty & 0x03) << 6 | 0x30 | volume);
+                self.apu.write(base + 1, 0x00);
+                self.apu.write(base + 2, timer as u8);
+                self.apu.write(base + 3, length | (timer >> 8) as u8);
+            }
+            ApuChannel::Triangle => {
+                let timer = (CPU_CLOCK / (32. * frequency) - 1.)
+                    .round()
+                    .clamp(2., 2047.) as u16;
+                self.apu.write(base, 0xFF);
+                self.apu.write(base + 2, timer as u8);
+                self.apu.write(base + 3, length | (timer >> 8) as u8);
+            }
+            ApuChannel::Noise { short } => {
+                // Higher notes use shorter periods
+                let period = 15 - (note % 16);
+                self.apu.write(base, 0x30 | volume);
+                self.apu
+                    .write(base + 2, if short { 0x80 } else { 0 } | period);
+                self.apu.write(base + 3, length);
+            }
+        }
+
+        self.note = Some(note);
+    }
+
+    fn note_off(&mut self, note: u8) {
+        if self.note != Some(note) {
+            return;
+        }
+
+        self.note = None;
+        match self.channel {
+            ApuChannel::Triangle => self.apu.write(0x4008, 0x00),
+            channel => {
+                let duty = match channel {
+                    ApuChannel::Pulse1 { duty } | ApuChannel::Pulse2 { duty } => duty,
+                    _ => 0,
+                };
+                self.apu.write(self.base(), (duty & 0x03) << 6 | 0x30);
+            }
+        }
+    }
+
+    fn tick(&mut self) -> f32 {
+        self.apu.tick()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::zero_crossing_pitch,
+        chip::{render, RegisterWrite},
+    };
+    use alloc::{vec, vec::Vec};
+
+    const SAMPLE_RATE: f32 = 48000.;
+
+    /// A 50% pulse at constant volume 15 with its length counter halted.
+    fn pulse_440() -> Vec<RegisterWrite> {
+        vec![
+            RegisterWrite::new(0., 0x4015, 0x01),
+            RegisterWrite::new(0., 0x4000, 0xBF),
+            RegisterWrite::new(0., 0x4002, 253),
+            RegisterWrite::new(0., 0x4003, 0x08),
+        ]
+    }
+
+    #[test]
+    fn pulse_plays_timer_frequency() {
+        let samples = render(&mut Apu::new(SAMPLE_RATE), &pulse_440(), 1., SAMPLE_RATE);
+
+        // 1789773 / (16 * 254) = 440.4 Hz
+        let pitch = zero_crossing_pitch(&samples, SAMPLE_RATE).unwrap();
+        assert!((pitch - 440.4).abs() < 0.5, "{}", pitch);
+    }
+
+    #[test]
+    fn writes_at_nan_times_are_skipped() {
+        // A negative NaN sorts before every other time
+        let mut writes = vec![RegisterWrite::new(-f32::NAN, 0x4000, 0x30)];
+        writes.extend(pulse_440());
+        let samples = render(&mut Apu::new(SAMPLE_RATE), &writes, 1., SAMPLE_RATE);
+
+        let pitch = zero_crossing_pitch(&samples, SAMPLE_RATE).unwrap();
+        assert!((pitch - 440.4).abs() < 0.5, "{}", pitch);
+    }
+
+    #[test]
+    fn length_counter_silences_channel() {
+        let mut writes = pulse_440();
+        // Constant volume without halting, and a length of 2 half frames
+        writes[1] = RegisterWrite::new(0., 0x4000, 0x9F);
+        writes[3] = RegisterWrite::new(0., 0x4003, 0x18);
+
+        let mut apu = Apu::new(SAMPLE_RATE);
+        render(&mut apu, &writes, 0.05, SAMPLE_RATE);
+
+        assert_eq!(0, apu.pulses[0].length);
+        assert_eq!(0, apu.pulses[0].tick(SAMPLE_RATE as f64));
+    }
+
+    #[test]
+    fn envelope_decays_each_quarter_frame() {
+        let mut writes = pulse_440();
+        // Envelope with a divider period of 0, looping
+        writes[1] = RegisterWrite::new(0., 0x4000, 0xA0);
+
+        let mut apu = Apu::new(SAMPLE_RATE);
+        render(&mut apu, &writes, 1.5 / 240., SAMPLE_RATE);
+        assert_eq!(15, apu.pulses[0].envelope.volume());
+        render(&mut apu, &[], 5. / 240., SAMPLE_RATE);
+        assert_eq!(10, apu.pulses[0].envelope.volume());
+        render(&mut apu, &[], 11. / 240., SAMPLE_RATE);
+        assert_eq!(15, apu.pulses[0].envelope.volume());
+    }
+
+    #[test]
+    fn sweep_raises_pitch() {
+        let mut writes = pulse_440();
+        // Sweep down by timer >> 3 every half frame
+        writes.push(RegisterWrite::new(0., 0x4001, 0x8B));
+
+        let mut apu = Apu::new(SAMPLE_RATE);
+        render(&mut apu, &writes, 0.1, SAMPLE_RATE);
+
+        assert!(apu.pulses[0].timer < 100, "{}", apu.pulses[0].timer);
+        assert!(apu.pulses[0].timer >= 8);
+    }
+
+    #[test]
+    fn triangle_steps_through_16_levels() {
+        let mut triangle = Triangle {
+            enabled: true,
+            ..Triangle::default()
+        };
+        triangle.write(0, 0xFF);
+        triangle.write(2, 100);
+        triangle.write(3, 0x08);
+        triangle.clock_quarter_frame();
+
+        let mut levels: Vec<u8> = (0..48000).map(|_| triangle.tick(48000.)).collect();
+        levels.sort_unstable();
+        levels.dedup();
+
+        assert_eq!((0..16).collect::<Vec<u8>>(), levels);
+    }
+
+    #[test]
+    fn noise_voice_plays_until_released() {
+        let mut voice = ApuVoice::new(ApuChannel::Noise { short: false }, SAMPLE_RATE);
+        voice.note_on(60, 1.);
+        let playing: Vec<f32> = (0..4800).map(|_| voice.tick()).collect();
+        voice.note_off(60);
+        let released: Vec<f32> = (0..4800).map(|_| voice.tick()).collect();
+
+        let energy = |samples: &[f32]| samples.iter().map(|s| s * s).sum::<f32>();
+        assert!(energy(&playing) > 1.);
+        assert!(energy(&released[2400..]) < 1e-6);
+    }
+
+    #[test]
+    fn pulse_voice_matches_note() {
+        let mut voice = ApuVoice::new(ApuChannel::Pulse2 { duty: 2 }, SAMPLE_RATE);
+        voice.note_on(57, 1.);
+        let samples: Vec<f32> = (0..48000).map(|_| voice.tick()).collect();
+
+        let pitch = zero_crossing_pitch(&samples, SAMPLE_RATE).unwrap();
+        assert!((pitch - 220.).abs() < 0.5, "{}", pitch);
+    }
+}
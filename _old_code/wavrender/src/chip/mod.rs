@@ -0,0 +1,96 @@
+mod apu;
+mod psg;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use alloc::vec::Vec;
+pub use apu::{Apu, ApuChannel, ApuVoice};
+pub use psg::Psg;
+
+/// An emulated sound chip, controlled by writing bytes to its registers.
+pub trait Chip {
+    fn write(&mut self, address: u16, value: u8);
+
+    fn tick(&mut self) -> f32;
+}
+
+/// A register write at a time in seconds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RegisterWrite {
+    pub time: f32,
+    pub address: u16,
+    pub value: u8,
+}
+
+impl RegisterWrite {
+    pub fn new(time: f32, address: u16, value: u8) -> Self {
+        Self {
+            time,
+            address,
+            value,
+        }
+    }
+}
+
+/// Plays a list of register writes, rendering `duration` seconds. Writes at times that aren't
+/// finite are skipped.
+pub fn render<C>(
+    chip: &mut C,
+    writes: &[RegisterWrite],
+    duration: f32,
+    sample_rate: f32,
+) -> Vec<f32>
+where
+    C: Chip + ?Sized,
+{
+    let mut writes: Vec<RegisterWrite> = writes
+        .iter()
+        .filter(|w| w.time.is_finite())
+        .copied()
+        .collect();
+    writes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let frames = (duration * sample_rate).round() as usize;
+    let mut next = 0;
+    (0..frames)
+        .map(|frame| {
+            let time = frame as f32 / sample_rate;
+            while let Some(write) = writes.get(next).filter(|w| w.time <= time) {
+                chip.write(write.address, write.value);
+                next += 1;
+            }
+
+            chip.tick()
+        })
+        .collect()
+}
+
+/// Removes the offset left by chips that only output positive levels, like the coupling
+/// capacitor on real hardware.
+#[derive(Clone, Debug, PartialEq)]
+struct DcBlocker {
+    coefficient: f32,
+    input: f32,
+    output: f32,
+}
+
+impl DcBlocker {
+    fn new(cutoff: f32, sample_rate: f32) -> Self {
+        Self {
+            coefficient: (-2. * core::f32::consts::PI * cutoff / sample_rate).exp(),
+            input: 0.,
+            output: 0.,
+        }
+    }
+
+    /// Starts as if the input had been constant for a long time.
+    fn settled(mut self, input: f32) -> Self {
+        self.input = input;
+        self
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        self.output = input - self.input + self.coefficient * self.output;
+        self.input = input;
+        self.output
+    }
+}
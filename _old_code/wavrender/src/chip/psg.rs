@@ -0,0 +1,184 @@
+use super::Chip;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::oscillator::{NoiseKind, Oscillator};
+
+// https://www.smspower.org/Development/SN76489
+const CLOCK: f64 = 3_579_545.;
+
+/// The TI SN76489 used in the Master System and BBC Micro: three square waves and noise.
+/// Every write goes to the same port, so the address is ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Psg {
+    /// Tone periods for the three square channels, then the noise control bits.
+    registers: [u16; 4],
+    /// Attenuation for each channel in 2 dB steps, where 15 is off.
+    attenuation: [u8; 4],
+    /// The channel and kind of register the last latch byte selected.
+    latched: (usize, bool),
+    phases: [f64; 4],
+    sample_rate: f64,
+}
+
+impl Psg {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            registers: [0; 4],
+            attenuation: [15; 4],
+            latched: (0, false),
+            phases: [0.; 4],
+            sample_rate: sample_rate as f64,
+        }
+    }
+
+    fn tone_frequency(&self, channel: usize) -> f64 {
+        CLOCK / (32. * self.registers[channel].max(1) as f64)
+    }
+
+    /// The oscillator for the noise channel. Periodic noise is a short pulse once every 16 steps.
+    fn noise(&self) -> Oscillator {
+        let shift = self.registers[3] & 0x03;
+        let rate = match shift {
+            3 => self.tone_frequency(2) * 2.,
+            _ => CLOCK / (16. * (32 << shift) as f64),
+        } as f32;
+
+        if self.registers[3] & 0x04 != 0 {
+            Oscillator::Noise {
+                kind: NoiseKind::White,
+                frequency: rate,
+                seed: 0,
+            }
+        } else {
+            Oscillator::Pulse {
+                frequency: rate / 16.,
+                duty: 1. / 16.,
+            }
+        }
+    }
+
+    fn gain(&self, channel: usize) -> f32 {
+        match self.attenuation[channel] {
+            15 => 0.,
+            attenuation => 10_f32.powf(-2. * attenuation as f32 / 20.),
+        }
+    }
+}
+
+impl Chip for Psg {
+    fn write(&mut self, _address: u16, value: u8) {
+        if value & 0x80 != 0 {
+            // Latch byte: 1CCT DDDD
+            let channel = ((value >> 5) & 0x03) as usize;
+            let volume = value & 0x10 != 0;
+            self.latched = (channel, volume);
+            let data = (value & 0x0F) as u16;
+
+            if volume {
+                self.attenuation[channel] = data as u8;
+            } else if channel == 3 {
+                self.registers[3] = data & 0x07;
+                self.phases[3] = 0.;
+            } else {
+                self.registers[channel] = (self.registers[channel] & 0x3F0) | data;
+            }
+        } else {
+            // Data byte: 0-DD DDDD, the high bits of a tone period
+            let (channel, volume) = self.latched;
+            let data = (value & 0x3F) as u16;
+            if volume {
+                self.attenuation[channel] = (data & 0x0F) as u8;
+            } else if channel == 3 {
+                self.registers[3] = data & 0x07;
+                self.phases[3] = 0.;
+            } else {
+                self.registers[channel] = (self.registers[channel] & 0x0F) | (data << 4);
+            }
+        }
+    }
+
+    fn tick(&mut self) -> f32 {
+        let mut output = 0.;
+        for channel in 0..3 {
+            let oscillator = Oscillator::Pulse {
+                frequency: self.tone_frequency(channel) as f32,
+                duty: 0.5,
+            };
+            // Periods of 0 and 1 output a constant level, used for sample playback
+            let value = if self.registers[channel] <= 1 {
+                1.
+            } else {
+                oscillator.sample_phase(self.phases[channel] as f32)
+            };
+            output += value * self.gain(channel);
+            self.phases[channel] =
+                (self.phases[channel] + oscillator.frequency() as f64 / self.sample_rate).fract();
+        }
+
+        let noise = self.noise();
+        output += noise.sample_phase(self.phases[3] as f32) * self.gain(3);
+        self.phases[3] = (self.phases[3] + noise.frequency() as f64 / self.sample_rate)
+            .rem_euclid(noise.period());
+
+        output / 4.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::{peak, zero_crossing_pitch},
+        chip::{render, RegisterWrite},
+    };
+    use alloc::{vec, vec::Vec};
+
+    const SAMPLE_RATE: f32 = 48000.;
+
+    /// Channel 0 at full volume with a tone period of 254.
+    fn tone() -> Vec<RegisterWrite> {
+        vec![
+            RegisterWrite::new(0., 0, 0x80 | 254 & 0x0F),
+            RegisterWrite::new(0., 0, (254 >> 4) as u8),
+            RegisterWrite::new(0., 0, 0x90),
+        ]
+    }
+
+    #[test]
+    fn latch_and_data_bytes_set_tone_period() {
+        let mut psg = Psg::new(SAMPLE_RATE);
+        let samples = render(&mut psg, &tone(), 1., SAMPLE_RATE);
+
+        assert_eq!(254, psg.registers[0]);
+        // 3579545 / (32 * 254) = 440.4 Hz
+        let pitch = zero_crossing_pitch(&samples, SAMPLE_RATE).unwrap();
+        assert!((pitch - 440.4).abs() < 0.5, "{}", pitch);
+        assert_eq!(0.25, peak(&samples));
+    }
+
+    #[test]
+    fn attenuation_is_2db_per_step() {
+        let mut writes = tone();
+        writes.push(RegisterWrite::new(0.5, 0, 0x93));
+        writes.push(RegisterWrite::new(0.75, 0, 0x9F));
+        let samples = render(&mut Psg::new(SAMPLE_RATE), &writes, 1., SAMPLE_RATE);
+
+        let db = 20. * (peak(&samples[24000..36000]) / peak(&samples[..24000])).log10();
+        assert!((db + 6.).abs() < 1e-3);
+        assert_eq!(0., peak(&samples[36000..]));
+    }
+
+    #[test]
+    fn periodic_noise_repeats_every_16_steps() {
+        let writes = [
+            // Periodic noise at the fastest rate, full volume
+            RegisterWrite::new(0., 0, 0xE0),
+            RegisterWrite::new(0., 0, 0xF0),
+        ];
+        let samples = render(&mut Psg::new(SAMPLE_RATE), &writes, 1., SAMPLE_RATE);
+
+        // 3579545 / 512 / 16 = 437 Hz
+        let pitch = zero_crossing_pitch(&samples, SAMPLE_RATE).unwrap();
+        assert!((pitch - 437.).abs() < 0.5, "{}", pitch);
+    }
+}
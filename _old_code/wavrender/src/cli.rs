@@ -0,0 +1,287 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+use wavrender::patch::MAX_DURATION;
+
+pub const USAGE: &str = "\
+usage: wavrender [options] [input] [output.wav]
+
+Renders a patch or a song (.song) to a WAV file. Without an input the built in demo is rendered.
+
+options:
+  -o, --output <path>       where to write the WAV file
+  -d, --duration <seconds>  length to render, replacing the input's own
+  -r, --sample-rate <hz>    sample rate, replacing the input's own
+  -c, --channels <count>    number of channels the mono render is copied to (default 1)
+  -b, --bpm <tempo>         tempo for songs
+  -s, --say <phonemes>      render a voice bark such as ha:+4u:-3 instead of an input
+  -n, --normalize           scale the render so its peak is at full scale
+  -q, --quiet               don't report progress
+  -h, --help                show this message";
+
+/// The highest sample rate, the fastest common converters run at.
+pub const MAX_SAMPLE_RATE: u32 = 768_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    Demo,
+    Patch(PathBuf),
+    Song(PathBuf),
+    /// A phoneme string for a formant voice bark.
+    Bark(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    /// Help was asked for.
+    Help,
+    Usage(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Help => write!(f, "{}", USAGE),
+            CliError::Usage(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub input: Input,
+    pub output: PathBuf,
+    pub duration: Option<f32>,
+    pub sample_rate: Option<u32>,
+    pub channels: u16,
+    pub bpm: Option<f32>,
+    pub normalize: bool,
+    pub quiet: bool,
+}
+
+impl Options {
+    /// Parses the arguments, not including the program name.
+    pub fn parse<I, S>(args: I) -> Result<Self, CliError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut args = args.into_iter().map(Into::into);
+        let mut positional = vec![];
+        let mut output = None;
+        let mut options = Self {
+            input: Input::Demo,
+            output: PathBuf::new(),
+            duration: None,
+            sample_rate: None,
+            channels: 1,
+            bpm: None,
+            normalize: false,
+            quiet: false,
+        };
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') || arg == "-" {
+                positional.push(arg);
+                continue;
+            }
+
+            // Values can follow as the next argument or after `=`
+            let (flag, inline) = match arg.find('=') {
+                Some(index) => (arg[..index].to_string(), Some(arg[index + 1..].to_string())),
+                None => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::Usage(format!("{} needs a value", flag)))
+            };
+
+            match flag.as_str() {
+                "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+                "-d" | "--duration" => {
+                    options.duration = Some(number(&flag, &value()?, |d: &f32| {
+                        (0. ..=MAX_DURATION).contains(d)
+                    })?)
+                }
+                "-r" | "--sample-rate" => {
+                    options.sample_rate = Some(number(&flag, &value()?, |r: &u32| {
+                        (1..=MAX_SAMPLE_RATE).contains(r)
+                    })?)
+                }
+                "-c" | "--channels" => {
+                    options.channels = number(&flag, &value()?, |c: &u16| *c > 0)?
+                }
+                "-b" | "--bpm" => {
+                    options.bpm = Some(number(&flag, &value()?, |b: &f32| {
+                        b.is_finite() && *b > 0.
+                    })?)
+                }
+                "-s" | "--say" => options.input = Input::Bark(value()?),
+                "-n" | "--normalize" => options.normalize = true,
+                "-q" | "--quiet" => options.quiet = true,
+                "-h" | "--help" => return Err(CliError::Help),
+                _ => return Err(CliError::Usage(format!("unknown option {}", flag))),
+            }
+        }
+
+        let mut positional = positional.into_iter().peekable();
+        // A bark has no input file, so its first positional is the output
+        let bark = matches!(options.input, Input::Bark(_));
+        if let Some(path) = positional.peek().filter(|_| bark) {
+            if let Some("patch") | Some("song") =
+                Path::new(path).extension().and_then(|e| e.to_str())
+            {
+                return Err(CliError::Usage(format!(
+                    "--say can't be used with the input {}",
+                    path
+                )));
+            }
+        }
+        if let Some(input) = positional.next_if(|_| !bark) {
+            let path = PathBuf::from(input);
+            options.input = match path.extension().and_then(|e| e.to_str()) {
+                Some("song") => Input::Song(path),
+                _ => Input::Patch(path),
+            };
+        }
+        if let Some(path) = positional.next() {
+            if output.is_some() {
+                return Err(CliError::Usage("the output is given twice".into()));
+            }
+            output = Some(PathBuf::from(path));
+        }
+        if let Some(extra) = positional.next() {
+            return Err(CliError::Usage(format!("unexpected argument {}", extra)));
+        }
+
+        if options.bpm.is_some() && !matches!(options.input, Input::Song(_)) {
+            return Err(CliError::Usage("--bpm only applies to songs".into()));
+        }
+
+        options.output = output.unwrap_or_else(|| match &options.input {
+            Input::Demo => PathBuf::from("sine.wav"),
+            Input::Bark(_) => PathBuf::from("bark.wav"),
+            Input::Patch(path) | Input::Song(path) => default_output(path),
+        });
+
+        Ok(options)
+    }
+}
+
+/// The input's file name with a .wav extension, in the working directory.
+fn default_output(input: &Path) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_else(|| "out".as_ref());
+    PathBuf::from(stem).with_extension("wav")
+}
+
+fn number<T, F>(flag: &str, value: &str, valid: F) -> Result<T, CliError>
+where
+    T: std::str::FromStr,
+    F: Fn(&T) -> bool,
+{
+    value
+        .parse()
+        .ok()
+        .filter(valid)
+        .ok_or_else(|| CliError::Usage(format!("invalid value `{}` for {}", value, flag)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        Options::parse(args.iter().copied())
+    }
+
+    #[test]
+    fn defaults_to_demo() {
+        let options = parse(&[]).unwrap();
+
+        assert_eq!(Input::Demo, options.input);
+        assert_eq!(PathBuf::from("sine.wav"), options.output);
+        assert_eq!(1, options.channels);
+        assert!(!options.normalize);
+    }
+
+    #[test]
+    fn parses_flags_and_positionals() {
+        let options = parse(&[
+            "songs/intro.song",
+            "-d",
+            "12.5",
+            "--sample-rate=48000",
+            "--channels",
+            "2",
+            "--bpm",
+            "140",
+            "-n",
+        ])
+        .unwrap();
+
+        assert_eq!(Input::Song("songs/intro.song".into()), options.input);
+        assert_eq!(PathBuf::from("intro.wav"), options.output);
+        assert_eq!(Some(12.5), options.duration);
+        assert_eq!(Some(48000), options.sample_rate);
+        assert_eq!(2, options.channels);
+        assert_eq!(Some(140.), options.bpm);
+        assert!(options.normalize);
+
+        let options = parse(&["bass.patch", "out.wav"]).unwrap();
+        assert_eq!(Input::Patch("bass.patch".into()), options.input);
+        assert_eq!(PathBuf::from("out.wav"), options.output);
+
+        let options = parse(&["--say", "ha:+4u", "grunt.wav"]).unwrap();
+        assert_eq!(Input::Bark("ha:+4u".into()), options.input);
+        assert_eq!(PathBuf::from("grunt.wav"), options.output);
+        assert_eq!(
+            PathBuf::from("bark.wav"),
+            parse(&["-s", "a"]).unwrap().output
+        );
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let usage = |args: &[&str]| match parse(args) {
+            Err(CliError::Usage(message)) => message,
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!("--duration needs a value", usage(&["--duration"]));
+        assert_eq!("invalid value `0` for -c", usage(&["-c", "0"]));
+        assert_eq!(
+            "invalid value `fast` for --bpm",
+            usage(&["x.song", "--bpm", "fast"])
+        );
+        assert_eq!("invalid value `inf` for -d", usage(&["-d", "inf"]));
+        assert_eq!("invalid value `1e30` for -d", usage(&["-d", "1e30"]));
+        assert_eq!(
+            "invalid value `4000000000` for -r",
+            usage(&["-r", "4000000000"])
+        );
+        assert_eq!(
+            "invalid value `NaN` for --bpm",
+            usage(&["x.song", "--bpm", "NaN"])
+        );
+        assert_eq!(
+            "--bpm only applies to songs",
+            usage(&["x.patch", "--bpm", "90"])
+        );
+        assert_eq!(
+            "--say can't be used with the input fm_bass.patch",
+            usage(&["fm_bass.patch", "--say", "a"])
+        );
+        assert_eq!(
+            "--say can't be used with the input intro.song",
+            usage(&["-s", "a", "intro.song", "out.wav"])
+        );
+        assert_eq!("unknown option --loud", usage(&["--loud"]));
+        assert_eq!("unexpected argument c", usage(&["a", "b", "c"]));
+        assert_eq!(Err(CliError::Help), parse(&["x.patch", "-h"]));
+    }
+}
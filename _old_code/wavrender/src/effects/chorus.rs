@@ -0,0 +1,127 @@
+use super::{blend, DelayLine, Effect};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::modulation::{Lfo, LfoShape, Rate};
+
+/// A delay swept by an LFO. Short delays with feedback make a flanger.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chorus {
+    lfo: Lfo,
+    /// The shortest delay in seconds.
+    delay: f32,
+    /// How far the delay sweeps above the shortest, in seconds.
+    depth: f32,
+    feedback: f32,
+    mix: f32,
+    sample_rate: f32,
+    line: DelayLine,
+}
+
+impl Chorus {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            lfo: Lfo::new(LfoShape::Sine, Rate::Hz(0.8), sample_rate),
+            delay: 0.015,
+            depth: 0.005,
+            feedback: 0.,
+            mix: 0.5,
+            sample_rate,
+            line: DelayLine::new(1),
+        }
+        .with_depth(0.005)
+    }
+
+    pub fn flanger(sample_rate: f32) -> Self {
+        Self::new(sample_rate)
+            .with_delay(0.001)
+            .with_depth(0.003)
+            .with_rate(0.25)
+            .with_feedback(0.6)
+    }
+
+    /// Sets the sweep speed in Hz.
+    pub fn with_rate(mut self, frequency: f32) -> Self {
+        self.lfo.set_rate(Rate::Hz(frequency));
+        self
+    }
+
+    /// Sets the shortest delay, in seconds.
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self.resize();
+        self
+    }
+
+    /// Sets how far the delay sweeps, in seconds.
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self.resize();
+        self
+    }
+
+    /// Sets how much output is fed back, -1 .. 1.
+    pub fn with_feedback(mut self, feedback: f32) -> Self {
+        self.feedback = feedback.clamp(-0.95, 0.95);
+        self
+    }
+
+    pub fn with_mix(mut self, mix: f32) -> Self {
+        self.mix = mix;
+        self
+    }
+
+    fn resize(&mut self) {
+        let longest = (self.delay + self.depth) * self.sample_rate;
+        self.line.reserve(longest.ceil() as usize + 2);
+    }
+}
+
+impl Effect for Chorus {
+    fn process(&mut self, input: f32) -> f32 {
+        let sweep = (self.lfo.tick() + 1.) / 2.;
+        let delay = (self.delay + self.depth * sweep) * self.sample_rate;
+        let delayed = self.line.read(delay);
+        self.line.push(input + delayed * self.feedback);
+
+        blend(input, delayed, self.mix)
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+        self.lfo.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oscillator::Oscillator;
+    use alloc::vec::Vec;
+
+    fn render(mut chorus: Chorus) -> Vec<f32> {
+        let sine = Oscillator::Sine { frequency: 440. };
+        (0..44100)
+            .map(|i| chorus.process(sine.sample(i as f32 / 44100.)))
+            .collect()
+    }
+
+    #[test]
+    fn sweeps_the_delay() {
+        let output = render(Chorus::new(44100.).with_mix(1.));
+
+        // Silent until the shortest delay has passed, then a delayed copy of the input
+        assert!(output[..600].iter().all(|s| *s == 0.));
+        let peak = output[1000..]
+            .iter()
+            .fold(0_f32, |peak, s| peak.max(s.abs()));
+        assert!((peak - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn flanger_feedback_is_stable() {
+        let output = render(Chorus::flanger(44100.));
+        let peak = output.iter().fold(0_f32, |peak, s| peak.max(s.abs()));
+
+        assert!(peak > 0.5 && peak < 4., "{}", peak);
+    }
+}
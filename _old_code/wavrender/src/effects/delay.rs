@@ -0,0 +1,112 @@
+use super::{blend, DelayLine, Effect};
+use crate::envelope::Length;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+/// A feedback delay. Repeats are darkened by a low pass in the feedback path.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delay {
+    time: Length,
+    feedback: f32,
+    mix: f32,
+    damping: f32,
+    bpm: f32,
+    sample_rate: f32,
+    line: DelayLine,
+    delay: f32,
+    filtered: f32,
+}
+
+impl Delay {
+    /// Creates a delay. `feedback` is the level of each repeat relative to the last, 0 .. 1.
+    pub fn new(time: Length, feedback: f32, sample_rate: f32) -> Self {
+        let mut delay = Self {
+            time,
+            feedback,
+            mix: 0.5,
+            damping: 0.,
+            bpm: 120.,
+            sample_rate,
+            line: DelayLine::new(1),
+            delay: 1.,
+            filtered: 0.,
+        };
+        delay.update_time();
+        delay
+    }
+
+    pub fn with_mix(mut self, mix: f32) -> Self {
+        self.mix = mix;
+        self
+    }
+
+    /// Sets how much high frequencies are removed from each repeat, 0 .. 1.
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.damping = damping.clamp(0., 0.99);
+        self
+    }
+
+    /// Sets the tempo used when the time is in beats.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.bpm = bpm;
+        self.update_time();
+    }
+
+    pub fn set_time(&mut self, time: Length) {
+        self.time = time;
+        self.update_time();
+    }
+
+    /// The delay time in samples.
+    pub fn delay_samples(&self) -> f32 {
+        self.delay
+    }
+
+    fn update_time(&mut self) {
+        self.delay = (self.time.seconds(self.bpm) * self.sample_rate).max(1.);
+        self.line.reserve(self.delay.ceil() as usize + 1);
+    }
+}
+
+impl Effect for Delay {
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.line.read(self.delay);
+        self.filtered = delayed + (self.filtered - delayed) * self.damping;
+        self.line.push(input + self.filtered * self.feedback);
+
+        blend(input, delayed, self.mix)
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+        self.filtered = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn repeats_decay_by_feedback() {
+        let mut delay = Delay::new(Length::Seconds(0.1), 0.5, 100.).with_mix(1.);
+        let output: Vec<f32> = (0..31)
+            .map(|i| delay.process(if i == 0 { 1. } else { 0. }))
+            .collect();
+
+        assert_eq!(1., output[10]);
+        assert_eq!(0.5, output[20]);
+        assert_eq!(0.25, output[30]);
+        assert_eq!(1.75, output.iter().sum::<f32>());
+    }
+
+    #[test]
+    fn syncs_to_tempo() {
+        let mut delay = Delay::new(Length::Beats(0.75), 0.3, 1000.);
+        assert_eq!(375., delay.delay_samples());
+
+        delay.set_tempo(60.);
+        assert_eq!(750., delay.delay_samples());
+    }
+}
@@ -0,0 +1,132 @@
+use super::{blend, Effect};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+/// The curve used to distort a signal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// Smooth saturation.
+    Tanh,
+    /// Cuts the signal off at -1 and 1.
+    HardClip,
+    /// Folds the signal back on itself at -1 and 1, adding bright harmonics.
+    Foldback,
+}
+
+/// A waveshaper. The input is multiplied by `drive` before being shaped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distortion {
+    shape: Shape,
+    drive: f32,
+    mix: f32,
+}
+
+impl Distortion {
+    pub fn new(shape: Shape, drive: f32) -> Self {
+        Self {
+            shape,
+            drive,
+            mix: 1.,
+        }
+    }
+
+    pub fn with_mix(mut self, mix: f32) -> Self {
+        self.mix = mix;
+        self
+    }
+}
+
+impl Effect for Distortion {
+    fn process(&mut self, input: f32) -> f32 {
+        let x = input * self.drive;
+        let shaped = match self.shape {
+            Shape::Tanh => x.tanh(),
+            Shape::HardClip => x.clamp(-1., 1.),
+            Shape::Foldback => {
+                // A triangle wave of the input, matching it between -1 and 1
+                let folded = (x + 1.).rem_euclid(4.);
+                if folded < 2. {
+                    folded - 1.
+                } else {
+                    3. - folded
+                }
+            }
+        };
+
+        blend(input, shaped, self.mix)
+    }
+
+    fn reset(&mut self) {}
+}
+
+/// Reduces the bit depth and sample rate for a lo-fi sound.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bitcrusher {
+    bits: u32,
+    downsample: f32,
+    counter: f32,
+    held: f32,
+}
+
+impl Bitcrusher {
+    /// `downsample` is how many samples each value is held for. 1 leaves the rate unchanged.
+    pub fn new(bits: u32, downsample: f32) -> Self {
+        Self {
+            bits: bits.clamp(1, 24),
+            downsample: downsample.max(1.),
+            counter: 0.,
+            held: 0.,
+        }
+    }
+}
+
+impl Effect for Bitcrusher {
+    fn process(&mut self, input: f32) -> f32 {
+        if self.counter <= 0. {
+            self.counter += self.downsample;
+            let levels = (1_u32 << (self.bits - 1)) as f32;
+            self.held = (input.clamp(-1., 1.) * levels).round() / levels;
+        }
+        self.counter -= 1.;
+
+        self.held
+    }
+
+    fn reset(&mut self) {
+        self.counter = 0.;
+        self.held = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    #[test]
+    fn shapes_stay_in_range() {
+        for shape in [Shape::Tanh, Shape::HardClip, Shape::Foldback].iter() {
+            let mut distortion = Distortion::new(*shape, 10.);
+            for i in -100..=100 {
+                let output = distortion.process(i as f32 / 100.);
+                assert!(output.abs() <= 1., "{:?} {}", shape, output);
+            }
+        }
+
+        let mut fold = Distortion::new(Shape::Foldback, 1.);
+        assert_eq!(0.5, fold.process(0.5));
+        assert_eq!(0.5, fold.process(1.5));
+        assert_eq!(-0.5, fold.process(-1.5));
+    }
+
+    #[test]
+    fn bitcrusher_quantizes_and_holds() {
+        let mut crusher = Bitcrusher::new(3, 2.);
+        let output: Vec<f32> = [0.3, 0.9, -0.6, -0.2]
+            .iter()
+            .map(|s| crusher.process(*s))
+            .collect();
+
+        assert_eq!(vec![0.25, 0.25, -0.5, -0.5], output);
+    }
+}
@@ -0,0 +1,138 @@
+use super::Effect;
+use crate::analysis::{db_to_gain, gain_to_db};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+/// A feed-forward compressor. Levels above the threshold are reduced by the ratio.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Compressor {
+    threshold: f32,
+    ratio: f32,
+    knee: f32,
+    makeup: f32,
+    attack: f32,
+    release: f32,
+    reduction: f32,
+}
+
+impl Compressor {
+    /// The threshold is in dB. Attack and release are in seconds.
+    pub fn new(threshold: f32, ratio: f32, attack: f32, release: f32, sample_rate: f32) -> Self {
+        Self {
+            threshold,
+            ratio: ratio.max(1.),
+            knee: 0.,
+            makeup: 0.,
+            attack: smoothing(attack, sample_rate),
+            release: smoothing(release, sample_rate),
+            reduction: 0.,
+        }
+    }
+
+    /// A compressor that reacts instantly and never lets peaks above the ceiling, in dB.
+    pub fn limiter(ceiling: f32, release: f32, sample_rate: f32) -> Self {
+        Self::new(ceiling, f32::INFINITY, 0., release, sample_rate)
+    }
+
+    /// Sets the width in dB of the region around the threshold where compression fades in.
+    pub fn with_knee(mut self, knee: f32) -> Self {
+        self.knee = knee.max(0.);
+        self
+    }
+
+    /// Sets the gain in dB applied after compression.
+    pub fn with_makeup(mut self, makeup: f32) -> Self {
+        self.makeup = makeup;
+        self
+    }
+
+    /// The current gain reduction in dB.
+    pub fn gain_reduction(&self) -> f32 {
+        self.reduction
+    }
+
+    /// How many dB a level is reduced by, before smoothing.
+    fn target_reduction(&self, level: f32) -> f32 {
+        let over = level - self.threshold;
+        let slope = 1. - 1. / self.ratio;
+        if 2. * over <= -self.knee {
+            0.
+        } else if 2. * over < self.knee {
+            slope * (over + self.knee / 2.).powi(2) / (2. * self.knee)
+        } else {
+            slope * over
+        }
+    }
+}
+
+/// The coefficient for a one pole smoother taking `time` seconds.
+fn smoothing(time: f32, sample_rate: f32) -> f32 {
+    if time <= 0. {
+        0.
+    } else {
+        (-1. / (time * sample_rate)).exp()
+    }
+}
+
+impl Effect for Compressor {
+    fn process(&mut self, input: f32) -> f32 {
+        let target = self.target_reduction(gain_to_db(input.abs()));
+        let coefficient = if target > self.reduction {
+            self.attack
+        } else {
+            self.release
+        };
+        self.reduction = target + (self.reduction - target) * coefficient;
+
+        input * db_to_gain(self.makeup - self.reduction)
+    }
+
+    fn reset(&mut self) {
+        self.reduction = 0.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::peak, oscillator::Oscillator};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn reduces_levels_above_threshold_by_ratio() {
+        let mut compressor = Compressor::new(-20., 4., 0.001, 0.5, 44100.);
+        let sine = Oscillator::Sine { frequency: 100. };
+        let output: Vec<f32> = (0..44100)
+            .map(|i| compressor.process(0.5 * sine.sample(i as f32 / 44100.)))
+            .collect();
+
+        // The -6 dB peaks are 14 dB over, so are brought down to -20 + 14 / 4
+        let level = gain_to_db(peak(&output[22050..]));
+        assert!((level + 16.5).abs() < 0.5, "{}", level);
+
+        let mut quiet = Compressor::new(-20., 4., 0.001, 0.5, 44100.);
+        assert_eq!(0.05, quiet.process(0.05));
+    }
+
+    #[test]
+    fn soft_knee_is_continuous() {
+        let compressor = Compressor::new(-20., 4., 0., 0., 44100.).with_knee(6.);
+
+        assert_eq!(0., compressor.target_reduction(-23.));
+        assert!(compressor.target_reduction(-20.) > 0.);
+        assert!((compressor.target_reduction(-17.) - 0.75 * 3.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn limiter_holds_ceiling() {
+        let mut limiter = Compressor::limiter(-6., 0.1, 44100.);
+        let ceiling = db_to_gain(-6.);
+        let sine = Oscillator::Sine { frequency: 220. };
+        let output: Vec<f32> = (0..4410)
+            .map(|i| limiter.process(2. * sine.sample(i as f32 / 44100.)))
+            .collect();
+
+        assert!(peak(&output) <= ceiling + 1e-4);
+        assert!(peak(&output) > ceiling * 0.9);
+    }
+}
@@ -0,0 +1,163 @@
+mod chorus;
+mod delay;
+mod distortion;
+mod dynamics;
+mod reverb;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use alloc::{boxed::Box, vec, vec::Vec};
+pub use chorus::Chorus;
+pub use delay::Delay;
+pub use distortion::{Bitcrusher, Distortion, Shape};
+pub use dynamics::Compressor;
+pub use reverb::Reverb;
+
+/// An audio effect that processes one sample at a time.
+pub trait Effect {
+    fn process(&mut self, input: f32) -> f32;
+
+    /// Clears any internal state, such as delay buffers.
+    fn reset(&mut self);
+}
+
+/// Blends the dry and processed signals. A mix of 1 is fully processed.
+fn blend(dry: f32, wet: f32, mix: f32) -> f32 {
+    dry + (wet - dry) * mix
+}
+
+/// A circular buffer of past samples that can be read at fractional delays.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    write: usize,
+}
+
+impl DelayLine {
+    /// Creates a delay line holding up to `max_delay` samples.
+    pub fn new(max_delay: usize) -> Self {
+        Self {
+            buffer: vec![0.; max_delay.max(1)],
+            write: 0,
+        }
+    }
+
+    /// The longest delay that can be read.
+    pub fn max_delay(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Grows the buffer if it can't hold `max_delay` samples. Growing clears it.
+    pub fn reserve(&mut self, max_delay: usize) {
+        if max_delay > self.buffer.len() {
+            *self = Self::new(max_delay);
+        }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.buffer[self.write] = value;
+        self.write = (self.write + 1) % self.buffer.len();
+    }
+
+    /// Returns the value pushed `delay` samples ago, where 1 is the most recent.
+    /// Fractional delays are linearly interpolated.
+    pub fn read(&self, delay: f32) -> f32 {
+        let delay = delay.max(1.).min(self.buffer.len() as f32);
+        let whole = delay.floor() as usize;
+        let fraction = delay - whole as f32;
+
+        let len = self.buffer.len();
+        let at = |delay: usize| self.buffer[(self.write + len - delay.min(len)) % len];
+        let a = at(whole);
+        let b = at(whole + 1);
+        a + (b - a) * fraction
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.iter_mut().for_each(|s| *s = 0.);
+    }
+}
+
+/// Effects applied one after another, such as on the master bus.
+#[derive(Default)]
+pub struct EffectChain {
+    effects: Vec<Box<dyn Effect>>,
+}
+
+impl EffectChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an effect to the end of the chain.
+    pub fn with<E>(mut self, effect: E) -> Self
+    where
+        E: Effect + 'static,
+    {
+        self.push(effect);
+        self
+    }
+
+    pub fn push<E>(&mut self, effect: E)
+    where
+        E: Effect + 'static,
+    {
+        self.effects.push(Box::new(effect));
+    }
+
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Processes rendered audio in place.
+    pub fn process_buffer(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+impl Effect for EffectChain {
+    fn process(&mut self, input: f32) -> f32 {
+        self.effects
+            .iter_mut()
+            .fold(input, |sample, effect| effect.process(sample))
+    }
+
+    fn reset(&mut self) {
+        self.effects.iter_mut().for_each(|e| e.reset());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_line_reads_fractional_delays() {
+        let mut line = DelayLine::new(4);
+        for value in [1., 2., 3., 4., 5.].iter() {
+            line.push(*value);
+        }
+
+        assert_eq!(5., line.read(1.));
+        assert_eq!(2., line.read(4.));
+        assert_eq!(4.5, line.read(1.5));
+        assert_eq!(2., line.read(10.));
+    }
+
+    #[test]
+    fn chain_applies_effects_in_order() {
+        let mut chain = EffectChain::new()
+            .with(Distortion::new(Shape::HardClip, 4.))
+            .with(Bitcrusher::new(1, 1.));
+        let mut samples = vec![0.1, -0.3, 0.];
+        chain.process_buffer(&mut samples);
+
+        assert_eq!(2, chain.len());
+        assert_eq!(vec![0., -1., 0.], samples);
+    }
+}
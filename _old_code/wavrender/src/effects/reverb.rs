@@ -0,0 +1,155 @@
+use super::{blend, DelayLine, Effect};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use alloc::vec::Vec;
+
+// https://ccrma.stanford.edu/~jos/pasp/Freeverb.html
+// Delay lengths in samples at 44.1 kHz
+const COMBS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASSES: [usize; 4] = [556, 441, 341, 225];
+const INPUT_GAIN: f32 = 0.015;
+
+/// A feedback comb filter with a low pass in the loop.
+#[derive(Clone, Debug, PartialEq)]
+struct Comb {
+    line: DelayLine,
+    filtered: f32,
+}
+
+impl Comb {
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.line.read(self.line.max_delay() as f32);
+        self.filtered = output + (self.filtered - output) * damping;
+        self.line.push(input + self.filtered * feedback);
+        output
+    }
+}
+
+/// A Schroeder allpass, which smears the signal in time without coloring it.
+#[derive(Clone, Debug, PartialEq)]
+struct Allpass {
+    line: DelayLine,
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.line.read(self.line.max_delay() as f32);
+        self.line.push(input + delayed * 0.5);
+        delayed - input
+    }
+}
+
+/// A Freeverb style reverb: parallel combs followed by allpasses in series.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reverb {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+    room_size: f32,
+    damping: f32,
+    mix: f32,
+}
+
+impl Reverb {
+    pub fn new(sample_rate: f32) -> Self {
+        let scale =
+            |length: usize| ((length as f32 * sample_rate / 44100.).round() as usize).max(1);
+        Self {
+            combs: COMBS
+                .iter()
+                .map(|length| Comb {
+                    line: DelayLine::new(scale(*length)),
+                    filtered: 0.,
+                })
+                .collect(),
+            allpasses: ALLPASSES
+                .iter()
+                .map(|length| Allpass {
+                    line: DelayLine::new(scale(*length)),
+                })
+                .collect(),
+            room_size: 0.5,
+            damping: 0.5,
+            mix: 0.3,
+        }
+    }
+
+    /// Sets the length of the tail, 0 .. 1.
+    pub fn with_room_size(mut self, room_size: f32) -> Self {
+        self.room_size = room_size.clamp(0., 1.);
+        self
+    }
+
+    /// Sets how quickly high frequencies die away, 0 .. 1.
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.damping = damping.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_mix(mut self, mix: f32) -> Self {
+        self.mix = mix;
+        self
+    }
+}
+
+impl Effect for Reverb {
+    fn process(&mut self, input: f32) -> f32 {
+        let feedback = 0.7 + self.room_size * 0.28;
+        let damping = self.damping * 0.4;
+
+        let scaled = input * INPUT_GAIN;
+        let mut wet: f32 = self
+            .combs
+            .iter_mut()
+            .map(|comb| comb.process(scaled, feedback, damping))
+            .sum();
+        for allpass in self.allpasses.iter_mut() {
+            wet = allpass.process(wet);
+        }
+
+        blend(input, wet, self.mix)
+    }
+
+    fn reset(&mut self) {
+        for comb in self.combs.iter_mut() {
+            comb.line.clear();
+            comb.filtered = 0.;
+        }
+        for allpass in self.allpasses.iter_mut() {
+            allpass.line.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The energy of the impulse response between two times, in seconds.
+    fn tail_energy(reverb: &mut Reverb, from: f32, to: f32) -> f32 {
+        let sample_rate = 22050.;
+        (0..(to * sample_rate) as usize)
+            .map(|i| reverb.process(if i == 0 { 1. } else { 0. }))
+            .skip((from * sample_rate) as usize)
+            .map(|s| s * s)
+            .sum()
+    }
+
+    #[test]
+    fn tail_decays() {
+        let mut reverb = Reverb::new(22050.).with_mix(1.);
+        let early = tail_energy(&mut reverb, 0., 0.5);
+        reverb.reset();
+        let late = tail_energy(&mut reverb, 2., 2.5);
+
+        assert!(early > 0.);
+        assert!(late < early / 10.);
+    }
+
+    #[test]
+    fn larger_rooms_ring_longer() {
+        let mut small = Reverb::new(22050.).with_room_size(0.1).with_mix(1.);
+        let mut large = Reverb::new(22050.).with_room_size(0.9).with_mix(1.);
+
+        assert!(tail_energy(&mut large, 1., 1.5) > tail_energy(&mut small, 1., 1.5) * 10.);
+    }
+}
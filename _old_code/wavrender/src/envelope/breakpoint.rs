@@ -0,0 +1,350 @@
+use super::{Curve, Gated};
+use crate::map_range;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use alloc::{vec, vec::Vec};
+
+/// How long a segment lasts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Length {
+    Seconds(f32),
+    /// Synced to the tempo of the envelope.
+    Beats(f32),
+}
+
+impl Length {
+    pub fn seconds(&self, bpm: f32) -> f32 {
+        match *self {
+            Length::Seconds(seconds) => seconds,
+            Length::Beats(beats) => beats * 60. / bpm,
+        }
+    }
+}
+
+/// Moves from the current level to `level` over `length`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub level: f32,
+    pub length: Length,
+    pub curve: Curve,
+}
+
+impl Segment {
+    pub fn new(level: f32, length: Length) -> Self {
+        Self {
+            level,
+            length,
+            curve: Curve::Linear,
+        }
+    }
+
+    pub fn with_curve(mut self, curve: Curve) -> Self {
+        self.curve = curve;
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    Idle,
+    Segment(usize),
+    /// Waiting at the sustain point for the gate to close.
+    Hold,
+}
+
+/// An envelope made of any number of segments.
+/// Levels are not limited to 0 .. 1 so the output may drive any parameter, such as a cutoff in Hz.
+pub struct BreakpointEnvelope {
+    segments: Vec<Segment>,
+    /// The segment the envelope holds at the end of while the gate is open.
+    sustain: Option<usize>,
+    /// Segments that repeat while the gate is open, inclusive.
+    loop_points: Option<(usize, usize)>,
+    bpm: f32,
+    sample_rate: f32,
+    gate: bool,
+    state: State,
+    level: f32,
+    start_level: f32,
+    position: u32,
+    length: u32,
+}
+
+impl BreakpointEnvelope {
+    pub fn new(segments: Vec<Segment>, sample_rate: f32) -> Self {
+        Self {
+            segments,
+            sustain: None,
+            loop_points: None,
+            bpm: 120.,
+            sample_rate,
+            gate: false,
+            state: State::Idle,
+            level: 0.,
+            start_level: 0.,
+            position: 0,
+            length: 0,
+        }
+    }
+
+    /// Creates a delay, attack, hold, decay, sustain, release envelope. Times are in seconds.
+    pub fn dahdsr(
+        delay: f32,
+        attack: f32,
+        hold: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+        sample_rate: f32,
+    ) -> Self {
+        let segments = vec![
+            Segment::new(0., Length::Seconds(delay)),
+            Segment::new(1., Length::Seconds(attack)),
+            Segment::new(1., Length::Seconds(hold)),
+            Segment::new(sustain, Length::Seconds(decay)),
+            Segment::new(0., Length::Seconds(release)),
+        ];
+
+        Self::new(segments, sample_rate).with_sustain(3)
+    }
+
+    /// Holds at the end of the given segment until the gate closes.
+    /// The segments after it make up the release.
+    pub fn with_sustain(mut self, segment: usize) -> Self {
+        self.sustain = Some(segment);
+        self
+    }
+
+    /// Repeats the segments from `start` to `end` while the gate is open.
+    /// Without a sustain point, the segments after `end` make up the release.
+    pub fn with_loop(mut self, start: usize, end: usize) -> Self {
+        self.loop_points = Some((start, end));
+        self
+    }
+
+    /// Sets the tempo used by segments with lengths in beats.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.bpm = bpm;
+    }
+
+    /// Returns the segment currently playing.
+    pub fn segment(&self) -> Option<usize> {
+        match self.state {
+            State::Segment(index) => Some(index),
+            State::Hold => self.sustain,
+            State::Idle => None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state != State::Idle
+    }
+
+    /// The most recent value returned by `tick()`.
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// Starts the first segment from the current level.
+    pub fn on(&mut self) {
+        self.gate = true;
+        self.enter(0);
+    }
+
+    /// Jumps to the release segments, starting from the current level.
+    pub fn off(&mut self) {
+        self.gate = false;
+
+        let release = match (self.sustain, self.loop_points) {
+            (Some(sustain), _) => sustain + 1,
+            (None, Some((_, end))) => end + 1,
+            (None, None) => return,
+        };
+
+        match self.state {
+            State::Hold => self.enter(release),
+            State::Segment(index) if index < release => self.enter(release),
+            _ => {}
+        }
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        let mut loops = 0;
+        while let State::Segment(index) = self.state {
+            if self.position < self.length {
+                break;
+            }
+
+            self.level = self.segments[index].level;
+            if self.gate && self.loop_points.map(|(_, end)| end) == Some(index) {
+                // A whole pass through the loop without a sample, so it would never end
+                loops += 1;
+                if loops > 1 {
+                    self.state = State::Hold;
+                    break;
+                }
+            }
+            self.next(index);
+        }
+
+        if let State::Segment(index) = self.state {
+            let segment = self.segments[index];
+            self.position += 1;
+            let progress = segment
+                .curve
+                .apply(self.position as f32 / self.length as f32);
+            self.level = map_range(progress, 0., 1., self.start_level, segment.level);
+        }
+
+        self.level
+    }
+
+    /// Moves on from a finished segment.
+    fn next(&mut self, index: usize) {
+        if self.gate {
+            if let Some((start, end)) = self.loop_points {
+                if index == end {
+                    self.enter(start);
+                    return;
+                }
+            }
+
+            if self.sustain == Some(index) {
+                self.state = State::Hold;
+                return;
+            }
+        }
+
+        self.enter(index + 1);
+    }
+
+    fn enter(&mut self, index: usize) {
+        match self.segments.get(index) {
+            Some(segment) => {
+                let seconds = segment.length.seconds(self.bpm);
+                self.state = State::Segment(index);
+                self.start_level = self.level;
+                self.position = 0;
+                self.length = (seconds * self.sample_rate).round().max(0.) as u32;
+            }
+            None => self.state = State::Idle,
+        }
+    }
+}
+
+impl Gated for BreakpointEnvelope {
+    fn on(&mut self) {
+        BreakpointEnvelope::on(self)
+    }
+
+    fn off(&mut self) {
+        BreakpointEnvelope::off(self)
+    }
+
+    fn tick(&mut self) -> f32 {
+        BreakpointEnvelope::tick(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(envelope: &mut BreakpointEnvelope, count: usize) -> Vec<f32> {
+        (0..count).map(|_| envelope.tick()).collect()
+    }
+
+    #[test]
+    fn dahdsr_stages() {
+        let mut envelope = BreakpointEnvelope::dahdsr(0.2, 0.2, 0.3, 0.2, 0.5, 0.2, 10.);
+        envelope.on();
+
+        assert_eq!(vec![0., 0.], ticks(&mut envelope, 2));
+        assert_eq!(vec![0.5, 1.], ticks(&mut envelope, 2));
+        assert_eq!(vec![1., 1., 1.], ticks(&mut envelope, 3));
+        assert_eq!(vec![0.75, 0.5], ticks(&mut envelope, 2));
+        assert_eq!(vec![0.5; 4], ticks(&mut envelope, 4));
+        assert_eq!(Some(3), envelope.segment());
+
+        envelope.off();
+        assert_eq!(vec![0.25, 0.], ticks(&mut envelope, 2));
+        envelope.tick();
+        assert!(!envelope.is_active());
+    }
+
+    #[test]
+    fn release_during_attack_skips_to_release() {
+        let mut envelope = BreakpointEnvelope::dahdsr(0., 1., 0., 1., 0.5, 0.5, 10.);
+        envelope.on();
+        ticks(&mut envelope, 4);
+        envelope.off();
+
+        assert_eq!(Some(4), envelope.segment());
+        let release = ticks(&mut envelope, 5);
+        assert!((release[0] - 0.32).abs() < 1e-6);
+        assert_eq!(0., release[4]);
+    }
+
+    #[test]
+    fn loops_while_gate_is_open() {
+        let segments = vec![
+            Segment::new(1., Length::Seconds(0.2)),
+            Segment::new(0., Length::Seconds(0.2)),
+            Segment::new(-1., Length::Seconds(0.2)),
+        ];
+        let mut envelope = BreakpointEnvelope::new(segments, 10.).with_loop(0, 1);
+        envelope.on();
+
+        assert_eq!(
+            vec![0.5, 1., 0.5, 0., 0.5, 1., 0.5, 0.],
+            ticks(&mut envelope, 8)
+        );
+
+        envelope.off();
+        assert_eq!(vec![-0.5, -1., -1.], ticks(&mut envelope, 3));
+        assert!(!envelope.is_active());
+    }
+
+    #[test]
+    fn zero_length_loop_holds() {
+        let segments = vec![
+            Segment::new(0.5, Length::Seconds(0.)),
+            Segment::new(0., Length::Seconds(0.2)),
+        ];
+        let mut envelope = BreakpointEnvelope::new(segments, 10.).with_loop(0, 0);
+        envelope.on();
+
+        assert_eq!(vec![0.5; 3], ticks(&mut envelope, 3));
+        assert!(envelope.is_active());
+
+        envelope.off();
+        assert_eq!(vec![0.25, 0.], ticks(&mut envelope, 2));
+    }
+
+    #[test]
+    fn beats_follow_tempo() {
+        let segments = vec![Segment::new(1., Length::Beats(1.))];
+        let mut envelope = BreakpointEnvelope::new(segments, 10.);
+        envelope.set_tempo(120.);
+        envelope.on();
+
+        let samples = ticks(&mut envelope, 5);
+        assert_eq!(1., samples[4]);
+        assert!(samples[3] < 1.);
+    }
+
+    #[test]
+    fn levels_may_be_any_value() {
+        let segments = vec![
+            Segment::new(5000., Length::Seconds(0.)),
+            Segment::new(200., Length::Seconds(0.4)),
+        ];
+        let mut envelope = BreakpointEnvelope::new(segments, 10.);
+        envelope.on();
+
+        assert_eq!(
+            vec![3800., 2600., 1400., 200., 200.],
+            ticks(&mut envelope, 5)
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use crate::map_range;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+mod breakpoint;
+pub use breakpoint::{BreakpointEnvelope, Length, Segment};
+
+//http://hackmeopen.com/2011/12/synth-diy-software-for-generating-adsr-envelopes/
+
+/// The shape of a segment as it moves from its start level to its target.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Curve {
+    Linear,
+    /// Starts slow and speeds up. Larger values are steeper.
+    Exponential(f32),
+    /// Starts fast and slows down. Larger values are steeper.
+    Logarithmic(f32),
+}
+
+impl Curve {
+    /// Maps the progress through a segment, 0 .. 1, to the shaped progress, 0 .. 1.
+    pub fn apply(&self, x: f32) -> f32 {
+        match *self {
+            Curve::Linear => x,
+            Curve::Exponential(steepness) => {
+                if steepness <= 0. {
+                    return x;
+                }
+
+                ((steepness * x).exp() - 1.) / (steepness.exp() - 1.)
+            }
+            Curve::Logarithmic(steepness) => 1. - Curve::Exponential(steepness).apply(1. - x),
+        }
+    }
+}
+
+/// What happens when `on()` is called while the envelope is already running.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Retrigger {
+    /// Attack restarts from 0. May click if the envelope was not silent.
+    Reset,
+    /// Attack starts from the current level.
+    FromCurrent,
+    /// Ignored while the note is held. Otherwise attack starts from the current level.
+    Legato,
+}
+
+/// An envelope that is started and released by a gate.
+pub trait Gated {
+    fn on(&mut self);
+
+    fn off(&mut self);
+
+    fn tick(&mut self) -> f32;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Stage {
+    Off,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+pub struct Envelope {
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    attack_curve: Curve,
+    decay_curve: Curve,
+    release_curve: Curve,
+    retrigger: Retrigger,
+    stage: Stage,
+    level: f32,
+    start_level: f32,
+    position: u32,
+    length: u32,
+    sample_rate: f32,
+}
+
+impl Envelope {
+    pub fn new(attack: f32, decay: f32, sustain: f32, release: f32, sample_rate: f32) -> Self {
+        let sustain = sustain.min(1.);
+
+        Self {
+            attack,
+            decay,
+            sustain,
+            release,
+            attack_curve: Curve::Linear,
+            decay_curve: Curve::Linear,
+            release_curve: Curve::Linear,
+            retrigger: Retrigger::FromCurrent,
+            stage: Stage::Off,
+            level: 0.,
+            start_level: 0.,
+            position: 0,
+            length: 0,
+            sample_rate,
+        }
+    }
+
+    pub fn with_curves(mut self, attack: Curve, decay: Curve, release: Curve) -> Self {
+        self.attack_curve = attack;
+        self.decay_curve = decay;
+        self.release_curve = release;
+        self
+    }
+
+    pub fn with_retrigger(mut self, retrigger: Retrigger) -> Self {
+        self.retrigger = retrigger;
+        self
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// Returns whether the envelope is producing output.
+    pub fn is_active(&self) -> bool {
+        self.stage != Stage::Off
+    }
+
+    /// The most recent value returned by `tick()`.
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    pub fn on(&mut self) {
+        match self.retrigger {
+            Retrigger::Reset => self.level = 0.,
+            Retrigger::FromCurrent => {}
+            Retrigger::Legato => match self.stage {
+                Stage::Attack | Stage::Decay | Stage::Sustain => return,
+                Stage::Off | Stage::Release => {}
+            },
+        }
+
+        self.enter(Stage::Attack);
+    }
+
+    /// Starts the release from the current level.
+    pub fn off(&mut self) {
+        if self.stage != Stage::Release && self.stage != Stage::Off {
+            self.enter(Stage::Release);
+        }
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        // Segments finish once every sample has been produced; this also skips zero length ones.
+        while self.is_segment() && self.position >= self.length {
+            self.level = self.target();
+            let next = match self.stage {
+                Stage::Attack => Stage::Decay,
+                Stage::Decay => Stage::Sustain,
+                _ => Stage::Off,
+            };
+            self.enter(next);
+        }
+
+        self.level = match self.stage {
+            Stage::Off => 0.,
+            Stage::Sustain => self.sustain,
+            Stage::Attack | Stage::Decay | Stage::Release => {
+                self.position += 1;
+                let progress = self
+                    .curve()
+                    .apply(self.position as f32 / self.length as f32);
+                map_range(progress, 0., 1., self.start_level, self.target())
+            }
+        };
+
+        self.level
+    }
+
+    fn enter(&mut self, stage: Stage) {
+        let seconds = match stage {
+            Stage::Attack => self.attack,
+            Stage::Decay => self.decay,
+            Stage::Release => self.release,
+            Stage::Off | Stage::Sustain => 0.,
+        };
+
+        self.stage = stage;
+        self.start_level = self.level;
+        self.position = 0;
+        self.length = (seconds * self.sample_rate).round().max(0.) as u32;
+    }
+
+    fn is_segment(&self) -> bool {
+        match self.stage {
+            Stage::Attack | Stage::Decay | Stage::Release => true,
+            Stage::Off | Stage::Sustain => false,
+        }
+    }
+
+    fn target(&self) -> f32 {
+        match self.stage {
+            Stage::Attack => 1.,
+            Stage::Decay | Stage::Sustain => self.sustain,
+            Stage::Off | Stage::Release => 0.,
+        }
+    }
+
+    fn curve(&self) -> Curve {
+        match self.stage {
+            Stage::Attack => self.attack_curve,
+            Stage::Decay => self.decay_curve,
+            _ => self.release_curve,
+        }
+    }
+}
+
+impl Gated for Envelope {
+    fn on(&mut self) {
+        Envelope::on(self)
+    }
+
+    fn off(&mut self) {
+        Envelope::off(self)
+    }
+
+    fn tick(&mut self) -> f32 {
+        Envelope::tick(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    /// Ten samples a second keeps segment lengths easy to count.
+    fn envelope() -> Envelope {
+        Envelope::new(1., 1., 0.5, 1., 10.)
+    }
+
+    fn ticks(envelope: &mut Envelope, count: usize) -> Vec<f32> {
+        (0..count).map(|_| envelope.tick()).collect()
+    }
+
+    #[test]
+    fn off_until_triggered() {
+        let mut envelope = envelope();
+
+        assert_eq!(vec![0.; 5], ticks(&mut envelope, 5));
+        assert_eq!(Stage::Off, envelope.stage());
+        assert!(!envelope.is_active());
+    }
+
+    #[test]
+    fn attack_reaches_peak_on_last_sample() {
+        let mut envelope = envelope();
+        envelope.on();
+
+        let attack = ticks(&mut envelope, 10);
+
+        assert!((attack[0] - 0.1).abs() < 1e-6);
+        assert_eq!(1., attack[9]);
+        assert_eq!(Stage::Attack, envelope.stage());
+        assert!(envelope.is_active());
+    }
+
+    #[test]
+    fn decay_reaches_sustain_then_holds() {
+        let mut envelope = envelope();
+        envelope.on();
+        ticks(&mut envelope, 10);
+
+        let decay = ticks(&mut envelope, 10);
+        assert!((decay[0] - 0.95).abs() < 1e-6);
+        assert_eq!(0.5, decay[9]);
+        assert_eq!(Stage::Decay, envelope.stage());
+
+        assert_eq!(vec![0.5; 10], ticks(&mut envelope, 10));
+        assert_eq!(Stage::Sustain, envelope.stage());
+    }
+
+    #[test]
+    fn release_reaches_zero_then_turns_off() {
+        let mut envelope = envelope();
+        envelope.on();
+        ticks(&mut envelope, 25);
+        envelope.off();
+        assert_eq!(Stage::Release, envelope.stage());
+
+        let release = ticks(&mut envelope, 10);
+        assert!((release[0] - 0.45).abs() < 1e-6);
+        assert_eq!(0., release[9]);
+
+        envelope.tick();
+        assert_eq!(Stage::Off, envelope.stage());
+        assert!(!envelope.is_active());
+    }
+
+    #[test]
+    fn release_during_attack_starts_from_current_level() {
+        let mut envelope = envelope();
+        envelope.on();
+        ticks(&mut envelope, 3);
+        envelope.off();
+
+        let release = ticks(&mut envelope, 10);
+        assert!((release[0] - 0.27).abs() < 1e-6);
+        assert!(release.windows(2).all(|w| w[1] <= w[0]));
+        assert_eq!(0., release[9]);
+    }
+
+    #[test]
+    fn retrigger_from_current_does_not_jump() {
+        let mut envelope = envelope();
+        envelope.on();
+        ticks(&mut envelope, 25);
+        envelope.off();
+        ticks(&mut envelope, 5);
+        let level = envelope.level();
+
+        envelope.on();
+        assert_eq!(Stage::Attack, envelope.stage());
+        let attack = envelope.tick();
+        assert!(attack > level && attack - level < 0.1);
+    }
+
+    #[test]
+    fn retrigger_reset_restarts_from_zero() {
+        let mut envelope = envelope().with_retrigger(Retrigger::Reset);
+        envelope.on();
+        ticks(&mut envelope, 25);
+
+        envelope.on();
+        assert!((envelope.tick() - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn legato_ignores_retrigger_while_held() {
+        let mut envelope = envelope().with_retrigger(Retrigger::Legato);
+        envelope.on();
+        ticks(&mut envelope, 25);
+
+        envelope.on();
+        assert_eq!(Stage::Sustain, envelope.stage());
+        assert_eq!(0.5, envelope.tick());
+
+        envelope.off();
+        ticks(&mut envelope, 5);
+        envelope.on();
+        assert_eq!(Stage::Attack, envelope.stage());
+    }
+
+    #[test]
+    fn curves_bend_segments() {
+        let linear = Curve::Linear.apply(0.5);
+        let exponential = Curve::Exponential(4.).apply(0.5);
+        let logarithmic = Curve::Logarithmic(4.).apply(0.5);
+
+        assert_eq!(0.5, linear);
+        assert!(exponential < linear);
+        assert!(logarithmic > linear);
+        for curve in [Curve::Exponential(4.), Curve::Logarithmic(4.)].iter() {
+            assert!(curve.apply(0.).abs() < 1e-6);
+            assert!((curve.apply(1.) - 1.).abs() < 1e-6);
+        }
+
+        let mut envelope =
+            envelope().with_curves(Curve::Exponential(4.), Curve::Linear, Curve::Linear);
+        envelope.on();
+        let attack = ticks(&mut envelope, 10);
+        assert!(attack[4] < 0.5);
+        assert_eq!(1., attack[9]);
+    }
+}
@@ -0,0 +1,544 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use core::f32::consts::PI;
+
+//https://www.w3.org/TR/audio-eq-cookbook/
+//https://cytomic.com/files/dsp/SvfLinearTrapOptimised2.pdf
+
+/// A filter that processes one sample at a time.
+pub trait Filter {
+    fn process(&mut self, input: f32) -> f32;
+
+    fn cutoff(&self) -> f32;
+
+    fn set_cutoff(&mut self, frequency: f32);
+
+    fn resonance(&self) -> f32;
+
+    fn set_resonance(&mut self, resonance: f32);
+
+    /// Clears any internal state.
+    fn reset(&mut self);
+}
+
+/// Keeps the cutoff in a range the filters remain stable in.
+fn clamp_cutoff(frequency: f32, sample_rate: f32) -> f32 {
+    frequency.max(1.).min(sample_rate * 0.49)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BiquadKind {
+    LowPass,
+    HighPass,
+    /// Band pass with a peak gain of 0 dB.
+    BandPass,
+    Notch,
+    Peaking {
+        gain_db: f32,
+    },
+    LowShelf {
+        gain_db: f32,
+    },
+    HighShelf {
+        gain_db: f32,
+    },
+}
+
+/// A second order filter using the RBJ cookbook coefficients.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Biquad {
+    kind: BiquadKind,
+    frequency: f32,
+    q: f32,
+    sample_rate: f32,
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    pub fn new(kind: BiquadKind, frequency: f32, q: f32, sample_rate: f32) -> Self {
+        let mut biquad = Self {
+            kind,
+            frequency,
+            q,
+            sample_rate,
+            b0: 1.,
+            b1: 0.,
+            b2: 0.,
+            a1: 0.,
+            a2: 0.,
+            z1: 0.,
+            z2: 0.,
+        };
+
+        biquad.calculate_coefficients();
+        biquad
+    }
+
+    pub fn kind(&self) -> BiquadKind {
+        self.kind
+    }
+
+    pub fn set_kind(&mut self, kind: BiquadKind) {
+        self.kind = kind;
+        self.calculate_coefficients();
+    }
+
+    /// Returns the magnitude of the frequency response at the given frequency.
+    pub fn response(&self, frequency: f32) -> f32 {
+        let w = 2. * PI * frequency / self.sample_rate;
+        let (c1, s1) = (w.cos(), w.sin());
+        let (c2, s2) = ((2. * w).cos(), (2. * w).sin());
+
+        let num_re = self.b0 + self.b1 * c1 + self.b2 * c2;
+        let num_im = -self.b1 * s1 - self.b2 * s2;
+        let den_re = 1. + self.a1 * c1 + self.a2 * c2;
+        let den_im = -self.a1 * s1 - self.a2 * s2;
+
+        ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)).sqrt()
+    }
+
+    fn calculate_coefficients(&mut self) {
+        let frequency = clamp_cutoff(self.frequency, self.sample_rate);
+        let w0 = 2. * PI * frequency / self.sample_rate;
+        let cos = w0.cos();
+        let alpha = w0.sin() / (2. * self.q.max(0.01));
+        let amplitude = |gain_db: f32| 10_f32.powf(gain_db / 40.);
+
+        let (b0, b1, b2, a0, a1, a2) = match self.kind {
+            BiquadKind::LowPass => (
+                (1. - cos) / 2.,
+                1. - cos,
+                (1. - cos) / 2.,
+                1. + alpha,
+                -2. * cos,
+                1. - alpha,
+            ),
+            BiquadKind::HighPass => (
+                (1. + cos) / 2.,
+                -(1. + cos),
+                (1. + cos) / 2.,
+                1. + alpha,
+                -2. * cos,
+                1. - alpha,
+            ),
+            BiquadKind::BandPass => (alpha, 0., -alpha, 1. + alpha, -2. * cos, 1. - alpha),
+            BiquadKind::Notch => (1., -2. * cos, 1., 1. + alpha, -2. * cos, 1. - alpha),
+            BiquadKind::Peaking { gain_db } => {
+                let a = amplitude(gain_db);
+                (
+                    1. + alpha * a,
+                    -2. * cos,
+                    1. - alpha * a,
+                    1. + alpha / a,
+                    -2. * cos,
+                    1. - alpha / a,
+                )
+            }
+            BiquadKind::LowShelf { gain_db } => {
+                let a = amplitude(gain_db);
+                let sqrt = 2. * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.) - (a - 1.) * cos + sqrt),
+                    2. * a * ((a - 1.) - (a + 1.) * cos),
+                    a * ((a + 1.) - (a - 1.) * cos - sqrt),
+                    (a + 1.) + (a - 1.) * cos + sqrt,
+                    -2. * ((a - 1.) + (a + 1.) * cos),
+                    (a + 1.) + (a - 1.) * cos - sqrt,
+                )
+            }
+            BiquadKind::HighShelf { gain_db } => {
+                let a = amplitude(gain_db);
+                let sqrt = 2. * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.) + (a - 1.) * cos + sqrt),
+                    -2. * a * ((a - 1.) + (a + 1.) * cos),
+                    a * ((a + 1.) + (a - 1.) * cos - sqrt),
+                    (a + 1.) - (a - 1.) * cos + sqrt,
+                    2. * ((a - 1.) - (a + 1.) * cos),
+                    (a + 1.) - (a - 1.) * cos - sqrt,
+                )
+            }
+        };
+
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = a1 / a0;
+        self.a2 = a2 / a0;
+    }
+}
+
+impl Filter for Biquad {
+    fn process(&mut self, input: f32) -> f32 {
+        // Transposed direct form II
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+
+    fn cutoff(&self) -> f32 {
+        self.frequency
+    }
+
+    fn set_cutoff(&mut self, frequency: f32) {
+        self.frequency = frequency;
+        self.calculate_coefficients();
+    }
+
+    fn resonance(&self) -> f32 {
+        self.q
+    }
+
+    fn set_resonance(&mut self, resonance: f32) {
+        self.q = resonance;
+        self.calculate_coefficients();
+    }
+
+    fn reset(&mut self) {
+        self.z1 = 0.;
+        self.z2 = 0.;
+    }
+}
+
+/// The output of a state variable filter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SvfMode {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    Peak,
+}
+
+/// All outputs of a state variable filter for a single sample.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SvfOutput {
+    pub low: f32,
+    pub high: f32,
+    pub band: f32,
+}
+
+/// A topology preserving transform state variable filter.
+/// Remains stable while the cutoff and resonance are modulated every sample.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateVariable {
+    mode: SvfMode,
+    cutoff: f32,
+    q: f32,
+    sample_rate: f32,
+    g: f32,
+    k: f32,
+    ic1eq: f32,
+    ic2eq: f32,
+}
+
+impl StateVariable {
+    pub fn new(mode: SvfMode, cutoff: f32, q: f32, sample_rate: f32) -> Self {
+        let mut svf = Self {
+            mode,
+            cutoff,
+            q,
+            sample_rate,
+            g: 0.,
+            k: 0.,
+            ic1eq: 0.,
+            ic2eq: 0.,
+        };
+
+        svf.set_cutoff(cutoff);
+        svf.set_resonance(q);
+        svf
+    }
+
+    pub fn set_mode(&mut self, mode: SvfMode) {
+        self.mode = mode;
+    }
+
+    /// Processes a sample, returning every output of the filter.
+    pub fn process_all(&mut self, input: f32) -> SvfOutput {
+        let a1 = 1. / (1. + self.g * (self.g + self.k));
+        let a2 = self.g * a1;
+        let a3 = self.g * a2;
+
+        let v3 = input - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = 2. * v1 - self.ic1eq;
+        self.ic2eq = 2. * v2 - self.ic2eq;
+
+        SvfOutput {
+            low: v2,
+            band: v1,
+            high: input - self.k * v1 - v2,
+        }
+    }
+}
+
+impl Filter for StateVariable {
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.process_all(input);
+        match self.mode {
+            SvfMode::LowPass => output.low,
+            SvfMode::HighPass => output.high,
+            SvfMode::BandPass => output.band,
+            SvfMode::Notch => output.low + output.high,
+            SvfMode::Peak => output.low - output.high,
+        }
+    }
+
+    fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    fn set_cutoff(&mut self, frequency: f32) {
+        self.cutoff = frequency;
+        self.g = (PI * clamp_cutoff(frequency, self.sample_rate) / self.sample_rate).tan();
+    }
+
+    fn resonance(&self) -> f32 {
+        self.q
+    }
+
+    fn set_resonance(&mut self, resonance: f32) {
+        self.q = resonance;
+        self.k = 1. / resonance.max(0.01);
+    }
+
+    fn reset(&mut self) {
+        self.ic1eq = 0.;
+        self.ic2eq = 0.;
+    }
+}
+
+/// A four pole Moog style ladder low pass with a saturating input.
+/// Resonance goes from 0 .. 1, self oscillating near 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ladder {
+    cutoff: f32,
+    resonance: f32,
+    sample_rate: f32,
+    /// Gain applied before the input saturation.
+    drive: f32,
+    g: f32,
+    stages: [f32; 4],
+}
+
+impl Ladder {
+    pub fn new(cutoff: f32, resonance: f32, sample_rate: f32) -> Self {
+        let mut ladder = Self {
+            cutoff,
+            resonance,
+            sample_rate,
+            drive: 1.,
+            g: 0.,
+            stages: [0.; 4],
+        };
+
+        ladder.set_cutoff(cutoff);
+        ladder
+    }
+
+    pub fn with_drive(mut self, drive: f32) -> Self {
+        self.drive = drive;
+        self
+    }
+}
+
+impl Filter for Ladder {
+    fn process(&mut self, input: f32) -> f32 {
+        let g = self.g / (1. + self.g);
+        let k = 4. * self.resonance.clamp(0., 1.);
+
+        // Solve the zero delay feedback loop for the input to the first stage.
+        let s = self
+            .stages
+            .iter()
+            .fold(0., |sum, s| sum * g + s / (1. + self.g));
+        let u = (input * self.drive - k * s) / (1. + k * g.powi(4));
+        let mut x = u.tanh();
+
+        for stage in self.stages.iter_mut() {
+            let v = (x - *stage) * g;
+            let y = v + *stage;
+            *stage = y + v;
+            x = y;
+        }
+
+        x
+    }
+
+    fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    fn set_cutoff(&mut self, frequency: f32) {
+        self.cutoff = frequency;
+        self.g = (PI * clamp_cutoff(frequency, self.sample_rate) / self.sample_rate).tan();
+    }
+
+    fn resonance(&self) -> f32 {
+        self.resonance
+    }
+
+    fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance;
+    }
+
+    fn reset(&mut self) {
+        self.stages = [0.; 4];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::peak, oscillator::Oscillator};
+    use alloc::vec::Vec;
+    use core::f32::consts::FRAC_1_SQRT_2;
+
+    const SAMPLE_RATE: f32 = 44100.;
+
+    /// Measures the steady state gain of a filter for a sine at the given frequency.
+    fn measured_gain<F: Filter>(filter: &mut F, frequency: f32) -> f32 {
+        filter.reset();
+        let oscillator = Oscillator::Sine { frequency };
+        let output: Vec<f32> = (0..SAMPLE_RATE as usize / 2)
+            .map(|i| filter.process(oscillator.sample(i as f32 / SAMPLE_RATE)))
+            .collect();
+
+        peak(&output[output.len() / 2 + 1..])
+    }
+
+    fn db(gain: f32) -> f32 {
+        20. * gain.log10()
+    }
+
+    #[test]
+    fn biquad_low_pass_is_3db_down_at_cutoff() {
+        let filter = Biquad::new(BiquadKind::LowPass, 1000., FRAC_1_SQRT_2, SAMPLE_RATE);
+
+        assert!((db(filter.response(1000.)) + 3.01).abs() < 0.05);
+        assert!((filter.response(20.) - 1.).abs() < 0.01);
+        assert!(db(filter.response(10000.)) < -35.);
+    }
+
+    #[test]
+    fn biquad_measured_response_matches_calculated() {
+        let kinds = [
+            BiquadKind::LowPass,
+            BiquadKind::HighPass,
+            BiquadKind::BandPass,
+            BiquadKind::Notch,
+            BiquadKind::Peaking { gain_db: 6. },
+            BiquadKind::LowShelf { gain_db: -6. },
+            BiquadKind::HighShelf { gain_db: 6. },
+        ];
+
+        for kind in kinds.iter() {
+            let mut filter = Biquad::new(*kind, 1000., FRAC_1_SQRT_2, SAMPLE_RATE);
+            for frequency in [100., 700., 1000., 3000.].iter() {
+                let measured = measured_gain(&mut filter, *frequency);
+                let calculated = filter.response(*frequency);
+                assert!(
+                    (measured - calculated).abs() < 0.02,
+                    "{:?} at {}: measured {} calculated {}",
+                    kind,
+                    frequency,
+                    measured,
+                    calculated
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn biquad_peaking_and_shelves_reach_gain() {
+        let peaking = Biquad::new(BiquadKind::Peaking { gain_db: 6. }, 1000., 1., SAMPLE_RATE);
+        let low = Biquad::new(
+            BiquadKind::LowShelf { gain_db: -12. },
+            1000.,
+            FRAC_1_SQRT_2,
+            SAMPLE_RATE,
+        );
+        let high = Biquad::new(
+            BiquadKind::HighShelf { gain_db: 12. },
+            1000.,
+            FRAC_1_SQRT_2,
+            SAMPLE_RATE,
+        );
+
+        assert!((db(peaking.response(1000.)) - 6.).abs() < 0.01);
+        assert!((db(low.response(20.)) + 12.).abs() < 0.1);
+        assert!((db(high.response(18000.)) - 12.).abs() < 0.2);
+        assert!(db(high.response(20.)).abs() < 0.1);
+    }
+
+    #[test]
+    fn biquad_notch_removes_center() {
+        let filter = Biquad::new(BiquadKind::Notch, 1000., FRAC_1_SQRT_2, SAMPLE_RATE);
+
+        assert!(filter.response(1000.) < 0.001);
+        assert!(filter.response(100.) > 0.95);
+    }
+
+    #[test]
+    fn state_variable_outputs() {
+        let mut low = StateVariable::new(SvfMode::LowPass, 1000., FRAC_1_SQRT_2, SAMPLE_RATE);
+        let mut high = StateVariable::new(SvfMode::HighPass, 1000., FRAC_1_SQRT_2, SAMPLE_RATE);
+        let mut band = StateVariable::new(SvfMode::BandPass, 1000., FRAC_1_SQRT_2, SAMPLE_RATE);
+
+        assert!((measured_gain(&mut low, 100.) - 1.).abs() < 0.02);
+        assert!(db(measured_gain(&mut low, 1000.)) < -2.5);
+        assert!(db(measured_gain(&mut low, 10000.)) < -35.);
+        assert!(db(measured_gain(&mut high, 100.)) < -35.);
+        assert!((measured_gain(&mut high, 10000.) - 1.).abs() < 0.02);
+        assert!(measured_gain(&mut band, 1000.) > measured_gain(&mut band, 200.));
+        assert!(measured_gain(&mut band, 1000.) > measured_gain(&mut band, 5000.));
+    }
+
+    #[test]
+    fn state_variable_is_stable_under_modulation() {
+        let mut filter = StateVariable::new(SvfMode::LowPass, 1000., 10., SAMPLE_RATE);
+        let noise = Oscillator::Saw { frequency: 110. };
+        let lfo = Oscillator::Sine { frequency: 30. };
+
+        for i in 0..SAMPLE_RATE as usize {
+            let t = i as f32 / SAMPLE_RATE;
+            filter.set_cutoff(5000. + 4900. * lfo.sample(t));
+            filter.set_resonance(0.5 + 9. * (lfo.sample(t * 1.3) + 1.) / 2.);
+            let output = filter.process(noise.sample(t));
+            assert!(output.is_finite() && output.abs() < 100.);
+        }
+    }
+
+    #[test]
+    fn ladder_rolls_off_24db_per_octave() {
+        let mut filter = Ladder::new(500., 0., SAMPLE_RATE).with_drive(0.1);
+
+        let pass = measured_gain(&mut filter, 50.);
+        let two_octaves = measured_gain(&mut filter, 2000.);
+        let three_octaves = measured_gain(&mut filter, 4000.);
+
+        assert!(db(two_octaves / pass) < -40.);
+        let slope = db(three_octaves / two_octaves);
+        assert!(slope < -20. && slope > -28., "{}", slope);
+    }
+
+    #[test]
+    fn ladder_resonance_boosts_cutoff() {
+        let mut flat = Ladder::new(1000., 0., SAMPLE_RATE).with_drive(0.1);
+        let mut resonant = Ladder::new(1000., 0.9, SAMPLE_RATE).with_drive(0.1);
+
+        let flat = measured_gain(&mut flat, 1000.) / measured_gain(&mut flat, 50.);
+        let resonant = measured_gain(&mut resonant, 1000.) / measured_gain(&mut resonant, 50.);
+
+        assert!(resonant > flat * 2.);
+    }
+}
@@ -0,0 +1,700 @@
+//! Formant synthesis for voice barks: a glottal pulse filtered by parallel band passes tuned to
+//! the resonances of the vocal tract, gliding between vowels.
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{
+    analysis::db_to_gain,
+    filter::{Biquad, BiquadKind, Filter},
+    pitch::{midi_to_hz, semitones_to_ratio},
+    rng::Rng,
+    voice::Voice,
+};
+use alloc::{string::String, vec, vec::Vec};
+use core::{f32::consts::PI, fmt};
+
+/// The number of formants each vowel is made of.
+pub const FORMANTS: usize = 5;
+
+/// One resonance of the vocal tract.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Formant {
+    pub frequency: f32,
+    /// The width of the peak in Hz.
+    pub bandwidth: f32,
+    /// The level of the peak, relative to the first formant.
+    pub gain_db: f32,
+}
+
+impl Formant {
+    pub const fn new(frequency: f32, bandwidth: f32, gain_db: f32) -> Self {
+        Self {
+            frequency,
+            bandwidth,
+            gain_db,
+        }
+    }
+
+    fn approach(&mut self, target: Formant, amount: f32) {
+        self.frequency += (target.frequency - self.frequency) * amount;
+        self.bandwidth += (target.bandwidth - self.bandwidth) * amount;
+        self.gain_db += (target.gain_db - self.gain_db) * amount;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Vowel {
+    /// As in "father".
+    A,
+    /// As in "bed".
+    E,
+    /// As in "see".
+    I,
+    /// As in "law".
+    O,
+    /// As in "boot".
+    U,
+}
+
+impl Vowel {
+    /// The formants of an adult male voice.
+    pub fn formants(self) -> [Formant; FORMANTS] {
+        let table = match self {
+            Vowel::A => [
+                (650., 80., 0.),
+                (1080., 90., -6.),
+                (2650., 120., -7.),
+                (2900., 130., -8.),
+                (3250., 140., -22.),
+            ],
+            Vowel::E => [
+                (400., 70., 0.),
+                (1700., 80., -14.),
+                (2600., 100., -12.),
+                (3200., 120., -14.),
+                (3580., 120., -20.),
+            ],
+            Vowel::I => [
+                (290., 40., 0.),
+                (1870., 90., -15.),
+                (2800., 100., -18.),
+                (3250., 120., -20.),
+                (3540., 120., -30.),
+            ],
+            Vowel::O => [
+                (400., 40., 0.),
+                (800., 80., -10.),
+                (2600., 100., -12.),
+                (2800., 120., -12.),
+                (3000., 120., -26.),
+            ],
+            Vowel::U => [
+                (350., 40., 0.),
+                (600., 60., -20.),
+                (2700., 100., -17.),
+                (2900., 120., -14.),
+                (3300., 120., -26.),
+            ],
+        };
+
+        let mut formants = [Formant::new(0., 0., 0.); FORMANTS];
+        for (formant, (frequency, bandwidth, gain_db)) in formants.iter_mut().zip(table.iter()) {
+            *formant = Formant::new(*frequency, *bandwidth, *gain_db);
+        }
+        formants
+    }
+}
+
+/// A sound in a bark.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Phoneme {
+    Vowel(Vowel),
+    /// A hum through closed lips, as in "mm".
+    Hum,
+    /// Unvoiced breath shaped like the vowel that follows, as in "ha".
+    Breath,
+    Pause,
+}
+
+impl Phoneme {
+    /// The formants the phoneme glides to. Breaths and pauses keep the current ones.
+    pub fn formants(self) -> Option<[Formant; FORMANTS]> {
+        match self {
+            Phoneme::Vowel(vowel) => Some(vowel.formants()),
+            Phoneme::Hum => Some([
+                Formant::new(250., 60., 0.),
+                Formant::new(1000., 150., -30.),
+                Formant::new(2200., 200., -36.),
+                Formant::new(2900., 250., -40.),
+                Formant::new(3300., 250., -46.),
+            ]),
+            Phoneme::Breath | Phoneme::Pause => None,
+        }
+    }
+
+    /// The level of the glottal pulse.
+    fn voicing(self) -> f32 {
+        match self {
+            Phoneme::Vowel(_) => 1.,
+            Phoneme::Hum => 0.8,
+            Phoneme::Breath | Phoneme::Pause => 0.,
+        }
+    }
+
+    /// The level of the breath noise.
+    fn aspiration(self) -> f32 {
+        match self {
+            Phoneme::Vowel(_) => 0.02,
+            Phoneme::Hum => 0.,
+            Phoneme::Breath => 0.5,
+            Phoneme::Pause => 0.,
+        }
+    }
+}
+
+/// The pulse of air through the vocal folds, as the Rosenberg model. Returns the derivative of
+/// the flow, which includes the lift in the highs from radiating out of the lips.
+pub struct Glottis {
+    sample_rate: f32,
+    /// The fraction of each period the folds are open, 0 .. 1.
+    open_quotient: f32,
+    phase: f32,
+    previous: f32,
+}
+
+impl Glottis {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            open_quotient: 0.6,
+            phase: 0.,
+            previous: 0.,
+        }
+    }
+
+    /// Lower is pressed and buzzy, higher is breathy and soft.
+    pub fn with_open_quotient(mut self, open_quotient: f32) -> Self {
+        self.open_quotient = open_quotient.clamp(0.05, 1.);
+        self
+    }
+
+    /// The flow through the folds at the given phase, 0 .. 1.
+    fn flow(&self, phase: f32) -> f32 {
+        let opening = self.open_quotient * 2. / 3.;
+        let closing = self.open_quotient / 3.;
+        if phase < opening {
+            0.5 * (1. - (PI * phase / opening).cos())
+        } else if phase < opening + closing {
+            (PI / 2. * (phase - opening) / closing).cos()
+        } else {
+            0.
+        }
+    }
+
+    pub fn tick(&mut self, frequency: f32) -> f32 {
+        let step = frequency / self.sample_rate;
+        self.phase = (self.phase + step).fract();
+        let flow = self.flow(self.phase);
+        let derivative = (flow - self.previous) / step.max(1e-6);
+        self.previous = flow;
+
+        // The steepest slope is at the closure
+        derivative * 2. * self.open_quotient / (3. * PI)
+    }
+}
+
+/// A voice singing vowels through parallel formant filters. Changes of phoneme and pitch glide
+/// over `glide` seconds.
+pub struct FormantVoice {
+    sample_rate: f32,
+    glottis: Glottis,
+    filters: [Biquad; FORMANTS],
+    formants: [Formant; FORMANTS],
+    target: [Formant; FORMANTS],
+    /// Scales every formant frequency: above 1 sounds smaller, below 1 larger.
+    formant_shift: f32,
+    vowel: Vowel,
+    note: Option<u8>,
+    frequency: f32,
+    target_frequency: f32,
+    level: f32,
+    voicing: f32,
+    target_voicing: f32,
+    aspiration: f32,
+    target_aspiration: f32,
+    /// How far values move towards their targets each sample.
+    glide: f32,
+    rng: Rng,
+}
+
+impl FormantVoice {
+    pub fn new(sample_rate: f32) -> Self {
+        let formants = Vowel::A.formants();
+        let filters = formants.map(|f| {
+            Biquad::new(
+                BiquadKind::BandPass,
+                f.frequency,
+                f.frequency / f.bandwidth,
+                sample_rate,
+            )
+        });
+
+        Self {
+            sample_rate,
+            glottis: Glottis::new(sample_rate),
+            filters,
+            formants,
+            target: formants,
+            formant_shift: 1.,
+            vowel: Vowel::A,
+            note: None,
+            frequency: 110.,
+            target_frequency: 110.,
+            level: 1.,
+            voicing: 0.,
+            target_voicing: 0.,
+            aspiration: 0.,
+            target_aspiration: 0.,
+            glide: 0.,
+            rng: Rng::new(0),
+        }
+        .with_glide(0.03)
+    }
+
+    pub fn with_glottis(mut self, glottis: Glottis) -> Self {
+        self.glottis = glottis;
+        self
+    }
+
+    pub fn with_formant_shift(mut self, shift: f32) -> Self {
+        self.formant_shift = shift.max(0.1);
+        self
+    }
+
+    /// The vowel sung by notes.
+    pub fn with_vowel(mut self, vowel: Vowel) -> Self {
+        self.vowel = vowel;
+        self
+    }
+
+    pub fn with_glide(mut self, seconds: f32) -> Self {
+        self.glide = 1. - (-1. / (seconds.max(1e-4) * self.sample_rate)).exp();
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.target_frequency = frequency;
+    }
+
+    /// Glides to the phoneme. The first phoneme after silence starts at its formants.
+    pub fn set_phoneme(&mut self, phoneme: Phoneme) {
+        if let Some(formants) = phoneme.formants() {
+            self.set_formants(formants);
+        }
+        self.target_voicing = phoneme.voicing();
+        self.target_aspiration = phoneme.aspiration();
+    }
+
+    /// Glides to custom formants, keeping the voicing.
+    pub fn set_formants(&mut self, formants: [Formant; FORMANTS]) {
+        self.target = formants;
+        if self.voicing < 1e-3 && self.aspiration < 1e-3 {
+            self.formants = formants;
+        }
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        let glide = self.glide;
+        self.frequency += (self.target_frequency - self.frequency) * glide;
+        self.voicing += (self.target_voicing - self.voicing) * glide;
+        self.aspiration += (self.target_aspiration - self.aspiration) * glide;
+
+        let source =
+            self.voicing * self.glottis.tick(self.frequency) + self.aspiration * self.rng.bipolar();
+
+        let nyquist = self.sample_rate / 2.;
+        let mut output = 0.;
+        for (i, (formant, filter)) in self
+            .formants
+            .iter_mut()
+            .zip(self.filters.iter_mut())
+            .enumerate()
+        {
+            formant.approach(self.target[i], glide);
+            let frequency = formant.frequency * self.formant_shift;
+            if frequency >= nyquist * 0.95 {
+                continue;
+            }
+            filter.set_cutoff(frequency);
+            filter.set_resonance(frequency / (formant.bandwidth * self.formant_shift));
+
+            // Alternating signs stop neighbouring band passes cancelling between their peaks
+            let sign = if i % 2 == 0 { 1. } else { -1. };
+            output += sign * db_to_gain(formant.gain_db) * filter.process(source);
+        }
+
+        output * self.level
+    }
+}
+
+impl Voice for FormantVoice {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        self.set_frequency(midi_to_hz(note as f32));
+        self.note = Some(note);
+        self.level = velocity;
+        self.set_phoneme(Phoneme::Vowel(self.vowel));
+    }
+
+    fn note_off(&mut self, note: u8) {
+        if self.note == Some(note) {
+            self.note = None;
+            self.set_phoneme(Phoneme::Pause);
+        }
+    }
+
+    fn tick(&mut self) -> f32 {
+        FormantVoice::tick(self)
+    }
+}
+
+/// Pitch offsets in semitones at times through a bark, joined by straight lines. Times are in
+/// phoneme units, so a contour stretches with the bark.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Contour {
+    /// Sorted (time, semitones) breakpoints.
+    points: Vec<(f32, f32)>,
+}
+
+impl Contour {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a breakpoint, replacing any at the same time.
+    pub fn with_point(mut self, time: f32, semitones: f32) -> Self {
+        self.points.retain(|(t, _)| *t != time);
+        let index = self.points.iter().take_while(|(t, _)| *t < time).count();
+        self.points.insert(index, (time, semitones));
+        self
+    }
+
+    pub fn semitones_at(&self, time: f32) -> f32 {
+        let first = match self.points.first() {
+            Some(first) => *first,
+            None => return 0.,
+        };
+        if time <= first.0 {
+            return first.1;
+        }
+
+        for pair in self.points.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            if time < end.0 {
+                return crate::map_range(time, start.0, end.0, start.1, end.1);
+            }
+        }
+
+        self.points[self.points.len() - 1].1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FormantError {
+    /// A character that isn't a phoneme, by its index in characters.
+    InvalidPhoneme { position: usize, character: char },
+    /// A pitch mark without a number after its sign.
+    InvalidPitch { position: usize },
+    /// A hold before any phoneme.
+    NothingToHold,
+}
+
+impl fmt::Display for FormantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormantError::InvalidPhoneme {
+                position,
+                character,
+            } => write!(f, "`{}` at {} is not a phoneme", character, position),
+            FormantError::InvalidPitch { position } => {
+                write!(f, "the pitch mark at {} needs a number", position)
+            }
+            FormantError::NothingToHold => write!(f, "`:` must follow a phoneme"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FormantError {}
+
+/// A short utterance, written as a phoneme string:
+///
+/// - `a e i o u` are vowels, `m` hums, `h` breathes and `_` or a space pauses
+/// - `:` holds the previous phoneme for another unit
+/// - `+N` or `-N` sets the pitch N semitones from the base at that point, gliding from the
+///   previous mark, or from the base at the start
+///
+/// `"ha:+4u:-3"` is a breathy "ha" rising four semitones, then an "u" falling to three below.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bark {
+    /// Each phoneme and how many units it lasts.
+    phonemes: Vec<(Phoneme, u32)>,
+    contour: Contour,
+    pitch: f32,
+    unit: f32,
+    formant_shift: f32,
+    seed: u64,
+}
+
+impl Bark {
+    pub fn parse(text: &str) -> Result<Self, FormantError> {
+        let mut phonemes: Vec<(Phoneme, u32)> = vec![];
+        let mut contour = Contour::new().with_point(0., 0.);
+        let units = |phonemes: &[(Phoneme, u32)]| phonemes.iter().map(|p| p.1).sum::<u32>();
+
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let phoneme = match chars[i] {
+                'a' => Phoneme::Vowel(Vowel::A),
+                'e' => Phoneme::Vowel(Vowel::E),
+                'i' => Phoneme::Vowel(Vowel::I),
+                'o' => Phoneme::Vowel(Vowel::O),
+                'u' => Phoneme::Vowel(Vowel::U),
+                'm' => Phoneme::Hum,
+                'h' => Phoneme::Breath,
+                '_' | ' ' => Phoneme::Pause,
+                ':' => {
+                    let last = phonemes.last_mut().ok_or(FormantError::NothingToHold)?;
+                    last.1 += 1;
+                    i += 1;
+                    continue;
+                }
+                '+' | '-' => {
+                    let start = i;
+                    i += 1;
+                    while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                        i += 1;
+                    }
+                    let number: String = chars[start..i].iter().collect();
+                    let semitones = number
+                        .parse::<f32>()
+                        .map_err(|_| FormantError::InvalidPitch { position: start })?;
+                    contour = contour.with_point(units(&phonemes) as f32, semitones);
+                    continue;
+                }
+                character => {
+                    return Err(FormantError::InvalidPhoneme {
+                        position: i,
+                        character,
+                    })
+                }
+            };
+            phonemes.push((phoneme, 1));
+            i += 1;
+        }
+
+        Ok(Self {
+            phonemes,
+            contour,
+            pitch: 110.,
+            unit: 0.12,
+            formant_shift: 1.,
+            seed: 0,
+        })
+    }
+
+    /// The base frequency the contour is relative to.
+    pub fn with_pitch(mut self, frequency: f32) -> Self {
+        self.pitch = frequency;
+        self
+    }
+
+    /// The length of one phoneme, in seconds.
+    pub fn with_unit(mut self, seconds: f32) -> Self {
+        self.unit = seconds;
+        self
+    }
+
+    /// Replaces the contour from the pitch marks.
+    pub fn with_contour(mut self, contour: Contour) -> Self {
+        self.contour = contour;
+        self
+    }
+
+    /// Scales the formants: above 1 for a smaller character, below 1 for a larger one.
+    pub fn with_formant_shift(mut self, shift: f32) -> Self {
+        self.formant_shift = shift;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn phonemes(&self) -> &[(Phoneme, u32)] {
+        &self.phonemes
+    }
+
+    pub fn contour(&self) -> &Contour {
+        &self.contour
+    }
+
+    /// The length of the phonemes, in seconds, not including the release.
+    pub fn duration(&self) -> f32 {
+        self.phonemes.iter().map(|p| p.1).sum::<u32>() as f32 * self.unit
+    }
+
+    /// Renders the bark, with a short release after the last phoneme.
+    pub fn render(&self, sample_rate: f32) -> Vec<f32> {
+        let mut voice = FormantVoice::new(sample_rate)
+            .with_formant_shift(self.formant_shift)
+            .with_seed(self.seed);
+        let unit_frames = self.unit * sample_rate;
+        let release = (0.1 * sample_rate) as usize;
+        let frames = (self.duration() * sample_rate) as usize;
+
+        let mut samples = Vec::with_capacity(frames + release);
+        let mut start = 0;
+        for (index, (phoneme, units)) in self.phonemes.iter().enumerate() {
+            if *phoneme == Phoneme::Breath {
+                let next = self.phonemes[index + 1..]
+                    .iter()
+                    .find_map(|(p, _)| p.formants());
+                if let Some(formants) = next {
+                    voice.set_formants(formants);
+                }
+            }
+            voice.set_phoneme(*phoneme);
+
+            start += units;
+            let end = (start as f32 * unit_frames) as usize;
+            while samples.len() < end {
+                let time = samples.len() as f32 / unit_frames;
+                let semitones = self.contour.semitones_at(time);
+                voice.set_frequency(self.pitch * semitones_to_ratio(semitones));
+                samples.push(voice.tick());
+            }
+        }
+
+        voice.set_phoneme(Phoneme::Pause);
+        samples.extend((0..release).map(|_| voice.tick()));
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{autocorrelation_pitch, peak, rms, Spectrum, Window};
+
+    const SAMPLE_RATE: f32 = 22050.;
+
+    fn sing(vowel: Vowel) -> Vec<f32> {
+        let mut voice = FormantVoice::new(SAMPLE_RATE).with_vowel(vowel);
+        voice.note_on(45, 1.);
+        (0..SAMPLE_RATE as usize / 2)
+            .map(|_| voice.tick())
+            .collect()
+    }
+
+    #[test]
+    fn vowels_have_their_formants() {
+        let power = |samples: &[f32], low, high| {
+            Spectrum::new(&samples[4096..], SAMPLE_RATE, Window::Hann).band_power(low, high)
+        };
+
+        // The second formant is high for "i" and low for "o"
+        let i = sing(Vowel::I);
+        let o = sing(Vowel::O);
+        let ratio = |samples: &[f32]| power(samples, 1700., 2000.) / power(samples, 700., 900.);
+        assert!(ratio(&i) > 100. * ratio(&o));
+
+        assert!(peak(&i) < 1. && rms(&i) > 0.05, "{} {}", peak(&i), rms(&i));
+    }
+
+    #[test]
+    fn legato_note_off_keeps_the_new_note() {
+        let mut voice = FormantVoice::new(SAMPLE_RATE);
+        voice.note_on(45, 1.);
+        voice.note_on(57, 1.);
+        voice.note_off(45);
+        let held: Vec<f32> = (0..SAMPLE_RATE as usize / 2)
+            .map(|_| voice.tick())
+            .collect();
+        assert!(rms(&held[4096..]) > 0.05);
+
+        voice.note_off(57);
+        let released: Vec<f32> = (0..SAMPLE_RATE as usize / 2)
+            .map(|_| voice.tick())
+            .collect();
+        assert!(rms(&released[4096..]) < 1e-3);
+    }
+
+    #[test]
+    fn parses_phoneme_strings() {
+        let bark = Bark::parse("ha+5:_m-2").unwrap();
+        assert_eq!(
+            &[
+                (Phoneme::Breath, 1),
+                (Phoneme::Vowel(Vowel::A), 2),
+                (Phoneme::Pause, 1),
+                (Phoneme::Hum, 1),
+            ],
+            bark.phonemes()
+        );
+        assert_eq!(2.5, bark.contour().semitones_at(1.));
+        assert_eq!(5., bark.contour().semitones_at(2.));
+        assert_eq!(1.5, bark.contour().semitones_at(3.5));
+        assert_eq!(-2., bark.contour().semitones_at(10.));
+        assert!((bark.duration() - 0.6).abs() < 1e-6);
+
+        assert_eq!(
+            Err(FormantError::InvalidPhoneme {
+                position: 2,
+                character: 'x'
+            }),
+            Bark::parse("hax")
+        );
+        assert_eq!(
+            Err(FormantError::InvalidPitch { position: 1 }),
+            Bark::parse("a+")
+        );
+        assert_eq!(Err(FormantError::NothingToHold), Bark::parse(":a"));
+    }
+
+    #[test]
+    fn pitch_follows_the_contour() {
+        let bark = Bark::parse("a::::::")
+            .unwrap()
+            .with_pitch(100.)
+            .with_contour(Contour::new().with_point(2., 0.).with_point(5., 12.));
+        let samples = bark.render(SAMPLE_RATE);
+        let unit = (0.12 * SAMPLE_RATE) as usize;
+        // Seven units and the release
+        assert_eq!(7 * unit + (0.1 * SAMPLE_RATE) as usize, samples.len());
+
+        let pitch = |from: usize| {
+            autocorrelation_pitch(&samples[from..from + unit], SAMPLE_RATE, 60., 400.).unwrap()
+        };
+        assert!((pitch(unit) - 100.).abs() < 3., "{}", pitch(unit));
+        assert!((pitch(5 * unit) - 200.).abs() < 6., "{}", pitch(5 * unit));
+    }
+
+    #[test]
+    fn pauses_are_silent_and_breaths_unvoiced() {
+        let samples = Bark::parse("a__h::").unwrap().render(SAMPLE_RATE);
+        let unit = (0.12 * SAMPLE_RATE) as usize;
+
+        // Past the glide out of the vowel
+        assert!(rms(&samples[2 * unit..3 * unit]) < 1e-3);
+        let breath = &samples[4 * unit..5 * unit];
+        assert!(rms(breath) > 0.01);
+        assert!(autocorrelation_pitch(breath, SAMPLE_RATE, 60., 400.).is_none());
+    }
+}
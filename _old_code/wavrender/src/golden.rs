@@ -0,0 +1,246 @@
+//! Regression tests comparing offline renders against reference WAVs in `golden/`.
+//!
+//! Set `GOLDEN_UPDATE=1` when running the tests to write new references instead of comparing,
+//! after checking that a change in the sound is intended. When a render doesn't match, it and
+//! its difference from the reference are written to `target/golden/` for listening to.
+
+use crate::{
+    analysis::{gain_to_db, Spectrum, Window},
+    output::{OutputFormat, SampleFormat, WavOutput},
+    sample::Sample,
+};
+use std::path::{Path, PathBuf};
+
+/// Renders are made at a low rate to keep the references small.
+const SAMPLE_RATE: f32 = 22050.;
+/// Frames per spectrum when measuring spectral distance, overlapping by half.
+const FRAME: usize = 1024;
+/// Magnitudes below this are treated as equal, so silence doesn't dominate the distance.
+const FLOOR_DB: f32 = -100.;
+
+/// How far a render may drift from its reference.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Tolerance {
+    /// The largest difference allowed in any one sample.
+    max_abs_diff: f32,
+    /// The largest log spectral distance allowed, in dB.
+    spectral_distance: f32,
+}
+
+impl Default for Tolerance {
+    /// Loose enough for floating point differences between platforms, and nothing audible.
+    fn default() -> Self {
+        Self {
+            max_abs_diff: 1e-4,
+            spectral_distance: 0.1,
+        }
+    }
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("golden")
+}
+
+fn failure_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+fn write(path: &Path, samples: &[f32]) {
+    let format = OutputFormat::new(1, SAMPLE_RATE as u32, SampleFormat::Float32);
+    let mut output = WavOutput::create(path, format)
+        .unwrap_or_else(|e| panic!("could not write {}: {}", path.display(), e));
+    output.write_mono(samples).unwrap();
+    output.finalize().unwrap();
+}
+
+fn max_abs_diff(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b.iter())
+        .fold(0_f32, |max, (a, b)| max.max((a - b).abs()))
+}
+
+/// The root mean square difference between the dB magnitude spectra of the two signals, frame
+/// by frame, averaged over frames.
+fn spectral_distance(a: &[f32], b: &[f32]) -> f32 {
+    let length = a.len().min(b.len());
+    let starts: Vec<usize> = (0..length.saturating_sub(FRAME) + 1)
+        .step_by(FRAME / 2)
+        .collect();
+    let db = |samples: &[f32]| -> Vec<f32> {
+        Spectrum::new(samples, SAMPLE_RATE, Window::Hann)
+            .magnitudes()
+            .iter()
+            .map(|m| gain_to_db(*m).max(FLOOR_DB))
+            .collect()
+    };
+
+    let total: f32 = starts
+        .iter()
+        .map(|start| {
+            let end = (start + FRAME).min(length);
+            let (a, b) = (db(&a[*start..end]), db(&b[*start..end]));
+            let squared: f32 = a.iter().zip(b.iter()).map(|(a, b)| (a - b).powi(2)).sum();
+            (squared / a.len() as f32).sqrt()
+        })
+        .sum();
+
+    total / starts.len().max(1) as f32
+}
+
+/// Compares a render against `golden/<name>.wav`, or writes it there when updating.
+fn check(name: &str, samples: &[f32], tolerance: Tolerance) {
+    // The reference is stored as a WAV, which holds -1 .. 1
+    let actual: Vec<f32> = samples.iter().map(|s| s.clamp(-1., 1.)).collect();
+    let reference = golden_dir().join(name).with_extension("wav");
+
+    if std::env::var_os("GOLDEN_UPDATE").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        write(&reference, &actual);
+        return;
+    }
+
+    let expected = match Sample::load(&reference) {
+        Ok(sample) => sample.channel(0).to_vec(),
+        Err(e) => panic!(
+            "could not read {}: {}. Run with GOLDEN_UPDATE=1 to create it.",
+            reference.display(),
+            e
+        ),
+    };
+
+    let mut problems = vec![];
+    if actual.len() != expected.len() {
+        problems.push(format!(
+            "rendered {} samples but the reference has {}",
+            actual.len(),
+            expected.len()
+        ));
+    }
+    let max_diff = max_abs_diff(&actual, &expected);
+    if max_diff > tolerance.max_abs_diff {
+        problems.push(format!(
+            "max abs diff {} ({:.1} dB) is over {}",
+            max_diff,
+            gain_to_db(max_diff),
+            tolerance.max_abs_diff
+        ));
+    }
+    let distance = spectral_distance(&actual, &expected);
+    if distance > tolerance.spectral_distance {
+        problems.push(format!(
+            "spectral distance {} dB is over {} dB",
+            distance, tolerance.spectral_distance
+        ));
+    }
+
+    if problems.is_empty() {
+        return;
+    }
+
+    let length = actual.len().max(expected.len());
+    let at = |samples: &[f32], i: usize| samples.get(i).copied().unwrap_or(0.);
+    let diff: Vec<f32> = (0..length)
+        .map(|i| at(&actual, i) - at(&expected, i))
+        .collect();
+    std::fs::create_dir_all(failure_dir()).unwrap();
+    let actual_path = failure_dir().join(format!("{}.actual.wav", name));
+    let diff_path = failure_dir().join(format!("{}.diff.wav", name));
+    write(&actual_path, &actual);
+    write(&diff_path, &diff);
+
+    panic!(
+        "{} doesn't match {}:\n  {}\nwrote {} and {}",
+        name,
+        reference.display(),
+        problems.join("\n  "),
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        formant::Bark,
+        patch::{Patch, SongFile},
+        physical::{ModalVoice, PluckedString},
+        sfxr::{Category, SfxParams},
+        voice::Voice,
+    };
+
+    fn patch_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("patches")
+            .join(name)
+    }
+
+    /// Keeps the first two seconds, which is enough to catch most changes.
+    fn excerpt(mut samples: Vec<f32>) -> Vec<f32> {
+        samples.truncate(2 * SAMPLE_RATE as usize);
+        samples
+    }
+
+    #[test]
+    fn metrics() {
+        let a: Vec<f32> = (0..4096).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+        assert_eq!(0., max_abs_diff(&a, &a));
+        assert_eq!(0., spectral_distance(&a, &a));
+
+        let louder: Vec<f32> = a.iter().map(|s| s * 2.).collect();
+        assert!((max_abs_diff(&a, &louder) - 0.5).abs() < 1e-6);
+        // Doubling moves every bin above the floor up 6 dB
+        let distance = spectral_distance(&a, &louder);
+        assert!(distance > 1. && distance <= 6.03, "{}", distance);
+    }
+
+    #[test]
+    fn demo() {
+        check("demo", &crate::demo(SAMPLE_RATE, 2.), Tolerance::default());
+    }
+
+    #[test]
+    fn fm_bass_patch() {
+        let patch = Patch::load(patch_path("fm_bass.patch")).unwrap();
+        let samples = excerpt(patch.render(SAMPLE_RATE).unwrap());
+        check("fm_bass", &samples, Tolerance::default());
+    }
+
+    #[test]
+    fn chip_tune_song() {
+        let file = SongFile::load(patch_path("chip_tune.song")).unwrap();
+        let samples = excerpt(file.build(file.bpm(), SAMPLE_RATE).render());
+        check("chip_tune", &samples, Tolerance::default());
+    }
+
+    #[test]
+    fn sfxr_presets() {
+        for category in [Category::Laser, Category::Explosion].iter() {
+            let samples = SfxParams::generate(*category, 1).render(SAMPLE_RATE);
+            check(
+                &format!("sfxr_{}", category.name()),
+                &excerpt(samples),
+                Tolerance::default(),
+            );
+        }
+    }
+
+    #[test]
+    fn bark() {
+        let samples = Bark::parse("ha:+4u:-3_m-5").unwrap().render(SAMPLE_RATE);
+        check("bark", &samples, Tolerance::default());
+    }
+
+    #[test]
+    fn physical_voices() {
+        let mut string = PluckedString::new(SAMPLE_RATE).with_stretch(0.2);
+        let mut bar = ModalVoice::bar(SAMPLE_RATE);
+        string.note_on(52, 0.9);
+        bar.note_on(76, 0.7);
+        let samples: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|_| (string.tick() + bar.tick()) / 2.)
+            .collect();
+
+        check("physical", &samples, Tolerance::default());
+    }
+}
@@ -0,0 +1,418 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{
+    graph::{Context, Node, Parameter, Port},
+    pitch::semitones_to_ratio,
+    rng::Rng,
+    sample::{Interpolation, Sample},
+};
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::f32::consts::PI;
+
+/// The shape each grain fades in and out with.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GrainWindow {
+    Hann,
+    /// Flat in the middle with cosine fades. `ratio` is the fraction of the grain spent
+    /// fading: 0 is rectangular and 1 is Hann.
+    Tukey {
+        ratio: f32,
+    },
+    Triangle,
+    /// A bell curve cut off at 3 standard deviations either side.
+    Gaussian,
+    Rectangular,
+}
+
+impl GrainWindow {
+    /// The level at a point through the grain, 0 .. 1.
+    pub fn value(self, x: f32) -> f32 {
+        if !(0. ..=1.).contains(&x) {
+            return 0.;
+        }
+
+        match self {
+            GrainWindow::Hann => 0.5 - 0.5 * (2. * PI * x).cos(),
+            GrainWindow::Tukey { ratio } => {
+                let fade = ratio.clamp(0., 1.) / 2.;
+                let edge = x.min(1. - x);
+                if edge >= fade {
+                    1.
+                } else {
+                    0.5 - 0.5 * (PI * edge / fade).cos()
+                }
+            }
+            GrainWindow::Triangle => 1. - (2. * x - 1.).abs(),
+            GrainWindow::Gaussian => (-0.5 * (6. * (x - 0.5)).powi(2)).exp(),
+            GrainWindow::Rectangular => 1.,
+        }
+    }
+}
+
+/// One grain reading from the source.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Grain {
+    /// The position in the source, in frames.
+    position: f64,
+    /// Source frames advanced per output sample.
+    step: f64,
+    age: usize,
+    length: usize,
+}
+
+/// Plays many short, overlapping, windowed grains read from a source, for textures and
+/// soundscapes. Grains start at a regular rate, and the same seed always gives the same output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Granulator {
+    source: Arc<Sample>,
+    sample_rate: f32,
+    /// The length of each grain in seconds.
+    grain_size: f32,
+    /// Grains started per second.
+    density: f32,
+    /// Where grains start, 0 .. 1 through the source.
+    position: f32,
+    /// The most a grain's start moves either side of the position, in seconds.
+    position_jitter: f32,
+    /// Transposition of every grain, in semitones.
+    pitch: f32,
+    /// The most a grain's pitch moves either side, in semitones.
+    pitch_jitter: f32,
+    window: GrainWindow,
+    max_grains: usize,
+    seed: u64,
+    rng: Rng,
+    grains: Vec<Grain>,
+    /// Samples until the next grain starts.
+    until_next: f64,
+}
+
+impl Granulator {
+    pub fn new(source: Arc<Sample>, sample_rate: f32) -> Self {
+        Self {
+            source,
+            sample_rate,
+            grain_size: 0.1,
+            density: 20.,
+            position: 0.,
+            position_jitter: 0.,
+            pitch: 0.,
+            pitch_jitter: 0.,
+            window: GrainWindow::Hann,
+            max_grains: 64,
+            seed: 0,
+            rng: Rng::new(0),
+            grains: vec![],
+            until_next: 0.,
+        }
+    }
+
+    /// Granulates a rendered mono buffer at the same sample rate.
+    pub fn from_buffer(samples: Vec<f32>, sample_rate: f32) -> Self {
+        let source = Sample::from_mono(samples, sample_rate as u32);
+        Self::new(Arc::new(source), sample_rate)
+    }
+
+    pub fn with_grain_size(mut self, seconds: f32) -> Self {
+        self.set_grain_size(seconds);
+        self
+    }
+
+    pub fn with_density(mut self, grains_per_second: f32) -> Self {
+        self.set_density(grains_per_second);
+        self
+    }
+
+    pub fn with_position(mut self, position: f32) -> Self {
+        self.set_position(position);
+        self
+    }
+
+    pub fn with_position_jitter(mut self, seconds: f32) -> Self {
+        self.position_jitter = seconds.max(0.);
+        self
+    }
+
+    pub fn with_pitch(mut self, semitones: f32) -> Self {
+        self.set_pitch(semitones);
+        self
+    }
+
+    pub fn with_pitch_jitter(mut self, semitones: f32) -> Self {
+        self.pitch_jitter = semitones.max(0.);
+        self
+    }
+
+    pub fn with_window(mut self, window: GrainWindow) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Limits how many grains play at once. New grains are skipped while at the limit.
+    pub fn with_max_grains(mut self, max_grains: usize) -> Self {
+        self.max_grains = max_grains;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn grain_size(&self) -> f32 {
+        self.grain_size
+    }
+
+    pub fn set_grain_size(&mut self, seconds: f32) {
+        self.grain_size = seconds.max(0.);
+    }
+
+    pub fn density(&self) -> f32 {
+        self.density
+    }
+
+    pub fn set_density(&mut self, grains_per_second: f32) {
+        self.density = grains_per_second.max(0.);
+    }
+
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: f32) {
+        self.position = position.clamp(0., 1.);
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn set_pitch(&mut self, semitones: f32) {
+        self.pitch = semitones;
+    }
+
+    /// Stops every grain and starts the random sequence again from the seed.
+    pub fn reset(&mut self) {
+        self.rng = Rng::new(self.seed);
+        self.grains.clear();
+        self.until_next = 0.;
+    }
+
+    /// Renders the next sample.
+    pub fn tick(&mut self) -> f32 {
+        if self.density > 0. {
+            if self.until_next <= 0. {
+                self.spawn();
+                self.until_next += self.sample_rate as f64 / self.density as f64;
+            }
+            self.until_next -= 1.;
+        }
+
+        let (source, window) = (&self.source, self.window);
+        let mut sum = 0.;
+        for grain in self.grains.iter_mut() {
+            // The last sample is at the end of the window, so grains are symmetric
+            let x = if grain.length > 1 {
+                grain.age as f32 / (grain.length - 1) as f32
+            } else {
+                0.5
+            };
+            let level = window.value(x);
+            sum += level * source.mono_at(grain.position, grain.step, Interpolation::Linear);
+            grain.position += grain.step;
+            grain.age += 1;
+        }
+        self.grains.retain(|g| g.age < g.length);
+
+        // Overlapping grains are mostly uncorrelated, so they add by power
+        let overlap = self.density * self.grain_size;
+        sum / overlap.max(1.).sqrt()
+    }
+
+    pub fn render(&mut self, frames: usize) -> Vec<f32> {
+        (0..frames).map(|_| self.tick()).collect()
+    }
+
+    fn spawn(&mut self) {
+        let length = (self.grain_size * self.sample_rate).round() as usize;
+        // Random values are always drawn so skipped grains don't change later ones
+        let jitter = self.rng.bipolar() * self.position_jitter;
+        let detune = self.rng.bipolar() * self.pitch_jitter;
+        if length == 0 || self.grains.len() >= self.max_grains || self.source.is_empty() {
+            return;
+        }
+
+        let source_rate = self.source.sample_rate() as f64;
+        let frames = self.source.len() as f64;
+        let start = self.position as f64 * frames + jitter as f64 * source_rate;
+        let step =
+            semitones_to_ratio(self.pitch + detune) as f64 * source_rate / self.sample_rate as f64;
+
+        self.grains.push(Grain {
+            position: start.clamp(0., frames - 1.),
+            step,
+            age: 0,
+            length,
+        });
+    }
+}
+
+/// Plays a granulator. The `position` control input is added to the `position` parameter, so
+/// an envelope or LFO can scan through the source.
+pub struct GranularNode {
+    granulator: Granulator,
+}
+
+impl GranularNode {
+    pub fn new(granulator: Granulator) -> Self {
+        Self { granulator }
+    }
+
+    const INPUTS: &'static [Port] = &[Port::control("position", 0.)];
+    const OUTPUTS: &'static [Port] = &[Port::audio("out")];
+    const PARAMETERS: &'static [Parameter] = &[
+        Parameter::new("position", 0.),
+        Parameter::new("density", 20.),
+        Parameter::new("grain size", 0.1),
+        Parameter::new("pitch", 0.),
+    ];
+}
+
+impl Node for GranularNode {
+    fn inputs(&self) -> &[Port] {
+        Self::INPUTS
+    }
+
+    fn outputs(&self) -> &[Port] {
+        Self::OUTPUTS
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        Self::PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> f32 {
+        match index {
+            0 => self.granulator.position(),
+            1 => self.granulator.density(),
+            2 => self.granulator.grain_size(),
+            _ => self.granulator.pitch(),
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.granulator.set_position(value),
+            1 => self.granulator.set_density(value),
+            2 => self.granulator.set_grain_size(value),
+            _ => self.granulator.set_pitch(value),
+        }
+    }
+
+    fn process(&mut self, _context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        let position = self.granulator.position();
+        for (out, offset) in outputs[0].iter_mut().zip(inputs[0].iter()) {
+            self.granulator.set_position(position + offset);
+            *out = self.granulator.tick();
+        }
+        self.granulator.set_position(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::{Spectrum, Window},
+        graph::Graph,
+        oscillator::Oscillator,
+    };
+
+    const SAMPLE_RATE: f32 = 44100.;
+
+    fn sine(frequency: f32, seconds: f32) -> Vec<f32> {
+        let sine = Oscillator::Sine { frequency };
+        (0..(seconds * SAMPLE_RATE) as usize)
+            .map(|i| sine.sample(i as f32 / SAMPLE_RATE))
+            .collect()
+    }
+
+    #[test]
+    fn window_shapes() {
+        assert_eq!(0., GrainWindow::Hann.value(0.));
+        assert_eq!(1., GrainWindow::Hann.value(0.5));
+        assert_eq!(0., GrainWindow::Hann.value(1.2));
+        assert_eq!(0.5, GrainWindow::Triangle.value(0.25));
+        assert!(GrainWindow::Gaussian.value(0.) < 0.02);
+
+        let tukey = GrainWindow::Tukey { ratio: 0.5 };
+        assert_eq!(1., tukey.value(0.25));
+        assert_eq!(1., tukey.value(0.7));
+        assert!((tukey.value(0.125) - 0.5).abs() < 1e-6);
+        for x in [0.1, 0.3, 0.9].iter() {
+            let hann = GrainWindow::Tukey { ratio: 1. }.value(*x);
+            assert!((hann - GrainWindow::Hann.value(*x)).abs() < 1e-6);
+            assert_eq!(1., GrainWindow::Tukey { ratio: 0. }.value(*x));
+        }
+    }
+
+    #[test]
+    fn grains_start_at_the_density() {
+        // Rectangular 10 ms grains of a constant, 10 times a second
+        let mut granulator = Granulator::from_buffer(vec![1.; 44100], SAMPLE_RATE)
+            .with_window(GrainWindow::Rectangular)
+            .with_grain_size(0.01)
+            .with_density(10.);
+        let samples = granulator.render(44100);
+
+        assert!(samples[..441].iter().all(|s| *s == 1.));
+        assert!(samples[441..4410].iter().all(|s| *s == 0.));
+        assert_eq!(4410, samples.iter().filter(|s| **s != 0.).count());
+    }
+
+    #[test]
+    fn grains_are_symmetric_and_end_at_zero() {
+        let mut granulator = Granulator::from_buffer(vec![1.; 44100], SAMPLE_RATE)
+            .with_grain_size(0.01)
+            .with_density(10.);
+        let samples = granulator.render(441);
+
+        assert_eq!(0., samples[0]);
+        assert!(samples[440].abs() < 1e-6, "{}", samples[440]);
+        assert!((samples[220] - 1.).abs() < 1e-6);
+        assert!((samples[100] - samples[340]).abs() < 1e-5);
+    }
+
+    #[test]
+    fn pitch_transposes_grains() {
+        let mut granulator = Granulator::from_buffer(sine(440., 1.), SAMPLE_RATE)
+            .with_grain_size(0.05)
+            .with_density(40.)
+            .with_position(0.2)
+            .with_pitch(12.);
+        let samples = granulator.render(16384);
+        let spectrum = Spectrum::new(&samples, SAMPLE_RATE, Window::Hann);
+
+        assert!((spectrum.peak_frequency() - 880.).abs() < 10.);
+    }
+
+    #[test]
+    fn seed_makes_jitter_repeatable() {
+        let render = |seed: u64| {
+            let mut graph = Graph::new(SAMPLE_RATE);
+            let granulator = Granulator::from_buffer(sine(300., 0.5), SAMPLE_RATE)
+                .with_position(0.5)
+                .with_position_jitter(0.2)
+                .with_pitch_jitter(3.)
+                .with_window(GrainWindow::Tukey { ratio: 0.3 })
+                .with_seed(seed);
+            let node = graph.add(GranularNode::new(granulator));
+            graph.set_output(node, 0).unwrap();
+            graph.render(8192)
+        };
+
+        assert_eq!(render(7), render(7));
+        assert_ne!(render(7), render(8));
+    }
+}
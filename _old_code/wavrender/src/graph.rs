@@ -0,0 +1,1017 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{
+    effects::Effect, envelope::Gated, filter::Filter, operator::Operator, oscillator::Oscillator,
+};
+use alloc::{boxed::Box, vec, vec::Vec};
+
+/// The number of samples rendered per block.
+pub const BLOCK_SIZE: usize = 64;
+
+/// The kind of signal a port carries.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PortKind {
+    /// Audio rate signal, usually -1 .. 1.
+    Audio,
+    /// Slower moving signal such as an envelope or a gain.
+    Control,
+}
+
+/// An input or output of a node.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Port {
+    pub name: &'static str,
+    pub kind: PortKind,
+    /// The value an input takes when nothing is connected to it.
+    pub default: f32,
+}
+
+impl Port {
+    pub const fn audio(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: PortKind::Audio,
+            default: 0.,
+        }
+    }
+
+    pub const fn control(name: &'static str, default: f32) -> Self {
+        Self {
+            name,
+            kind: PortKind::Control,
+            default,
+        }
+    }
+}
+
+/// A value on a node that may be set or automated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: &'static str,
+    pub default: f32,
+}
+
+impl Parameter {
+    pub const fn new(name: &'static str, default: f32) -> Self {
+        Self { name, default }
+    }
+}
+
+/// Information about the block being processed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Context {
+    pub sample_rate: f32,
+    /// The time in seconds of the first sample in the block.
+    pub time: f32,
+    /// The number of samples in the block.
+    pub frames: usize,
+}
+
+impl Context {
+    /// Returns the time in seconds of the given sample in the block.
+    pub fn sample_time(&self, frame: usize) -> f32 {
+        self.time + frame as f32 / self.sample_rate
+    }
+}
+
+/// A unit of processing in the graph.
+pub trait Node {
+    fn inputs(&self) -> &[Port] {
+        &[]
+    }
+
+    fn outputs(&self) -> &[Port];
+
+    fn parameters(&self) -> &[Parameter] {
+        &[]
+    }
+
+    /// Returns the current value of a parameter.
+    fn parameter(&self, index: usize) -> f32 {
+        self.parameters()[index].default
+    }
+
+    fn set_parameter(&mut self, _index: usize, _value: f32) {}
+
+    /// Processes a block. Each input and output holds `context.frames` samples.
+    fn process(&mut self, context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]);
+}
+
+impl<N> Node for Box<N>
+where
+    N: Node + ?Sized,
+{
+    fn inputs(&self) -> &[Port] {
+        (**self).inputs()
+    }
+
+    fn outputs(&self) -> &[Port] {
+        (**self).outputs()
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        (**self).parameters()
+    }
+
+    fn parameter(&self, index: usize) -> f32 {
+        (**self).parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        (**self).set_parameter(index, value);
+    }
+
+    fn process(&mut self, context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        (**self).process(context, inputs, outputs);
+    }
+}
+
+/// A handle to a node in a graph.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, PartialEq)]
+pub enum GraphError {
+    UnknownNode(NodeId),
+    UnknownInput { node: NodeId, input: usize },
+    UnknownOutput { node: NodeId, output: usize },
+    UnknownParameter { node: NodeId, parameter: usize },
+    KindMismatch { output: PortKind, input: PortKind },
+    Cycle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Connection {
+    from: usize,
+    output: usize,
+    to: usize,
+    input: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Modulation {
+    from: usize,
+    output: usize,
+    to: usize,
+    parameter: usize,
+    depth: f32,
+}
+
+struct Automation {
+    node: usize,
+    parameter: usize,
+    /// Sorted (time, value) breakpoints.
+    points: Vec<(f32, f32)>,
+}
+
+impl Automation {
+    fn value(&self, time: f32) -> f32 {
+        let first = self.points[0];
+        if time <= first.0 {
+            return first.1;
+        }
+
+        for pair in self.points.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            if time < end.0 {
+                return crate::map_range(time, start.0, end.0, start.1, end.1);
+            }
+        }
+
+        self.points[self.points.len() - 1].1
+    }
+}
+
+/// A collection of nodes connected by ports, processed in topological order.
+pub struct Graph {
+    sample_rate: f32,
+    frame: u64,
+    nodes: Vec<Box<dyn Node>>,
+    buffers: Vec<Vec<Vec<f32>>>,
+    parameters: Vec<Vec<f32>>,
+    connections: Vec<Connection>,
+    automations: Vec<Automation>,
+    modulations: Vec<Modulation>,
+    order: Vec<usize>,
+    output: Option<(usize, usize)>,
+    scratch: Vec<Vec<f32>>,
+}
+
+impl Graph {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            frame: 0,
+            nodes: vec![],
+            buffers: vec![],
+            parameters: vec![],
+            connections: vec![],
+            automations: vec![],
+            modulations: vec![],
+            order: vec![],
+            output: None,
+            scratch: vec![],
+        }
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// The time in seconds of the next sample to be rendered.
+    pub fn time(&self) -> f32 {
+        (self.frame as f64 / self.sample_rate as f64) as f32
+    }
+
+    /// Adds a node to the graph.
+    pub fn add<N>(&mut self, node: N) -> NodeId
+    where
+        N: Node + 'static,
+    {
+        self.add_boxed(Box::new(node))
+    }
+
+    /// Adds a node whose type is only known at runtime.
+    pub fn add_boxed(&mut self, node: Box<dyn Node>) -> NodeId {
+        let index = self.nodes.len();
+        self.buffers
+            .push(vec![vec![0.; BLOCK_SIZE]; node.outputs().len()]);
+        self.parameters.push(
+            (0..node.parameters().len())
+                .map(|i| node.parameter(i))
+                .collect(),
+        );
+        self.nodes.push(node);
+        self.order.push(index);
+
+        NodeId(index)
+    }
+
+    /// Connects an output of one node to the input of another.
+    /// Multiple outputs connected to the same input are summed.
+    pub fn connect(
+        &mut self,
+        from: NodeId,
+        output: usize,
+        to: NodeId,
+        input: usize,
+    ) -> Result<(), GraphError> {
+        let output_kind = self.node(from)?.outputs().get(output).map(|p| p.kind);
+        let output_kind = output_kind.ok_or(GraphError::UnknownOutput { node: from, output })?;
+        let input_kind = self.node(to)?.inputs().get(input).map(|p| p.kind);
+        let input_kind = input_kind.ok_or(GraphError::UnknownInput { node: to, input })?;
+
+        if output_kind != input_kind {
+            return Err(GraphError::KindMismatch {
+                output: output_kind,
+                input: input_kind,
+            });
+        }
+
+        self.connections.push(Connection {
+            from: from.0,
+            output,
+            to: to.0,
+            input,
+        });
+
+        match self.schedule() {
+            Some(order) => {
+                self.order = order;
+                Ok(())
+            }
+            None => {
+                self.connections.pop();
+                Err(GraphError::Cycle)
+            }
+        }
+    }
+
+    /// Modulates a parameter by the output of another node, so any node may act as a modulation source.
+    /// The first sample of the source each block, multiplied by `depth`, is added to the parameter.
+    pub fn modulate(
+        &mut self,
+        from: NodeId,
+        output: usize,
+        to: NodeId,
+        parameter: usize,
+        depth: f32,
+    ) -> Result<(), GraphError> {
+        if self.node(from)?.outputs().len() <= output {
+            return Err(GraphError::UnknownOutput { node: from, output });
+        }
+        self.parameter_index(to, parameter)?;
+
+        self.modulations.push(Modulation {
+            from: from.0,
+            output,
+            to: to.0,
+            parameter,
+            depth,
+        });
+
+        match self.schedule() {
+            Some(order) => {
+                self.order = order;
+                Ok(())
+            }
+            None => {
+                self.modulations.pop();
+                Err(GraphError::Cycle)
+            }
+        }
+    }
+
+    /// Sets which node output is returned when rendering.
+    pub fn set_output(&mut self, node: NodeId, output: usize) -> Result<(), GraphError> {
+        if self.node(node)?.outputs().len() <= output {
+            return Err(GraphError::UnknownOutput { node, output });
+        }
+
+        self.output = Some((node.0, output));
+        Ok(())
+    }
+
+    /// Sets the value of a parameter. Automation on the parameter takes precedence.
+    pub fn set_parameter(
+        &mut self,
+        node: NodeId,
+        parameter: usize,
+        value: f32,
+    ) -> Result<(), GraphError> {
+        self.parameter_index(node, parameter)?;
+        self.parameters[node.0][parameter] = value;
+        Ok(())
+    }
+
+    /// Adds an automation breakpoint for a parameter.
+    /// Values are linearly interpolated between breakpoints and applied at the start of each block.
+    pub fn automate(
+        &mut self,
+        node: NodeId,
+        parameter: usize,
+        time: f32,
+        value: f32,
+    ) -> Result<(), GraphError> {
+        self.parameter_index(node, parameter)?;
+
+        let existing = self
+            .automations
+            .iter()
+            .position(|a| a.node == node.0 && a.parameter == parameter);
+
+        let automation = match existing {
+            Some(index) => &mut self.automations[index],
+            None => {
+                self.automations.push(Automation {
+                    node: node.0,
+                    parameter,
+                    points: vec![],
+                });
+                self.automations.last_mut().unwrap()
+            }
+        };
+
+        let index = automation.points.partition_point(|p| p.0 <= time);
+        automation.points.insert(index, (time, value));
+
+        Ok(())
+    }
+
+    /// Renders a single block, returning the output of the graph.
+    pub fn process_block(&mut self) -> &[f32] {
+        let context = Context {
+            sample_rate: self.sample_rate,
+            time: self.time(),
+            frames: BLOCK_SIZE,
+        };
+
+        for automation in self.automations.iter() {
+            self.parameters[automation.node][automation.parameter] = automation.value(context.time);
+        }
+
+        for &index in self.order.iter() {
+            let node = &mut self.nodes[index];
+            let buffers = &self.buffers;
+            for (parameter, value) in self.parameters[index].iter().enumerate() {
+                let modulation: f32 = self
+                    .modulations
+                    .iter()
+                    .filter(|m| m.to == index && m.parameter == parameter)
+                    .map(|m| buffers[m.from][m.output][0] * m.depth)
+                    .sum();
+
+                node.set_parameter(parameter, *value + modulation);
+            }
+
+            let ports = node.inputs();
+            self.scratch.resize(ports.len(), vec![0.; BLOCK_SIZE]);
+            for (input, port) in ports.iter().enumerate() {
+                let buffer = &mut self.scratch[input];
+                let mut connected = self
+                    .connections
+                    .iter()
+                    .filter(|c| c.to == index && c.input == input)
+                    .peekable();
+
+                if connected.peek().is_none() {
+                    buffer.iter_mut().for_each(|s| *s = port.default);
+                    continue;
+                }
+
+                buffer.iter_mut().for_each(|s| *s = 0.);
+                for connection in connected {
+                    let source = &self.buffers[connection.from][connection.output];
+                    for (s, v) in buffer.iter_mut().zip(source.iter()) {
+                        *s += v;
+                    }
+                }
+            }
+
+            let inputs: Vec<&[f32]> = self.scratch[..ports.len()].iter().map(|b| &b[..]).collect();
+
+            node.process(&context, &inputs, &mut self.buffers[index]);
+        }
+
+        self.frame += BLOCK_SIZE as u64;
+
+        match self.output {
+            Some((node, output)) => &self.buffers[node][output],
+            None => &[],
+        }
+    }
+
+    /// Renders the given number of samples from the graph output.
+    pub fn render(&mut self, frames: usize) -> Vec<f32> {
+        self.render_with_progress(frames, |_| {})
+    }
+
+    /// Renders like `render`, reporting the fraction done, 0 .. 1, about every tenth of a second
+    /// of audio.
+    pub fn render_with_progress<F>(&mut self, frames: usize, mut progress: F) -> Vec<f32>
+    where
+        F: FnMut(f32),
+    {
+        let interval = (self.sample_rate as usize / 10 / BLOCK_SIZE).max(1);
+        let mut samples = Vec::with_capacity(frames + BLOCK_SIZE);
+        let mut blocks = 0;
+        while samples.len() < frames {
+            let block = self.process_block();
+            if block.is_empty() {
+                samples.resize(frames, 0.);
+            } else {
+                samples.extend_from_slice(block);
+            }
+
+            blocks += 1;
+            if blocks % interval == 0 {
+                progress(samples.len().min(frames) as f32 / frames as f32);
+            }
+        }
+        progress(1.);
+
+        samples.truncate(frames);
+        samples
+    }
+
+    fn node(&self, node: NodeId) -> Result<&dyn Node, GraphError> {
+        self.nodes
+            .get(node.0)
+            .map(|n| n.as_ref())
+            .ok_or(GraphError::UnknownNode(node))
+    }
+
+    fn parameter_index(&self, node: NodeId, parameter: usize) -> Result<(), GraphError> {
+        if self.node(node)?.parameters().len() <= parameter {
+            return Err(GraphError::UnknownParameter { node, parameter });
+        }
+
+        Ok(())
+    }
+
+    /// Topologically sorts the nodes. Returns `None` if there is a cycle.
+    fn schedule(&self) -> Option<Vec<usize>> {
+        let edges: Vec<(usize, usize)> = self
+            .connections
+            .iter()
+            .map(|c| (c.from, c.to))
+            .chain(self.modulations.iter().map(|m| (m.from, m.to)))
+            .collect();
+
+        let mut incoming = vec![0; self.nodes.len()];
+        for (_, to) in edges.iter() {
+            incoming[*to] += 1;
+        }
+
+        let mut ready: Vec<usize> = (0..self.nodes.len())
+            .filter(|i| incoming[*i] == 0)
+            .collect();
+        ready.reverse();
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(index) = ready.pop() {
+            order.push(index);
+            for (_, to) in edges.iter().filter(|(from, _)| *from == index) {
+                incoming[*to] -= 1;
+                if incoming[*to] == 0 {
+                    ready.push(*to);
+                }
+            }
+        }
+
+        if order.len() == self.nodes.len() {
+            Some(order)
+        } else {
+            None
+        }
+    }
+}
+
+const AUDIO_OUT: &[Port] = &[Port::audio("out")];
+const CONTROL_OUT: &[Port] = &[Port::control("out", 0.)];
+
+/// Plays an oscillator. The phase is accumulated so the frequency may be automated.
+pub struct OscillatorNode {
+    oscillator: Oscillator,
+    /// The oscillator's period, found once since searching for it is slow for additive waves.
+    period: f64,
+    phase: f64,
+}
+
+impl OscillatorNode {
+    pub fn new(oscillator: Oscillator) -> Self {
+        Self {
+            period: oscillator.period(),
+            oscillator,
+            phase: 0.,
+        }
+    }
+
+    const PARAMETERS: &'static [Parameter] = &[
+        Parameter::new("frequency", 0.),
+        Parameter::new("position", 0.),
+    ];
+}
+
+impl Node for OscillatorNode {
+    fn outputs(&self) -> &[Port] {
+        AUDIO_OUT
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        Self::PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> f32 {
+        match index {
+            0 => self.oscillator.frequency(),
+            _ => self.oscillator.position(),
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.oscillator.set_frequency(value),
+            _ => self.oscillator.set_position(value),
+        }
+    }
+
+    fn process(&mut self, context: &Context, _inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        let delta = self.oscillator.frequency() as f64 / context.sample_rate as f64;
+        for sample in outputs[0].iter_mut() {
+            *sample = self
+                .oscillator
+                .sample_phase_at(self.phase as f32, context.sample_rate);
+            self.phase = (self.phase + delta).rem_euclid(self.period);
+        }
+    }
+}
+
+/// An envelope driven by the `gate` parameter. The envelope is on while the gate is 0.5 or above.
+pub struct EnvelopeNode<E> {
+    envelope: E,
+    gate: bool,
+}
+
+impl<E> EnvelopeNode<E>
+where
+    E: Gated,
+{
+    pub fn new(envelope: E) -> Self {
+        Self {
+            envelope,
+            gate: false,
+        }
+    }
+
+    const PARAMETERS: &'static [Parameter] = &[Parameter::new("gate", 0.)];
+}
+
+impl<E> Node for EnvelopeNode<E>
+where
+    E: Gated,
+{
+    fn outputs(&self) -> &[Port] {
+        CONTROL_OUT
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        Self::PARAMETERS
+    }
+
+    fn set_parameter(&mut self, _index: usize, value: f32) {
+        let gate = value >= 0.5;
+        if gate && !self.gate {
+            self.envelope.on();
+        } else if !gate && self.gate {
+            self.envelope.off();
+        }
+
+        self.gate = gate;
+    }
+
+    fn process(&mut self, _context: &Context, _inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        for sample in outputs[0].iter_mut() {
+            *sample = self.envelope.tick();
+        }
+    }
+}
+
+/// A FM operator. The `modulator` input is added to the carrier's phase in radians.
+pub struct OperatorNode {
+    operator: Operator,
+    phase: f32,
+}
+
+impl OperatorNode {
+    pub fn new(operator: Operator) -> Self {
+        Self {
+            operator,
+            phase: 0.,
+        }
+    }
+
+    const INPUTS: &'static [Port] = &[Port::audio("modulator")];
+    const PARAMETERS: &'static [Parameter] = &[Parameter::new("frequency", 0.)];
+}
+
+impl Node for OperatorNode {
+    fn inputs(&self) -> &[Port] {
+        Self::INPUTS
+    }
+
+    fn outputs(&self) -> &[Port] {
+        AUDIO_OUT
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        Self::PARAMETERS
+    }
+
+    fn parameter(&self, _index: usize) -> f32 {
+        self.operator.frequency()
+    }
+
+    fn set_parameter(&mut self, _index: usize, value: f32) {
+        self.operator.set_frequency(value);
+    }
+
+    fn process(&mut self, context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        let delta = self.operator.frequency() / context.sample_rate;
+        for (sample, modulator) in outputs[0].iter_mut().zip(inputs[0].iter()) {
+            *sample = self.operator.render_phase(self.phase, *modulator);
+            self.phase = (self.phase + delta).fract();
+        }
+    }
+}
+
+/// Multiplies the input by the `gain` control input and the `gain` parameter.
+pub struct Gain {
+    gain: f32,
+}
+
+impl Gain {
+    pub fn new(gain: f32) -> Self {
+        Self { gain }
+    }
+
+    const INPUTS: &'static [Port] = &[Port::audio("in"), Port::control("gain", 1.)];
+    const PARAMETERS: &'static [Parameter] = &[Parameter::new("gain", 1.)];
+}
+
+impl Node for Gain {
+    fn inputs(&self) -> &[Port] {
+        Self::INPUTS
+    }
+
+    fn outputs(&self) -> &[Port] {
+        AUDIO_OUT
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        Self::PARAMETERS
+    }
+
+    fn parameter(&self, _index: usize) -> f32 {
+        self.gain
+    }
+
+    fn set_parameter(&mut self, _index: usize, value: f32) {
+        self.gain = value;
+    }
+
+    fn process(&mut self, _context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        let samples = inputs[0].iter().zip(inputs[1].iter());
+        for (out, (sample, gain)) in outputs[0].iter_mut().zip(samples) {
+            *out = sample * gain * self.gain;
+        }
+    }
+}
+
+/// Sums a number of audio inputs.
+pub struct Mix {
+    inputs: Vec<Port>,
+    gain: f32,
+}
+
+impl Mix {
+    pub fn new(inputs: usize) -> Self {
+        Self {
+            inputs: vec![Port::audio("in"); inputs],
+            gain: 1.,
+        }
+    }
+
+    const PARAMETERS: &'static [Parameter] = &[Parameter::new("gain", 1.)];
+}
+
+impl Node for Mix {
+    fn inputs(&self) -> &[Port] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[Port] {
+        AUDIO_OUT
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        Self::PARAMETERS
+    }
+
+    fn parameter(&self, _index: usize) -> f32 {
+        self.gain
+    }
+
+    fn set_parameter(&mut self, _index: usize, value: f32) {
+        self.gain = value;
+    }
+
+    fn process(&mut self, _context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        for (frame, out) in outputs[0].iter_mut().enumerate() {
+            *out = inputs.iter().map(|i| i[frame]).sum::<f32>() * self.gain;
+        }
+    }
+}
+
+/// Runs a filter. The `cutoff` control input shifts the cutoff parameter by octaves.
+pub struct FilterNode<F> {
+    filter: F,
+    cutoff: f32,
+}
+
+impl<F> FilterNode<F>
+where
+    F: Filter,
+{
+    pub fn new(filter: F) -> Self {
+        Self {
+            cutoff: filter.cutoff(),
+            filter,
+        }
+    }
+
+    const INPUTS: &'static [Port] = &[Port::audio("in"), Port::control("cutoff", 0.)];
+    const PARAMETERS: &'static [Parameter] = &[
+        Parameter::new("cutoff", 1000.),
+        Parameter::new("resonance", core::f32::consts::FRAC_1_SQRT_2),
+    ];
+}
+
+impl<F> Node for FilterNode<F>
+where
+    F: Filter,
+{
+    fn inputs(&self) -> &[Port] {
+        Self::INPUTS
+    }
+
+    fn outputs(&self) -> &[Port] {
+        AUDIO_OUT
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        Self::PARAMETERS
+    }
+
+    fn parameter(&self, index: usize) -> f32 {
+        match index {
+            0 => self.cutoff,
+            _ => self.filter.resonance(),
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        match index {
+            0 => self.cutoff = value,
+            _ => {
+                if self.filter.resonance() != value {
+                    self.filter.set_resonance(value);
+                }
+            }
+        }
+    }
+
+    fn process(&mut self, _context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        let samples = inputs[0].iter().zip(inputs[1].iter());
+        for (out, (sample, octaves)) in outputs[0].iter_mut().zip(samples) {
+            let cutoff = self.cutoff * 2_f32.powf(*octaves);
+            if self.filter.cutoff() != cutoff {
+                self.filter.set_cutoff(cutoff);
+            }
+
+            *out = self.filter.process(*sample);
+        }
+    }
+}
+
+/// Runs an effect, such as a delay or an `EffectChain`, on its input.
+pub struct EffectNode<E> {
+    effect: E,
+}
+
+impl<E> EffectNode<E>
+where
+    E: Effect,
+{
+    pub fn new(effect: E) -> Self {
+        Self { effect }
+    }
+
+    const INPUTS: &'static [Port] = &[Port::audio("in")];
+}
+
+impl<E> Node for EffectNode<E>
+where
+    E: Effect,
+{
+    fn inputs(&self) -> &[Port] {
+        Self::INPUTS
+    }
+
+    fn outputs(&self) -> &[Port] {
+        AUDIO_OUT
+    }
+
+    fn process(&mut self, _context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        for (out, sample) in outputs[0].iter_mut().zip(inputs[0].iter()) {
+            *out = self.effect.process(*sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Outputs a constant value.
+    struct Constant(f32);
+
+    impl Node for Constant {
+        fn outputs(&self) -> &[Port] {
+            AUDIO_OUT
+        }
+
+        fn process(&mut self, _context: &Context, _inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+            outputs[0].iter_mut().for_each(|s| *s = self.0);
+        }
+    }
+
+    #[test]
+    fn mix_sums_inputs_and_applies_gain() {
+        let mut graph = Graph::new(44100.);
+        let a = graph.add(Constant(0.25));
+        let b = graph.add(Constant(0.5));
+        let mix = graph.add(Mix::new(2));
+        let gain = graph.add(Gain::new(2.));
+        graph.connect(a, 0, mix, 0).unwrap();
+        graph.connect(b, 0, mix, 1).unwrap();
+        graph.connect(mix, 0, gain, 0).unwrap();
+        graph.set_output(gain, 0).unwrap();
+
+        let samples = graph.render(100);
+
+        assert_eq!(100, samples.len());
+        assert!(samples.iter().all(|s| *s == 1.5));
+    }
+
+    #[test]
+    fn connect_rejects_cycles() {
+        let mut graph = Graph::new(44100.);
+        let a = graph.add(Gain::new(1.));
+        let b = graph.add(Gain::new(1.));
+        graph.connect(a, 0, b, 0).unwrap();
+
+        assert_eq!(Err(GraphError::Cycle), graph.connect(b, 0, a, 0));
+    }
+
+    #[test]
+    fn connect_rejects_mismatched_ports() {
+        let mut graph = Graph::new(44100.);
+        let a = graph.add(Constant(1.));
+        let b = graph.add(Gain::new(1.));
+
+        assert_eq!(
+            Err(GraphError::KindMismatch {
+                output: PortKind::Audio,
+                input: PortKind::Control
+            }),
+            graph.connect(a, 0, b, 1)
+        );
+    }
+
+    #[test]
+    fn nodes_are_processed_in_dependency_order() {
+        let mut graph = Graph::new(44100.);
+        let gain = graph.add(Gain::new(3.));
+        let constant = graph.add(Constant(1.));
+        graph.connect(constant, 0, gain, 0).unwrap();
+        graph.set_output(gain, 0).unwrap();
+
+        assert!(graph.process_block().iter().all(|s| *s == 3.));
+    }
+
+    #[test]
+    fn envelopes_modulate_parameters() {
+        use crate::envelope::{BreakpointEnvelope, Length, Segment};
+
+        let segments = vec![
+            Segment::new(0., Length::Seconds(0.)),
+            Segment::new(2., Length::Seconds(2.)),
+        ];
+        let envelope = BreakpointEnvelope::new(segments, BLOCK_SIZE as f32);
+        let mut graph = Graph::new(BLOCK_SIZE as f32);
+        let envelope = graph.add(EnvelopeNode::new(envelope));
+        let constant = graph.add(Constant(1.));
+        let gain = graph.add(Gain::new(1.));
+        graph.connect(constant, 0, gain, 0).unwrap();
+        graph.modulate(envelope, 0, gain, 0, 0.5).unwrap();
+        graph.set_parameter(envelope, 0, 1.).unwrap();
+        graph.set_output(gain, 0).unwrap();
+
+        let samples = graph.render(BLOCK_SIZE * 3);
+
+        assert!((samples[0] - 1.).abs() < 0.01);
+        assert!((samples[BLOCK_SIZE] - 1.5).abs() < 0.01);
+        assert!((samples[BLOCK_SIZE * 2] - 2.).abs() < 0.01);
+        assert_eq!(
+            Err(GraphError::Cycle),
+            graph.modulate(gain, 0, envelope, 0, 1.)
+        );
+    }
+
+    #[test]
+    fn automation_interpolates_between_breakpoints() {
+        let mut graph = Graph::new(BLOCK_SIZE as f32);
+        let constant = graph.add(Constant(1.));
+        let gain = graph.add(Gain::new(1.));
+        graph.connect(constant, 0, gain, 0).unwrap();
+        graph.set_output(gain, 0).unwrap();
+        graph.automate(gain, 0, 0., 0.).unwrap();
+        graph.automate(gain, 0, 2., 1.).unwrap();
+
+        let samples = graph.render(BLOCK_SIZE * 4);
+
+        assert_eq!(0., samples[0]);
+        assert_eq!(0.5, samples[BLOCK_SIZE]);
+        assert_eq!(1., samples[BLOCK_SIZE * 2]);
+        assert_eq!(1., samples[BLOCK_SIZE * 3]);
+    }
+
+    #[test]
+    fn effects_process_node_input() {
+        use crate::{effects::Delay, envelope::Length};
+
+        let mut graph = Graph::new(100.);
+        let constant = graph.add(Constant(1.));
+        let delay = graph.add(EffectNode::new(
+            Delay::new(Length::Seconds(0.1), 0., 100.).with_mix(1.),
+        ));
+        graph.connect(constant, 0, delay, 0).unwrap();
+        graph.set_output(delay, 0).unwrap();
+
+        let samples = graph.render(20);
+
+        assert!(samples[..10].iter().all(|s| *s == 0.));
+        assert!(samples[10..].iter().all(|s| *s == 1.));
+    }
+}
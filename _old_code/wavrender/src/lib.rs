@@ -0,0 +1,104 @@
+//! Offline audio synthesis: oscillators, envelopes, FM operators, filters, effects and a node
+//! graph, with patch and song files rendered to WAV.
+//!
+//! With the default `std` feature turned off the DSP core builds with `no_std` and `alloc`.
+//! Reading and writing files, patches, songs and playback need `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+// The test harness links `std`, whose float methods are then used instead of `math::Float`
+#![cfg_attr(all(test, not(feature = "std")), allow(unused_imports))]
+
+extern crate alloc;
+
+use core::ops::{Add, Div, Mul, Sub};
+
+pub mod analysis;
+pub mod chip;
+pub mod effects;
+pub mod envelope;
+pub mod filter;
+pub mod formant;
+#[cfg(all(test, feature = "std"))]
+mod golden;
+pub mod granular;
+pub mod graph;
+mod math;
+pub mod modulation;
+pub mod operator;
+pub mod oscillator;
+#[cfg(feature = "std")]
+pub mod output;
+#[cfg(feature = "std")]
+pub mod patch;
+pub mod physical;
+pub mod pitch;
+#[cfg(feature = "std")]
+pub mod playback;
+pub mod resample;
+pub mod rng;
+pub mod sample;
+pub mod sampler;
+pub mod sequencer;
+pub mod sfxr;
+pub mod spatial;
+pub mod voice;
+pub mod wavetable;
+
+use alloc::vec::Vec;
+use envelope::Envelope;
+#[cfg(not(feature = "std"))]
+use math::Float;
+use modulation::{Destination, Lfo, LfoShape, ModMatrix, Rate};
+use operator::Operator;
+use oscillator::Oscillator;
+
+/// The original FM bass demo.
+pub fn demo(sample_rate: f32, duration: f32) -> Vec<f32> {
+    let mut envelope = Envelope::new(2., 2., 0.5, 2., sample_rate);
+    envelope.on();
+
+    let mut matrix = ModMatrix::new();
+    let wobble = matrix.add_envelope(Envelope::new(1., 1., 0.666, 1., sample_rate));
+    let vibrato = matrix.add_lfo(Lfo::new(LfoShape::Sine, Rate::Hz(5.), sample_rate));
+    matrix.route(wobble, Destination::FmDepth, 8.);
+    matrix.route(vibrato, Destination::Pitch, 0.2);
+    matrix.note_on(1.);
+
+    let carrier = Operator::new(65.);
+    let mut phase = 0.;
+    let frames = (sample_rate * duration) as usize;
+    let mut samples = Vec::with_capacity(frames);
+    for t in (0..frames).map(|x| x as f32 / sample_rate) {
+        if t >= 4. {
+            envelope.off();
+        }
+
+        let modulation = matrix.tick();
+
+        // Example modulator
+        let modulator = Oscillator::Sine { frequency: 130. }.sample(t);
+        let mod2 = Oscillator::Square { frequency: 65. }.sample(t);
+        let r = carrier.render_phase(phase, modulation.fm_depth * modulator);
+        phase = (phase + carrier.frequency() * modulation.pitch_ratio() / sample_rate).fract();
+
+        let e = envelope.tick();
+
+        samples.push((mod2 + r) * e);
+    }
+
+    samples
+}
+
+/// Maps one range to another
+pub fn map_range<Num>(
+    input: Num,
+    input_start: Num,
+    input_end: Num,
+    output_start: Num,
+    output_end: Num,
+) -> Num
+where
+    Num: Copy + Add<Output = Num> + Sub<Output = Num> + Div<Output = Num> + Mul<Output = Num>,
+{
+    output_start + ((input - input_start) * (output_end - output_start)) / (input_end - input_start)
+}
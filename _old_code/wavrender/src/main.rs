@@ -1,76 +1,170 @@
-use hound;
-use operator::{Modulator, Operator};
-use std::f32::consts::PI;
-use std::i16;
-use std::io::BufReader;
+use cli::{CliError, Input};
+use wavrender::{
+    analysis, demo,
+    formant::Bark,
+    output::{OutputFormat, SampleFormat, WavOutput},
+    patch,
+};
 
-mod envelope;
-use envelope::Envelope;
-mod oscillator;
-use oscillator::Oscillator;
-
-mod operator;
+mod cli;
 
 fn main() {
-    let sample_rate = 44100;
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
+    let options = match cli::Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(CliError::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("wavrender: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
     };
 
-    let mut writer = hound::WavWriter::create("sine.wav", spec).unwrap();
+    if let Err(e) = render(&options) {
+        eprintln!("wavrender: {}", e);
+        std::process::exit(1);
+    }
+}
 
-    let duration_seconds = 8;
+/// Renders the input and writes it to the output file.
+fn render(options: &cli::Options) -> Result<(), Box<dyn std::error::Error>> {
+    let mut progress = Progress::new(options.quiet);
+    let (mut samples, sample_rate) = match &options.input {
+        Input::Demo => {
+            let sample_rate = options.sample_rate.unwrap_or(44100) as f32;
+            let duration = options.duration.unwrap_or(8.);
+            frames(duration, sample_rate)?;
+            (demo(sample_rate, duration), sample_rate)
+        }
+        Input::Patch(path) => {
+            let patch = patch::Patch::load(path)?;
+            let sample_rate = options
+                .sample_rate
+                .map(|rate| rate as f32)
+                .unwrap_or_else(|| patch.sample_rate());
+            let duration = options.duration.unwrap_or_else(|| patch.duration());
 
-    let mut envelope = Envelope::new(2., 2., 0.5, 2., sample_rate as f32);
-    envelope.on();
-    let mut wobble = Envelope::new(1., 1., 0.666, 1., sample_rate as f32);
-    wobble.on();
-    for t in (0..sample_rate * duration_seconds).map(|x| x as f32 / 44100.0) {
-        if t >= 4. {
-            envelope.off();
+            let frames = frames(duration, sample_rate)?;
+            let mut graph = patch.build(sample_rate)?;
+            let samples = graph.render_with_progress(frames, |done| progress.report(done));
+            (samples, sample_rate)
         }
+        Input::Song(path) => {
+            let file = patch::SongFile::load(path)?;
+            let sample_rate = options
+                .sample_rate
+                .map(|rate| rate as f32)
+                .unwrap_or_else(|| file.sample_rate());
 
-        // Example modulator
-        let modulator = Oscillator::Sine { frequency: 130. }.sample(t);
-        let mod2 = Oscillator::Square { frequency: 65. }.sample(t);
-        let r = Operator::new(65.).render(t, 8. * modulator * wobble.tick());
+            let mut song = file.build(options.bpm.unwrap_or_else(|| file.bpm()), sample_rate);
+            frames(song.duration(), sample_rate)?;
+            let mut samples = song.render_with_progress(|done| progress.report(done));
+            if let Some(duration) = options.duration {
+                samples.resize(frames(duration, sample_rate)?, 0.);
+            }
+            (samples, sample_rate)
+        }
+        Input::Bark(phonemes) => {
+            let sample_rate = options.sample_rate.unwrap_or(44100) as f32;
+            let mut samples = Bark::parse(phonemes)?.render(sample_rate);
+            if let Some(duration) = options.duration {
+                samples.resize(frames(duration, sample_rate)?, 0.);
+            }
+            (samples, sample_rate)
+        }
+    };
+    progress.finish();
 
-        let e = envelope.tick();
+    if options.normalize {
+        let peak = analysis::peak(&samples);
+        if peak > 0. {
+            samples.iter_mut().for_each(|s| *s /= peak);
+        }
+    }
 
-        let sample = (mod2 + r) * e;
+    let format = OutputFormat::new(options.channels, sample_rate as u32, SampleFormat::Int16);
+    let mut writer = WavOutput::create(&options.output, format)
+        .map_err(|e| format!("could not write {}: {}", options.output.display(), e))?;
+    writer.write_mono(&samples)?;
+    writer.finalize()?;
 
-        let const_amplitude = i16::MAX as f32;
-        writer
-            .write_sample((sample * const_amplitude) as i16)
-            .unwrap();
+    Ok(())
+}
+
+/// The number of frames in a render, or an error when the length or sample rate is past what
+/// can be rendered and written.
+fn frames(duration: f32, sample_rate: f32) -> Result<usize, String> {
+    if !(1. ..=cli::MAX_SAMPLE_RATE as f32).contains(&sample_rate) {
+        return Err(format!(
+            "the sample rate {} Hz is outside 1 .. {} Hz",
+            sample_rate,
+            cli::MAX_SAMPLE_RATE
+        ));
+    }
+    if !(0. ..=patch::MAX_DURATION).contains(&duration) {
+        return Err(format!(
+            "the render would be {} seconds long, over the limit of {}",
+            duration,
+            patch::MAX_DURATION
+        ));
     }
-    writer.finalize().unwrap();
+
+    Ok((duration * sample_rate) as usize)
 }
 
-/// Maps one range to another
-pub fn map_range<Num>(
-    input: Num,
-    input_start: Num,
-    input_end: Num,
-    output_start: Num,
-    output_end: Num,
-) -> Num
-where
-    Num: Copy
-        + std::ops::Add<Output = Num>
-        + std::ops::Sub<Output = Num>
-        + std::ops::Div<Output = Num>
-        + std::ops::Mul<Output = Num>,
-{
-    output_start + ((input - input_start) * (output_end - output_start)) / (input_end - input_start)
+/// Reports how much of a render is done on stderr, on a single line.
+struct Progress {
+    quiet: bool,
+    percent: Option<u32>,
+}
+
+impl Progress {
+    fn new(quiet: bool) -> Self {
+        Self {
+            quiet,
+            percent: None,
+        }
+    }
+
+    fn report(&mut self, done: f32) {
+        let percent = (done * 100.) as u32;
+        if self.quiet || self.percent == Some(percent) {
+            return;
+        }
+
+        self.percent = Some(percent);
+        eprint!("\rrendering {:3}%", percent);
+    }
+
+    fn finish(&mut self) {
+        if self.percent.is_some() {
+            eprintln!();
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use wavrender::sequencer::{Pattern, Song};
+
+    #[test]
+    fn render_lengths_are_limited() {
+        assert_eq!(Ok(44100), frames(1., 44100.));
+        assert!(frames(f32::INFINITY, 44100.).is_err());
+        assert!(frames(1e30, 44100.).is_err());
+        assert!(frames(f32::NAN, 44100.).is_err());
+        assert!(frames(-1., 44100.).is_err());
+        assert!(frames(1., 4e9).is_err());
+        assert!(frames(1., 0.).is_err());
+
+        // A slow enough tempo makes even a one step song too long
+        let mut song = Song::new(1e-30, 8000.);
+        let pattern = song.add_pattern(Pattern::new(1));
+        song.set_order(vec![pattern]);
+        assert!(frames(song.duration(), song.sample_rate()).is_err());
+    }
 
     #[test]
     fn is_active_0_returns_false() {
@@ -0,0 +1,103 @@
+//! The float methods `std` provides, built on `libm` for `no_std`. Modules that use them import
+//! `Float` when `std` is off; with it the inherent methods are used instead.
+#![cfg_attr(any(feature = "std", test), allow(dead_code))]
+
+pub(crate) trait Float: Sized {
+    fn asin(self) -> Self;
+    fn ceil(self) -> Self;
+    fn cos(self) -> Self;
+    fn exp(self) -> Self;
+    fn floor(self) -> Self;
+    fn log10(self) -> Self;
+    fn log2(self) -> Self;
+    fn round(self) -> Self;
+    fn sin(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn tan(self) -> Self;
+    fn tanh(self) -> Self;
+    fn trunc(self) -> Self;
+    fn powf(self, n: Self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn fract(self) -> Self;
+    fn rem_euclid(self, rhs: Self) -> Self;
+}
+
+macro_rules! impl_float {
+    ($float:ty, $pow:path, [$($name:ident => $libm:path),*]) => {
+        impl Float for $float {
+            $(
+                fn $name(self) -> Self {
+                    $libm(self)
+                }
+            )*
+
+            fn powf(self, n: Self) -> Self {
+                $pow(self, n)
+            }
+
+            fn powi(self, n: i32) -> Self {
+                $pow(self, n as $float)
+            }
+
+            fn fract(self) -> Self {
+                self - self.trunc()
+            }
+
+            fn rem_euclid(self, rhs: Self) -> Self {
+                let remainder = self % rhs;
+                if remainder < 0. {
+                    remainder + rhs.abs()
+                } else {
+                    remainder
+                }
+            }
+        }
+    };
+}
+
+impl_float!(f32, libm::powf, [
+    asin => libm::asinf,
+    ceil => libm::ceilf,
+    cos => libm::cosf,
+    exp => libm::expf,
+    floor => libm::floorf,
+    log10 => libm::log10f,
+    log2 => libm::log2f,
+    round => libm::roundf,
+    sin => libm::sinf,
+    sqrt => libm::sqrtf,
+    tan => libm::tanf,
+    tanh => libm::tanhf,
+    trunc => libm::truncf
+]);
+
+impl_float!(f64, libm::pow, [
+    asin => libm::asin,
+    ceil => libm::ceil,
+    cos => libm::cos,
+    exp => libm::exp,
+    floor => libm::floor,
+    log10 => libm::log10,
+    log2 => libm::log2,
+    round => libm::round,
+    sin => libm::sin,
+    sqrt => libm::sqrt,
+    tan => libm::tan,
+    tanh => libm::tanh,
+    trunc => libm::trunc
+]);
+
+#[cfg(test)]
+mod tests {
+    use super::Float;
+
+    #[test]
+    fn matches_std() {
+        for x in [-2.75_f32, -0.5, 0., 0.3, 1.5, 7.25].iter().copied() {
+            assert_eq!(f32::fract(x), Float::fract(x));
+            assert_eq!(f32::rem_euclid(x, 2.), Float::rem_euclid(x, 2.));
+            assert!((f32::sin(x) - Float::sin(x)).abs() < 1e-6);
+            assert!((f32::powi(x, 3) - Float::powi(x, 3)).abs() < 1e-4);
+        }
+    }
+}
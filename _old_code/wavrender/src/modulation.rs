@@ -0,0 +1,379 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{envelope::Gated, oscillator::Oscillator, pitch::semitones_to_ratio, rng::Rng};
+use alloc::{boxed::Box, vec, vec::Vec};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LfoShape {
+    Sine,
+    Square,
+    Triangle,
+    Saw,
+    /// A new random value each cycle.
+    SampleAndHold,
+    /// Smoothly moves to a new random value each cycle.
+    Random,
+}
+
+/// How fast an LFO cycles.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rate {
+    Hz(f32),
+    /// The length of one cycle in beats, synced to the tempo.
+    Beats(f32),
+}
+
+/// A low frequency oscillator producing values in -1 .. 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lfo {
+    shape: LfoShape,
+    rate: Rate,
+    /// Offset applied to the phase, in cycles.
+    phase_offset: f32,
+    bpm: f32,
+    sample_rate: f32,
+    phase: f32,
+    rng: Rng,
+    previous: f32,
+    next: f32,
+}
+
+impl Lfo {
+    pub fn new(shape: LfoShape, rate: Rate, sample_rate: f32) -> Self {
+        Self {
+            shape,
+            rate,
+            phase_offset: 0.,
+            bpm: 120.,
+            sample_rate,
+            phase: 0.,
+            rng: Rng::new(0),
+            previous: 0.,
+            next: 0.,
+        }
+        .with_seed(0)
+    }
+
+    /// Sets the starting phase, in cycles.
+    pub fn with_phase(mut self, phase_offset: f32) -> Self {
+        self.phase_offset = phase_offset;
+        self
+    }
+
+    /// Sets the seed used by the random shapes.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self.previous = self.rng.bipolar();
+        self.next = self.rng.bipolar();
+        self
+    }
+
+    /// Sets the tempo used when the rate is in beats.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.bpm = bpm;
+    }
+
+    pub fn set_rate(&mut self, rate: Rate) {
+        self.rate = rate;
+    }
+
+    pub fn frequency(&self) -> f32 {
+        match self.rate {
+            Rate::Hz(frequency) => frequency,
+            Rate::Beats(beats) => self.bpm / (60. * beats),
+        }
+    }
+
+    /// Restarts the cycle, such as on a new note.
+    pub fn reset(&mut self) {
+        self.phase = 0.;
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        let phase = (self.phase + self.phase_offset).rem_euclid(1.);
+        let value = match self.shape {
+            LfoShape::Sine => Oscillator::Sine { frequency: 1. }.sample_phase(phase),
+            LfoShape::Square => Oscillator::Pulse {
+                frequency: 1.,
+                duty: 0.5,
+            }
+            .sample_phase(phase),
+            LfoShape::Triangle => Oscillator::Triangle { frequency: 1. }.sample_phase(phase),
+            LfoShape::Saw => Oscillator::Saw { frequency: 1. }.sample_phase(phase),
+            LfoShape::SampleAndHold => self.previous,
+            LfoShape::Random => {
+                let x = (1. - (phase.fract() * core::f32::consts::PI).cos()) / 2.;
+                self.previous + (self.next - self.previous) * x
+            }
+        };
+
+        let delta = self.frequency() / self.sample_rate;
+        self.phase += delta;
+        if self.phase >= 1. {
+            self.phase = self.phase.fract();
+        }
+        // The random shapes move on when the offset phase wraps, so they shift with it
+        if phase + delta >= 1. {
+            self.previous = self.next;
+            self.next = self.rng.bipolar();
+        }
+
+        value
+    }
+}
+
+/// Something that produces modulation values.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Source {
+    Lfo(usize),
+    Envelope(usize),
+    /// The velocity of the current note, 0 .. 1.
+    Velocity,
+    /// 0 .. 1.
+    ModWheel,
+}
+
+/// Something that may be modulated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Destination {
+    /// In semitones.
+    Pitch,
+    /// Added to the modulation index of a FM operator.
+    FmDepth,
+    /// In octaves.
+    FilterCutoff,
+    /// Added to a base gain of 1.
+    Amplitude,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Route {
+    pub source: Source,
+    pub destination: Destination,
+    pub depth: f32,
+}
+
+/// The summed modulation for each destination for a single sample.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Modulation {
+    pub pitch: f32,
+    pub fm_depth: f32,
+    pub filter_cutoff: f32,
+    pub amplitude: f32,
+}
+
+impl Modulation {
+    /// The amount to multiply a frequency by.
+    pub fn pitch_ratio(&self) -> f32 {
+        semitones_to_ratio(self.pitch)
+    }
+
+    /// The amount to multiply a cutoff by.
+    pub fn cutoff_ratio(&self) -> f32 {
+        2_f32.powf(self.filter_cutoff)
+    }
+
+    pub fn gain(&self) -> f32 {
+        (1. + self.amplitude).max(0.)
+    }
+}
+
+/// Routes modulation sources to destinations, each with its own depth.
+pub struct ModMatrix {
+    lfos: Vec<Lfo>,
+    envelopes: Vec<Box<dyn Gated>>,
+    routes: Vec<Route>,
+    velocity: f32,
+    mod_wheel: f32,
+    lfo_values: Vec<f32>,
+    envelope_values: Vec<f32>,
+}
+
+impl Default for ModMatrix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModMatrix {
+    pub fn new() -> Self {
+        Self {
+            lfos: vec![],
+            envelopes: vec![],
+            routes: vec![],
+            velocity: 1.,
+            mod_wheel: 0.,
+            lfo_values: vec![],
+            envelope_values: vec![],
+        }
+    }
+
+    pub fn add_lfo(&mut self, lfo: Lfo) -> Source {
+        self.lfos.push(lfo);
+        self.lfo_values.push(0.);
+        Source::Lfo(self.lfos.len() - 1)
+    }
+
+    pub fn add_envelope<E>(&mut self, envelope: E) -> Source
+    where
+        E: Gated + 'static,
+    {
+        self.envelopes.push(Box::new(envelope));
+        self.envelope_values.push(0.);
+        Source::Envelope(self.envelopes.len() - 1)
+    }
+
+    pub fn route(&mut self, source: Source, destination: Destination, depth: f32) {
+        self.routes.push(Route {
+            source,
+            destination,
+            depth,
+        });
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    pub fn set_mod_wheel(&mut self, mod_wheel: f32) {
+        self.mod_wheel = mod_wheel;
+    }
+
+    /// Sets the tempo for any tempo synced LFOs.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.lfos.iter_mut().for_each(|lfo| lfo.set_tempo(bpm));
+    }
+
+    /// Starts the envelopes and sets the velocity.
+    pub fn note_on(&mut self, velocity: f32) {
+        self.velocity = velocity;
+        self.envelopes.iter_mut().for_each(|e| e.on());
+    }
+
+    pub fn note_off(&mut self) {
+        self.envelopes.iter_mut().for_each(|e| e.off());
+    }
+
+    /// Advances every source by one sample and sums the routes.
+    pub fn tick(&mut self) -> Modulation {
+        for (value, lfo) in self.lfo_values.iter_mut().zip(self.lfos.iter_mut()) {
+            *value = lfo.tick();
+        }
+
+        for (value, envelope) in self
+            .envelope_values
+            .iter_mut()
+            .zip(self.envelopes.iter_mut())
+        {
+            *value = envelope.tick();
+        }
+
+        let mut modulation = Modulation::default();
+        for route in self.routes.iter() {
+            let value = match route.source {
+                Source::Lfo(index) => self.lfo_values[index],
+                Source::Envelope(index) => self.envelope_values[index],
+                Source::Velocity => self.velocity,
+                Source::ModWheel => self.mod_wheel,
+            } * route.depth;
+
+            match route.destination {
+                Destination::Pitch => modulation.pitch += value,
+                Destination::FmDepth => modulation.fm_depth += value,
+                Destination::FilterCutoff => modulation.filter_cutoff += value,
+                Destination::Amplitude => modulation.amplitude += value,
+            }
+        }
+
+        modulation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::Envelope;
+
+    #[test]
+    fn lfo_syncs_to_tempo() {
+        let mut lfo = Lfo::new(LfoShape::Saw, Rate::Beats(1.), 100.);
+        lfo.set_tempo(120.);
+
+        assert_eq!(2., lfo.frequency());
+        let samples: Vec<f32> = (0..50).map(|_| lfo.tick()).collect();
+        assert!((samples[10] - 0.4).abs() < 1e-4);
+        assert!((samples[40] + 0.4).abs() < 1e-4);
+    }
+
+    #[test]
+    fn lfo_phase_offset_shifts_wave() {
+        let mut sine = Lfo::new(LfoShape::Sine, Rate::Hz(1.), 100.).with_phase(0.25);
+
+        assert!((sine.tick() - 1.).abs() < 1e-6);
+
+        // Half a cycle ahead, including when a new random value is picked
+        for shape in [LfoShape::SampleAndHold, LfoShape::Random].iter() {
+            let render = |offset| {
+                let mut lfo = Lfo::new(*shape, Rate::Hz(8.), 64.).with_phase(offset);
+                (0..32).map(|_| lfo.tick()).collect::<Vec<f32>>()
+            };
+            let (plain, shifted) = (render(0.), render(0.5));
+
+            assert_ne!(shifted[3], shifted[4]);
+            for (shifted, plain) in shifted.iter().zip(plain[4..].iter()) {
+                assert!((shifted - plain).abs() < 1e-6, "{} {}", shifted, plain);
+            }
+        }
+    }
+
+    #[test]
+    fn random_shapes_are_deterministic_under_seed() {
+        for shape in [LfoShape::SampleAndHold, LfoShape::Random].iter() {
+            let render = |seed| {
+                let mut lfo = Lfo::new(*shape, Rate::Hz(10.), 100.).with_seed(seed);
+                (0..100).map(|_| lfo.tick()).collect::<Vec<f32>>()
+            };
+
+            assert_eq!(render(3), render(3));
+            assert_ne!(render(3), render(4));
+            assert!(render(3).iter().all(|v| v.abs() <= 1.));
+        }
+    }
+
+    #[test]
+    fn sample_and_hold_holds_for_a_cycle() {
+        let mut lfo = Lfo::new(LfoShape::SampleAndHold, Rate::Hz(10.), 100.);
+        let samples: Vec<f32> = (0..20).map(|_| lfo.tick()).collect();
+
+        assert!(samples[..10].iter().all(|s| *s == samples[0]));
+        assert!(samples[10..].iter().all(|s| *s == samples[10]));
+        assert_ne!(samples[0], samples[10]);
+    }
+
+    #[test]
+    fn matrix_sums_routes_with_depth() {
+        let mut matrix = ModMatrix::new();
+        let lfo = matrix.add_lfo(Lfo::new(LfoShape::Square, Rate::Hz(1.), 100.));
+        let envelope = matrix.add_envelope(Envelope::new(0., 0., 1., 0., 100.));
+        matrix.route(lfo, Destination::Pitch, 2.);
+        matrix.route(Source::Velocity, Destination::Amplitude, -0.5);
+        matrix.route(Source::ModWheel, Destination::Pitch, 12.);
+        matrix.route(envelope, Destination::FilterCutoff, 3.);
+        matrix.route(envelope, Destination::FmDepth, 4.);
+        matrix.set_mod_wheel(0.5);
+        matrix.note_on(0.8);
+
+        let modulation = matrix.tick();
+
+        assert_eq!(8., modulation.pitch);
+        assert_eq!(2_f32.powf(8. / 12.), modulation.pitch_ratio());
+        assert_eq!(3., modulation.filter_cutoff);
+        assert_eq!(8., modulation.cutoff_ratio());
+        assert_eq!(4., modulation.fm_depth);
+        assert!((modulation.gain() - 0.6).abs() < 1e-6);
+
+        // The square is -1 for the second half of its cycle
+        let modulation = (0..60).map(|_| matrix.tick()).last().unwrap();
+        assert_eq!(4., modulation.pitch);
+    }
+}
@@ -1,6 +1,4 @@
-use std::f32::consts::PI;
-
-use crate::oscillator::Oscillator;
+use crate::pitch::midi_to_hz;
 
 pub struct Operator {
     carrier_frequency: f32,
@@ -16,7 +14,25 @@ impl Operator {
         Self { carrier_frequency }
     }
 
+    /// An operator tuned to an equal tempered MIDI note.
+    pub fn from_midi(note: f32) -> Self {
+        Self::new(midi_to_hz(note))
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.carrier_frequency
+    }
+
+    pub fn set_frequency(&mut self, carrier_frequency: f32) {
+        self.carrier_frequency = carrier_frequency;
+    }
+
     pub fn render(&self, t: f32, modulator: f32) -> f32 {
         crate::oscillator::sine(t, self.carrier_frequency, modulator)
     }
+
+    /// Renders the carrier at the given phase, measured in cycles.
+    pub fn render_phase(&self, phase: f32, modulator: f32) -> f32 {
+        crate::oscillator::sine(phase, 1., modulator)
+    }
 }
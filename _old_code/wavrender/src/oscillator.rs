@@ -1,5 +1,50 @@
-use std::f32::consts::PI;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{pitch::midi_to_hz, rng::mix, wavetable::Wavetable};
+use alloc::{sync::Arc, vec::Vec};
+use core::f32::consts::PI;
 
+/// The number of values white, pink and brown noise produce before repeating.
+const NOISE_PERIOD: u64 = 1 << 20;
+/// The number of random rows summed for pink and brown noise.
+const NOISE_ROWS: u32 = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NoiseKind {
+    White,
+    /// Equal energy per octave, using the Voss-McCartney algorithm.
+    Pink,
+    /// Falls 6 dB per octave, like a random walk.
+    Brown,
+    /// The NES noise channel's shift register. Short mode repeats every 93 values, otherwise every 32767.
+    Periodic {
+        short: bool,
+    },
+}
+
+/// A sine at a multiple of an additive oscillator's frequency.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Partial {
+    pub ratio: f32,
+    pub amplitude: f32,
+}
+
+impl Partial {
+    pub fn new(ratio: f32, amplitude: f32) -> Self {
+        Self { ratio, amplitude }
+    }
+
+    /// The partials of a wave with the given amplitude for each harmonic, starting at the fundamental.
+    pub fn harmonics(amplitudes: &[f32]) -> Vec<Self> {
+        amplitudes
+            .iter()
+            .enumerate()
+            .map(|(i, amplitude)| Self::new((i + 1) as f32, *amplitude))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Oscillator {
     Sine {
         /// The frequency of the wave
@@ -15,6 +60,29 @@ pub enum Oscillator {
         /// The frequency of the wave
         frequency: f32,
     },
+    /// A square wave with an adjustable width.
+    Pulse {
+        frequency: f32,
+        /// The fraction of each cycle spent high, 0 .. 1.
+        duty: f32,
+    },
+    Noise {
+        kind: NoiseKind,
+        /// The number of new values per second.
+        frequency: f32,
+        seed: u64,
+    },
+    /// A sum of sines.
+    Additive {
+        frequency: f32,
+        partials: Vec<Partial>,
+    },
+    Wavetable {
+        table: Arc<Wavetable>,
+        frequency: f32,
+        /// Morphs across the table's frames, 0 .. 1.
+        position: f32,
+    },
 }
 
 pub fn sine(t: f32, freq: f32, modulator: f32) -> f32 {
@@ -22,8 +90,7 @@ pub fn sine(t: f32, freq: f32, modulator: f32) -> f32 {
 }
 
 fn square(t: f32, freq: f32) -> f32 {
-    let v = (2. * (t * freq).floor() - (2. * t * freq).floor()) + 1.;
-    v
+    (2. * (t * freq).floor() - (2. * t * freq).floor()) + 1.
 }
 
 impl Oscillator {
@@ -33,32 +100,362 @@ impl Oscillator {
             Oscillator::Square { frequency } => *frequency,
             Oscillator::Triangle { frequency } => *frequency,
             Oscillator::Saw { frequency } => *frequency,
+            Oscillator::Pulse { frequency, .. } => *frequency,
+            Oscillator::Noise { frequency, .. } => *frequency,
+            Oscillator::Additive { frequency, .. } => *frequency,
+            Oscillator::Wavetable { frequency, .. } => *frequency,
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        match self {
+            Oscillator::Sine { frequency: f } => *f = frequency,
+            Oscillator::Square { frequency: f } => *f = frequency,
+            Oscillator::Triangle { frequency: f } => *f = frequency,
+            Oscillator::Saw { frequency: f } => *f = frequency,
+            Oscillator::Pulse { frequency: f, .. } => *f = frequency,
+            Oscillator::Noise { frequency: f, .. } => *f = frequency,
+            Oscillator::Additive { frequency: f, .. } => *f = frequency,
+            Oscillator::Wavetable { frequency: f, .. } => *f = frequency,
+        }
+    }
+
+    /// Tunes to an equal tempered MIDI note, which may be fractional.
+    pub fn set_note(&mut self, note: f32) {
+        self.set_frequency(midi_to_hz(note));
+    }
+
+    /// The wavetable position, or 0 for other waves.
+    pub fn position(&self) -> f32 {
+        match self {
+            Oscillator::Wavetable { position, .. } => *position,
+            _ => 0.,
+        }
+    }
+
+    /// Sets the wavetable position. Other waves are unchanged.
+    pub fn set_position(&mut self, position: f32) {
+        if let Oscillator::Wavetable { position: p, .. } = self {
+            *p = position;
+        }
+    }
+
+    /// The number of cycles after which the wave repeats.
+    /// Accumulated phases may be wrapped by this without changing the sound.
+    pub fn period(&self) -> f64 {
+        match self {
+            Oscillator::Noise {
+                kind: NoiseKind::Periodic { short },
+                ..
+            } => lfsr(*short).len() as f64,
+            Oscillator::Noise { .. } => NOISE_PERIOD as f64,
+            Oscillator::Additive { partials, .. } => additive_period(partials),
+            _ => 1.,
         }
     }
 
     pub fn sample(&self, t: f32) -> f32 {
+        self.sample_phase(t * self.frequency())
+    }
+
+    /// Samples the wave at the given phase, measured in cycles.
+    /// Used when the frequency changes over time and the phase is accumulated.
+    pub fn sample_phase(&self, phase: f32) -> f32 {
         match self {
-            Oscillator::Sine { frequency } => sine(t, *frequency, 0.),
-            Oscillator::Square { frequency } => square(t, *frequency),
-            Oscillator::Triangle { frequency } => {
-                if *frequency == 0. {
-                    return 0.;
+            Oscillator::Sine { .. } => sine(phase, 1., 0.),
+            Oscillator::Square { .. } => square(phase, 1.),
+            Oscillator::Triangle { .. } => 2. / PI * ((2. * PI * phase).sin()).asin(),
+            Oscillator::Saw { .. } => 2. * (phase - (0.5 + phase).floor()),
+            Oscillator::Pulse { duty, .. } => {
+                if phase - phase.floor() < *duty {
+                    1.
+                } else {
+                    -1.
                 }
-                let p = 1. / frequency;
+            }
+            Oscillator::Noise { kind, seed, .. } => noise(*kind, *seed, phase as f64),
+            Oscillator::Additive { partials, .. } => additive(partials, phase, f32::INFINITY),
+            Oscillator::Wavetable {
+                table,
+                frequency,
+                position,
+            } => table.sample(phase, *position, *frequency, table.sample_rate()),
+        }
+    }
+
+    /// Like `sample_phase`, but band-limited for the sample rate it's played at: additive
+    /// partials at or above Nyquist are left out, and wavetables pick their mip-map by it.
+    pub fn sample_phase_at(&self, phase: f32, sample_rate: f32) -> f32 {
+        match self {
+            Oscillator::Additive {
+                frequency,
+                partials,
+            } => additive(partials, phase, sample_rate / 2. / frequency.abs()),
+            Oscillator::Wavetable {
+                table,
+                frequency,
+                position,
+            } => table.sample(phase, *position, *frequency, sample_rate),
+            _ => self.sample_phase(phase),
+        }
+    }
+}
 
-                2. / PI * ((2. * PI * t / p).sin()).asin()
+/// Sums the partials with a ratio below `max_ratio`.
+fn additive(partials: &[Partial], phase: f32, max_ratio: f32) -> f32 {
+    let phase = phase as f64;
+    partials
+        .iter()
+        .filter(|p| p.ratio < max_ratio)
+        .map(|p| {
+            let cycles = (phase * p.ratio as f64).fract();
+            p.amplitude * (2. * core::f64::consts::PI * cycles).sin() as f32
+        })
+        .sum()
+}
+
+/// The fewest cycles after which every partial is back at its start. Ratios with no small common
+/// period wrap after 1000 cycles, with a tiny discontinuity.
+fn additive_period(partials: &[Partial]) -> f64 {
+    (1..1000)
+        .find(|cycles| {
+            partials.iter().all(|p| {
+                let x = p.ratio as f64 * *cycles as f64;
+                (x - x.round()).abs() < 1e-4
+            })
+        })
+        .unwrap_or(1000) as f64
+}
+
+/// Noise where each cycle of the phase is a new value.
+fn noise(kind: NoiseKind, seed: u64, phase: f64) -> f32 {
+    let phase = phase.rem_euclid(NOISE_PERIOD as f64);
+    let step = phase as u64;
+
+    match kind {
+        NoiseKind::White => random(seed, 0, step),
+        NoiseKind::Pink => {
+            // Row k holds a value for 2^k steps. Rows are staggered so they don't all change together.
+            let sum: f32 = (0..NOISE_ROWS)
+                .map(|row| {
+                    let offset = mix(seed ^ row as u64) & ((1 << row) - 1);
+                    random(seed, row + 1, (step + offset) >> row)
+                })
+                .sum();
+
+            sum / NOISE_ROWS as f32
+        }
+        NoiseKind::Brown => {
+            // Like pink, but slower rows are louder and values are interpolated
+            let mut sum = 0.;
+            let mut total = 0.;
+            for row in 0..NOISE_ROWS {
+                let weight = 2_f32.powf(row as f32 / 2.);
+                let length = (1_u64 << row) as f64;
+                let position = (phase + (mix(seed ^ row as u64) % (1 << row)) as f64) / length;
+                let index = position as u64;
+                let a = random(seed, row + 1, index);
+                let b = random(seed, row + 1, index + 1);
+                sum += weight * (a + (b - a) * position.fract() as f32);
+                total += weight;
             }
 
-            Oscillator::Saw { frequency } => {
-                //
-                let frequency = *frequency;
+            sum / total
+        }
+        NoiseKind::Periodic { short } => {
+            let table = lfsr(short);
+            let offset = (seed % table.len() as u64) as usize;
+            table[(step as usize + offset) % table.len()]
+        }
+    }
+}
+
+/// A value in -1 .. 1 that depends only on its arguments.
+fn random(seed: u64, row: u32, step: u64) -> f32 {
+    let hash = mix(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ mix(((row as u64) << 32) ^ step));
+    (hash >> 40) as f32 / (1u64 << 23) as f32 - 1.
+}
+
+/// One period of the NES noise channel's 15 bit shift register, as -1 or 1.
+fn lfsr(short: bool) -> &'static [f32] {
+    static LONG: [f32; 32767] = lfsr_period(1);
+    static SHORT: [f32; 93] = lfsr_period(6);
+
+    if short {
+        &SHORT
+    } else {
+        &LONG
+    }
+}
+
+/// Steps the register from 1 until it returns to 1. `N` must be the period for the tap.
+const fn lfsr_period<const N: usize>(tap: u16) -> [f32; N] {
+    let mut values = [0.; N];
+    let mut register: u16 = 1;
+    let mut i = 0;
+    while i < N {
+        values[i] = if register & 1 == 0 { 1. } else { -1. };
+        let feedback = (register ^ (register >> tap)) & 1;
+        register = (register >> 1) | (feedback << 14);
+        i += 1;
+    }
+    assert!(register == 1, "not the register's period");
+
+    values
+}
 
-                if frequency == 0. {
-                    return 0.;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{autocorrelation_pitch, Spectrum, Window};
+    use alloc::vec;
+
+    /// One value per sample.
+    fn render(kind: NoiseKind, seed: u64, length: usize) -> Vec<f32> {
+        let noise = Oscillator::Noise {
+            kind,
+            frequency: 1.,
+            seed,
+        };
+        (0..length).map(|i| noise.sample_phase(i as f32)).collect()
+    }
+
+    /// The average power per DFT bin between two bins, over many windows.
+    fn band_power(samples: &[f32], size: usize, bins: core::ops::Range<usize>) -> f64 {
+        let mut total = 0.;
+        let mut count = 0;
+        for window in samples.chunks_exact(size) {
+            for bin in bins.clone() {
+                let (mut re, mut im) = (0_f64, 0_f64);
+                for (i, sample) in window.iter().enumerate() {
+                    let angle = 2. * core::f64::consts::PI * (bin * i) as f64 / size as f64;
+                    re += *sample as f64 * angle.cos();
+                    im -= *sample as f64 * angle.sin();
                 }
-                let p = 1. / frequency;
-                2. * (t / p - (0.5 + t / p).floor())
+                total += re * re + im * im;
+                count += 1;
             }
         }
+
+        total / count as f64
+    }
+
+    /// How much more power there is two octaves lower.
+    fn two_octave_ratio(kind: NoiseKind) -> f64 {
+        let samples = render(kind, 1, 1024 * 32);
+        band_power(&samples, 1024, 16..32) / band_power(&samples, 1024, 64..128)
+    }
+
+    #[test]
+    fn sine_peaks_at_its_frequency() {
+        let sine = Oscillator::Sine { frequency: 440. };
+        let samples: Vec<f32> = (0..22050).map(|i| sine.sample(i as f32 / 44100.)).collect();
+        let spectrum = Spectrum::new(&samples, 44100., Window::BlackmanHarris);
+
+        assert!((spectrum.peak_frequency() - 440.).abs() < 0.5);
+        let pitch = autocorrelation_pitch(&samples, 44100., 50., 2000.).unwrap();
+        assert!((pitch - 440.).abs() < 0.5, "{}", pitch);
+    }
+
+    #[test]
+    fn additive_sums_partials() {
+        let additive = Oscillator::Additive {
+            frequency: 100.,
+            partials: Partial::harmonics(&[1., 0., 0.5]),
+        };
+
+        for t in [0.001, 0.0042, 0.013].iter() {
+            let expected = (2. * PI * 100. * t).sin() + 0.5 * (2. * PI * 300. * t).sin();
+            assert!((additive.sample(*t) - expected).abs() < 1e-4);
+        }
+        assert_eq!(1., additive.period());
+
+        let bell = Oscillator::Additive {
+            frequency: 100.,
+            partials: vec![Partial::new(1., 1.), Partial::new(2.76, 0.5)],
+        };
+        assert_eq!(25., bell.period());
+        assert!((bell.sample_phase(0.3) - bell.sample_phase(25.3)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn partials_above_nyquist_are_left_out() {
+        let additive = Oscillator::Additive {
+            frequency: 5000.,
+            partials: Partial::harmonics(&[1., 0., 0., 0., 1.]),
+        };
+        let render = |band_limited: bool| -> Vec<f32> {
+            (0..16384)
+                .map(|i| {
+                    let phase = (i as f32 * 5000. / 44100.).fract();
+                    if band_limited {
+                        additive.sample_phase_at(phase, 44100.)
+                    } else {
+                        additive.sample_phase(phase)
+                    }
+                })
+                .collect()
+        };
+        let spectrum = |samples: &[f32]| Spectrum::new(samples, 44100., Window::BlackmanHarris);
+
+        // The 5th harmonic at 25 kHz folds back to 19.1 kHz
+        let (naive, band_limited) = (spectrum(&render(false)), spectrum(&render(true)));
+        assert!(naive.magnitude_at(19100.) > 0.05);
+        assert!(band_limited.magnitude_at(19100.) < 1e-3);
+        let fundamental = band_limited.magnitude_at(5000.);
+        assert!((fundamental / naive.magnitude_at(5000.) - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn white_noise_is_uniform_and_uncorrelated() {
+        let samples = render(NoiseKind::White, 1, 100000);
+        let n = samples.len() as f32;
+        let mean = samples.iter().sum::<f32>() / n;
+        let variance = samples.iter().map(|s| s * s).sum::<f32>() / n;
+        let correlation = samples.windows(2).map(|w| w[0] * w[1]).sum::<f32>() / n / variance;
+
+        assert!(samples.iter().all(|s| s.abs() <= 1.));
+        assert!(mean.abs() < 0.01);
+        assert!((variance - 1. / 3.).abs() < 0.01);
+        assert!(correlation.abs() < 0.02);
+    }
+
+    #[test]
+    fn spectra_slope() {
+        let white = two_octave_ratio(NoiseKind::White);
+        let pink = two_octave_ratio(NoiseKind::Pink);
+        let brown = two_octave_ratio(NoiseKind::Brown);
+
+        // Flat, 3 dB and 6 dB per octave
+        assert!(white > 0.8 && white < 1.25, "{}", white);
+        assert!(pink > 3. && pink < 5.3, "{}", pink);
+        assert!(brown > 11. && brown < 22., "{}", brown);
+    }
+
+    #[test]
+    fn noise_is_deterministic_under_seed() {
+        for kind in [NoiseKind::White, NoiseKind::Pink, NoiseKind::Brown].iter() {
+            assert_eq!(render(*kind, 3, 1000), render(*kind, 3, 1000));
+            assert_ne!(render(*kind, 3, 1000), render(*kind, 4, 1000));
+            assert!(render(*kind, 3, 1000).iter().all(|s| s.abs() <= 1.));
+        }
+    }
+
+    #[test]
+    fn periodic_noise_repeats() {
+        for (short, period) in [(false, 32767), (true, 93)].iter() {
+            let kind = NoiseKind::Periodic { short: *short };
+            let samples = render(kind, 0, period * 2);
+            let noise = Oscillator::Noise {
+                kind,
+                frequency: 1.,
+                seed: 0,
+            };
+
+            assert_eq!(*period as f64, noise.period());
+            assert_eq!(samples[..*period], samples[*period..]);
+            assert!(samples.iter().all(|s| s.abs() == 1.));
+            assert_ne!(samples[..*period / 2], samples[*period / 2..*period]);
+        }
     }
 }
@@ -0,0 +1,330 @@
+use crate::rng::Rng;
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    path::Path,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SampleFormat {
+    Int16,
+    Int24,
+    Int32,
+    Float32,
+}
+
+impl SampleFormat {
+    pub fn bits(&self) -> u16 {
+        match self {
+            SampleFormat::Int16 => 16,
+            SampleFormat::Int24 => 24,
+            SampleFormat::Int32 | SampleFormat::Float32 => 32,
+        }
+    }
+}
+
+/// How samples outside of -1 .. 1 are handled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Clipping {
+    /// Samples are cut off at -1 and 1.
+    Hard,
+    /// Samples above the knee are smoothly saturated towards 1.
+    Soft { knee: f32 },
+    /// Gain is reduced instantly to keep peaks at the ceiling, then recovers over `release` seconds.
+    Limit { ceiling: f32, release: f32 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OutputFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub sample_format: SampleFormat,
+    pub clipping: Clipping,
+    /// Whether TPDF dither is added when reducing to an integer format.
+    pub dither: bool,
+}
+
+impl OutputFormat {
+    pub fn new(channels: u16, sample_rate: u32, sample_format: SampleFormat) -> Self {
+        Self {
+            channels,
+            sample_rate,
+            sample_format,
+            clipping: Clipping::Hard,
+            dither: sample_format != SampleFormat::Float32,
+        }
+    }
+
+    pub fn with_clipping(mut self, clipping: Clipping) -> Self {
+        self.clipping = clipping;
+        self
+    }
+
+    pub fn with_dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub fn spec(&self) -> hound::WavSpec {
+        hound::WavSpec {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: self.sample_format.bits(),
+            sample_format: match self.sample_format {
+                SampleFormat::Float32 => hound::SampleFormat::Float,
+                _ => hound::SampleFormat::Int,
+            },
+        }
+    }
+}
+
+impl Default for OutputFormat {
+    /// Mono 16 bit audio at 44.1 kHz.
+    fn default() -> Self {
+        Self::new(1, 44100, SampleFormat::Int16)
+    }
+}
+
+/// Writes audio to a WAV file, handling clipping, dither and conversion to the sample format.
+pub struct WavOutput<W>
+where
+    W: Write + Seek,
+{
+    writer: hound::WavWriter<W>,
+    format: OutputFormat,
+    rng: Rng,
+    limiter_gain: f32,
+    frame: Vec<f32>,
+}
+
+impl WavOutput<BufWriter<File>> {
+    pub fn create<P>(path: P, format: OutputFormat) -> Result<Self, hound::Error>
+    where
+        P: AsRef<Path>,
+    {
+        // Checked before the file is truncated
+        if format.channels == 0 {
+            return Err(hound::Error::Unsupported);
+        }
+
+        let writer = BufWriter::new(File::create(path)?);
+        Self::new(writer, format)
+    }
+}
+
+impl<W> WavOutput<W>
+where
+    W: Write + Seek,
+{
+    /// Fails with `Unsupported` when the format has no channels.
+    pub fn new(writer: W, format: OutputFormat) -> Result<Self, hound::Error> {
+        if format.channels == 0 {
+            return Err(hound::Error::Unsupported);
+        }
+
+        Ok(Self {
+            writer: hound::WavWriter::new(writer, format.spec())?,
+            format,
+            rng: Rng::new(0),
+            limiter_gain: 1.,
+            frame: Vec::with_capacity(format.channels as usize),
+        })
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Writes one sample per channel. A single sample is copied to every channel.
+    pub fn write_frame(&mut self, frame: &[f32]) -> Result<(), hound::Error> {
+        let channels = self.format.channels as usize;
+        self.frame.clear();
+        match frame.len() {
+            1 => self.frame.resize(channels, frame[0]),
+            _ => {
+                self.frame.extend_from_slice(frame);
+                self.frame.resize(channels, 0.);
+            }
+        }
+
+        self.clip();
+
+        for index in 0..channels {
+            let sample = self.frame[index];
+            self.write_sample(sample)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes mono samples, copying each to every channel.
+    pub fn write_mono(&mut self, samples: &[f32]) -> Result<(), hound::Error> {
+        for sample in samples {
+            self.write_frame(&[*sample])?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes samples that are already interleaved by channel.
+    pub fn write_interleaved(&mut self, samples: &[f32]) -> Result<(), hound::Error> {
+        for frame in samples.chunks(self.format.channels as usize) {
+            self.write_frame(frame)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes any buffered samples and updates the header, so the file is valid if writing stops here.
+    pub fn flush(&mut self) -> Result<(), hound::Error> {
+        self.writer.flush()
+    }
+
+    pub fn finalize(self) -> Result<(), hound::Error> {
+        self.writer.finalize()
+    }
+
+    fn clip(&mut self) {
+        match self.format.clipping {
+            Clipping::Hard => {}
+            Clipping::Soft { knee } => {
+                for sample in self.frame.iter_mut() {
+                    *sample = soft_clip(*sample, knee);
+                }
+            }
+            Clipping::Limit { ceiling, release } => {
+                let peak = self.frame.iter().fold(0_f32, |peak, s| peak.max(s.abs()));
+                let recovery = 1. / (release.max(0.001) * self.format.sample_rate as f32);
+                self.limiter_gain = (self.limiter_gain + recovery).min(1.);
+                if peak * self.limiter_gain > ceiling {
+                    self.limiter_gain = ceiling / peak;
+                }
+
+                for sample in self.frame.iter_mut() {
+                    *sample *= self.limiter_gain;
+                }
+            }
+        }
+    }
+
+    fn write_sample(&mut self, sample: f32) -> Result<(), hound::Error> {
+        let sample = sample.clamp(-1., 1.);
+        let bits = match self.format.sample_format {
+            SampleFormat::Float32 => return self.writer.write_sample(sample),
+            format => format.bits(),
+        };
+
+        let max = ((1_i64 << (bits - 1)) - 1) as f64;
+        let mut scaled = sample as f64 * max;
+        if self.format.dither {
+            // Triangular noise spanning +- 1 least significant bit
+            scaled += (self.rng.next_f32() - self.rng.next_f32()) as f64;
+        }
+
+        let quantized = scaled.round().max(-max - 1.).min(max) as i32;
+        self.writer.write_sample(quantized)
+    }
+}
+
+/// Leaves samples below the knee untouched and saturates the rest towards 1.
+fn soft_clip(sample: f32, knee: f32) -> f32 {
+    let knee = knee.clamp(0., 0.999);
+    let magnitude = sample.abs();
+    if magnitude <= knee {
+        return sample;
+    }
+
+    let range = 1. - knee;
+    let saturated = knee + range * ((magnitude - knee) / range).tanh();
+    saturated.copysign(sample)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn render(format: OutputFormat, frames: &[&[f32]]) -> Vec<u8> {
+        let mut cursor = Cursor::new(vec![]);
+        let mut output = WavOutput::new(&mut cursor, format).unwrap();
+        for frame in frames {
+            output.write_frame(frame).unwrap();
+        }
+        output.finalize().unwrap();
+
+        cursor.into_inner()
+    }
+
+    fn read_ints(bytes: Vec<u8>) -> (hound::WavSpec, Vec<i32>) {
+        let mut reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        let samples = reader.samples::<i32>().map(|s| s.unwrap()).collect();
+        (reader.spec(), samples)
+    }
+
+    #[test]
+    fn writes_integer_formats_without_dither() {
+        for (format, max) in [
+            (SampleFormat::Int16, i16::MAX as i32),
+            (SampleFormat::Int24, (1 << 23) - 1),
+            (SampleFormat::Int32, i32::MAX),
+        ]
+        .iter()
+        {
+            let format = OutputFormat::new(1, 48000, *format).with_dither(false);
+            let (spec, samples) = read_ints(render(format, &[&[1.], &[-1.], &[0.5], &[2.]]));
+
+            assert_eq!(48000, spec.sample_rate);
+            assert_eq!(*max, samples[0]);
+            assert_eq!(-max, samples[1]);
+            assert!((samples[2] as f64 - *max as f64 / 2.).abs() <= 1.);
+            assert_eq!(*max, samples[3]);
+        }
+    }
+
+    #[test]
+    fn writes_float_and_stereo() {
+        let format = OutputFormat::new(2, 44100, SampleFormat::Float32);
+        let bytes = render(format, &[&[0.25], &[0.5, -0.5]]);
+        let mut reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        let samples: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+
+        assert_eq!(2, reader.spec().channels);
+        assert_eq!(vec![0.25, 0.25, 0.5, -0.5], samples);
+
+        let format = OutputFormat::new(0, 44100, SampleFormat::Float32);
+        assert!(WavOutput::new(Cursor::new(vec![]), format).is_err());
+    }
+
+    #[test]
+    fn dither_is_small_and_unbiased() {
+        let format = OutputFormat::new(1, 44100, SampleFormat::Int16);
+        let silence = vec![[0_f32]; 10000];
+        let frames: Vec<&[f32]> = silence.iter().map(|f| &f[..]).collect();
+        let (_, samples) = read_ints(render(format, &frames));
+
+        assert!(samples.iter().all(|s| s.abs() <= 1));
+        assert!(samples.iter().any(|s| *s != 0));
+        let mean = samples.iter().sum::<i32>() as f32 / samples.len() as f32;
+        assert!(mean.abs() < 0.05);
+    }
+
+    #[test]
+    fn soft_clip_and_limit() {
+        assert_eq!(0.5, soft_clip(0.5, 0.8));
+        assert!(soft_clip(4., 0.8) <= 1. && soft_clip(1.2, 0.8) > 0.99);
+        assert!(soft_clip(-0.9, 0.8) < -0.8);
+
+        let format =
+            OutputFormat::new(1, 100, SampleFormat::Float32).with_clipping(Clipping::Limit {
+                ceiling: 0.5,
+                release: 0.1,
+            });
+        let bytes = render(format, &[&[1.], &[0.5], &[0.25]]);
+        let mut reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        let samples: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+
+        assert_eq!(0.5, samples[0]);
+        assert!(samples[1] < 0.5 && samples[1] > 0.25);
+        assert!(samples[2] > 0.125);
+    }
+}
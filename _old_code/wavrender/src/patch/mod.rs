@@ -0,0 +1,769 @@
+use crate::{
+    envelope::{BreakpointEnvelope, Envelope},
+    filter::{Biquad, BiquadKind, Ladder, StateVariable, SvfMode},
+    graph::{
+        EnvelopeNode, FilterNode, Gain, Graph, GraphError, Mix, Node, NodeId, OperatorNode,
+        OscillatorNode,
+    },
+    operator::Operator,
+    oscillator::{NoiseKind, Oscillator},
+    resample::Oversampled,
+};
+use std::{fmt, path::Path};
+
+mod parser;
+mod song;
+pub use parser::{parse, Expr, Value};
+pub use song::SongFile;
+
+/// The longest a patch may play, in seconds.
+pub const MAX_DURATION: f32 = 60. * 60.;
+/// The most inputs a `mix` node may have.
+const MAX_MIX_INPUTS: usize = 64;
+/// The highest factor a node may be oversampled by.
+const MAX_OVERSAMPLE: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum PatchError {
+    /// The patch could not be read.
+    Io(String),
+    /// The patch is malformed. Lines and columns start at 1.
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Io(message) => write!(f, "{}", message),
+            PatchError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}: {}", line, column, message),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum NodeKind {
+    Oscillator(Oscillator),
+    Operator(f32),
+    Envelope {
+        attack: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+    },
+    Dahdsr([f32; 6]),
+    Gain(f32),
+    Mix(usize),
+    Filter(SvfMode, f32, f32),
+    Biquad(BiquadKind, f32, f32),
+    Ladder(f32, f32),
+    Oversample(usize, Box<NodeKind>),
+}
+
+impl NodeKind {
+    fn parse(expr: &Expr) -> Result<Self, PatchError> {
+        let list = expr.list()?;
+        let kind = list
+            .first()
+            .ok_or_else(|| expr.error("expected a node kind"))?;
+        let args = &list[1..];
+
+        let arity = |count: usize, usage: &str| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(expr.error(format!("expected ({})", usage)))
+            }
+        };
+
+        let numbers = || {
+            args.iter()
+                .map(|a| a.number())
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let noise = |kind, frequency| Oscillator::Noise {
+            kind,
+            frequency,
+            seed: 0,
+        };
+
+        let node = match kind.symbol()? {
+            "oscillator" => {
+                arity(2, "oscillator shape frequency")?;
+                let frequency = args[1].frequency()?;
+                NodeKind::Oscillator(match args[0].symbol()? {
+                    "sine" => Oscillator::Sine { frequency },
+                    "square" => Oscillator::Square { frequency },
+                    "triangle" => Oscillator::Triangle { frequency },
+                    "saw" => Oscillator::Saw { frequency },
+                    "white" => noise(NoiseKind::White, frequency),
+                    "pink" => noise(NoiseKind::Pink, frequency),
+                    "brown" => noise(NoiseKind::Brown, frequency),
+                    shape => return Err(args[0].error(format!("unknown shape `{}`", shape))),
+                })
+            }
+            "operator" => {
+                arity(1, "operator frequency")?;
+                NodeKind::Operator(args[0].frequency()?)
+            }
+            "envelope" => {
+                arity(4, "envelope attack decay sustain release")?;
+                let n = numbers()?;
+                NodeKind::Envelope {
+                    attack: n[0],
+                    decay: n[1],
+                    sustain: n[2],
+                    release: n[3],
+                }
+            }
+            "dahdsr" => {
+                arity(6, "dahdsr delay attack hold decay sustain release")?;
+                let n = numbers()?;
+                NodeKind::Dahdsr([n[0], n[1], n[2], n[3], n[4], n[5]])
+            }
+            "gain" => {
+                arity(1, "gain amount")?;
+                NodeKind::Gain(args[0].number()?)
+            }
+            "mix" => {
+                arity(1, "mix inputs")?;
+                let inputs = args[0].number()?;
+                if !(0. ..=MAX_MIX_INPUTS as f32).contains(&inputs) || inputs.fract() != 0. {
+                    return Err(args[0].error(format!(
+                        "the input count must be a whole number up to {}",
+                        MAX_MIX_INPUTS
+                    )));
+                }
+                NodeKind::Mix(inputs as usize)
+            }
+            "filter" => {
+                arity(3, "filter mode cutoff q")?;
+                let mode = match args[0].symbol()? {
+                    "lowpass" => SvfMode::LowPass,
+                    "highpass" => SvfMode::HighPass,
+                    "bandpass" => SvfMode::BandPass,
+                    "notch" => SvfMode::Notch,
+                    "peak" => SvfMode::Peak,
+                    mode => return Err(args[0].error(format!("unknown filter mode `{}`", mode))),
+                };
+                NodeKind::Filter(mode, args[1].number()?, args[2].number()?)
+            }
+            "biquad" => {
+                if args.len() != 3 && args.len() != 4 {
+                    return Err(expr.error("expected (biquad kind cutoff q [gain-db])"));
+                }
+                let gain_db = args.get(3).map(|a| a.number()).transpose()?.unwrap_or(0.);
+                let kind = match args[0].symbol()? {
+                    "lowpass" => BiquadKind::LowPass,
+                    "highpass" => BiquadKind::HighPass,
+                    "bandpass" => BiquadKind::BandPass,
+                    "notch" => BiquadKind::Notch,
+                    "peaking" => BiquadKind::Peaking { gain_db },
+                    "lowshelf" => BiquadKind::LowShelf { gain_db },
+                    "highshelf" => BiquadKind::HighShelf { gain_db },
+                    kind => return Err(args[0].error(format!("unknown biquad kind `{}`", kind))),
+                };
+                NodeKind::Biquad(kind, args[1].number()?, args[2].number()?)
+            }
+            "ladder" => {
+                arity(2, "ladder cutoff resonance")?;
+                NodeKind::Ladder(args[0].number()?, args[1].number()?)
+            }
+            "oversample" => {
+                arity(2, "oversample factor (node ...)")?;
+                let factor = args[0].number()?;
+                if !(1. ..=MAX_OVERSAMPLE as f32).contains(&factor) || factor.fract() != 0. {
+                    return Err(args[0].error(format!(
+                        "the factor must be a whole number up to {}",
+                        MAX_OVERSAMPLE
+                    )));
+                }
+                NodeKind::Oversample(factor as usize, Box::new(NodeKind::parse(&args[1])?))
+            }
+            kind => return Err(list[0].error(format!("unknown node kind `{}`", kind))),
+        };
+
+        Ok(node)
+    }
+
+    fn make(&self, sample_rate: f32) -> Box<dyn Node> {
+        match self.clone() {
+            NodeKind::Oscillator(oscillator) => Box::new(OscillatorNode::new(oscillator)),
+            NodeKind::Operator(frequency) => Box::new(OperatorNode::new(Operator::new(frequency))),
+            NodeKind::Envelope {
+                attack,
+                decay,
+                sustain,
+                release,
+            } => Box::new(EnvelopeNode::new(Envelope::new(
+                attack,
+                decay,
+                sustain,
+                release,
+                sample_rate,
+            ))),
+            NodeKind::Dahdsr(n) => Box::new(EnvelopeNode::new(BreakpointEnvelope::dahdsr(
+                n[0],
+                n[1],
+                n[2],
+                n[3],
+                n[4],
+                n[5],
+                sample_rate,
+            ))),
+            NodeKind::Gain(gain) => Box::new(Gain::new(gain)),
+            NodeKind::Mix(inputs) => Box::new(Mix::new(inputs)),
+            NodeKind::Filter(mode, cutoff, q) => Box::new(FilterNode::new(StateVariable::new(
+                mode,
+                cutoff,
+                q,
+                sample_rate,
+            ))),
+            NodeKind::Biquad(kind, cutoff, q) => {
+                Box::new(FilterNode::new(Biquad::new(kind, cutoff, q, sample_rate)))
+            }
+            NodeKind::Ladder(cutoff, resonance) => {
+                Box::new(FilterNode::new(Ladder::new(cutoff, resonance, sample_rate)))
+            }
+            NodeKind::Oversample(factor, node) => Box::new(Oversampled::new(
+                node.make(sample_rate * factor as f32),
+                factor,
+            )),
+        }
+    }
+}
+
+/// Either a port or a parameter, picked by name or index.
+#[derive(Clone, Debug, PartialEq)]
+enum Selector {
+    Index(usize),
+    Name(String),
+}
+
+/// A reference to `node` or `(node port)`, along with where it was written.
+#[derive(Clone, Debug, PartialEq)]
+struct Reference {
+    node: usize,
+    selector: Selector,
+    expr: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Statement {
+    Connect(Reference, Reference),
+    Modulate(Reference, Reference, f32),
+    Automate(Reference, Vec<(f32, f32)>),
+}
+
+/// An instrument described by text, built into an audio graph.
+///
+/// ```text
+/// ; A FM bass
+/// (patch
+///   (duration 8)
+///   (node modulator (oscillator sine 130))
+///   (node depth (gain 8))
+///   ; Frequencies can also be note names
+///   (node carrier (operator C2))
+///   (node amp (envelope 2 2 0.5 2))
+///   (node vca (gain 1))
+///   ; Runs the node at 4 times the sample rate
+///   (node drive (oversample 4 (ladder 800 0.5)))
+///   (connect modulator depth)
+///   (connect depth carrier)
+///   (connect carrier vca)
+///   (connect amp (vca gain))
+///   (connect vca drive)
+///   (automate amp gate (0 1) (4 0))
+///   (output drive))
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Patch {
+    sample_rate: f32,
+    duration: f32,
+    names: Vec<String>,
+    nodes: Vec<NodeKind>,
+    statements: Vec<Statement>,
+    output: Option<Reference>,
+}
+
+impl Patch {
+    /// Parses and validates a patch.
+    pub fn parse(source: &str) -> Result<Self, PatchError> {
+        let exprs = parse(source)?;
+        let root = match exprs.as_slice() {
+            [root] => root,
+            [] => {
+                return Err(PatchError::Syntax {
+                    line: 1,
+                    column: 1,
+                    message: "expected (patch ...)".into(),
+                })
+            }
+            [_, extra, ..] => return Err(extra.error("expected a single (patch ...)")),
+        };
+
+        let forms = root.list()?;
+        match forms.first().map(|f| f.symbol()) {
+            Some(Ok("patch")) => {}
+            _ => return Err(root.error("expected (patch ...)")),
+        }
+
+        let mut patch = Self {
+            sample_rate: 44100.,
+            duration: 4.,
+            names: vec![],
+            nodes: vec![],
+            statements: vec![],
+            output: None,
+        };
+
+        for form in forms[1..].iter() {
+            patch.parse_form(form)?;
+        }
+
+        if patch.output.is_none() {
+            return Err(root.error("missing (output node)"));
+        }
+
+        patch.build(patch.sample_rate)?;
+        Ok(patch)
+    }
+
+    pub fn load<P>(path: P) -> Result<Self, PatchError>
+    where
+        P: AsRef<Path>,
+    {
+        let source = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            PatchError::Io(format!("could not read {}: {}", path.as_ref().display(), e))
+        })?;
+
+        Self::parse(&source)
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// The default length to render, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Builds the audio graph for the patch.
+    pub fn build(&self, sample_rate: f32) -> Result<Graph, PatchError> {
+        let mut graph = Graph::new(sample_rate);
+        let nodes: Vec<NodeId> = self
+            .nodes
+            .iter()
+            .map(|n| graph.add_boxed(n.make(sample_rate)))
+            .collect();
+
+        let error = |reference: &Reference, e: GraphError| {
+            let message = match e {
+                GraphError::KindMismatch { output, input } => format!(
+                    "cannot connect a {:?} output to a {:?} input",
+                    output, input
+                ),
+                GraphError::Cycle => "this connection creates a cycle".into(),
+                e => format!("{:?}", e),
+            };
+            reference.expr.error(message)
+        };
+
+        for statement in self.statements.iter() {
+            match statement {
+                Statement::Connect(from, to) => {
+                    let output = self.output_index(from, sample_rate)?;
+                    let input = self.input_index(to, sample_rate)?;
+                    graph
+                        .connect(nodes[from.node], output, nodes[to.node], input)
+                        .map_err(|e| error(to, e))?;
+                }
+                Statement::Modulate(from, to, depth) => {
+                    let output = self.output_index(from, sample_rate)?;
+                    let parameter = self.parameter_index(to, sample_rate)?;
+                    graph
+                        .modulate(nodes[from.node], output, nodes[to.node], parameter, *depth)
+                        .map_err(|e| error(to, e))?;
+                }
+                Statement::Automate(target, points) => {
+                    let parameter = self.parameter_index(target, sample_rate)?;
+                    for (time, value) in points.iter() {
+                        graph
+                            .automate(nodes[target.node], parameter, *time, *value)
+                            .map_err(|e| error(target, e))?;
+                    }
+                }
+            }
+        }
+
+        if let Some(output) = &self.output {
+            let index = self.output_index(output, sample_rate)?;
+            graph
+                .set_output(nodes[output.node], index)
+                .map_err(|e| error(output, e))?;
+        }
+
+        Ok(graph)
+    }
+
+    /// Renders the patch for its duration.
+    pub fn render(&self, sample_rate: f32) -> Result<Vec<f32>, PatchError> {
+        let mut graph = self.build(sample_rate)?;
+        Ok(graph.render((self.duration * sample_rate) as usize))
+    }
+
+    fn parse_form(&mut self, form: &Expr) -> Result<(), PatchError> {
+        let list = form.list()?;
+        let name = list
+            .first()
+            .ok_or_else(|| form.error("expected a statement"))?;
+        let args = &list[1..];
+        let arity = |count: usize, usage: &str| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(form.error(format!("expected ({})", usage)))
+            }
+        };
+
+        match name.symbol()? {
+            "sample-rate" => {
+                arity(1, "sample-rate hz")?;
+                let sample_rate = args[0].number()?;
+                if !sample_rate.is_finite() || sample_rate <= 0. {
+                    return Err(args[0].error("the sample rate must be above 0"));
+                }
+                self.sample_rate = sample_rate;
+            }
+            "duration" => {
+                arity(1, "duration seconds")?;
+                let duration = args[0].number()?;
+                if !(0. ..=MAX_DURATION).contains(&duration) {
+                    return Err(args[0].error(format!(
+                        "the duration must be 0 to {} seconds",
+                        MAX_DURATION
+                    )));
+                }
+                self.duration = duration;
+            }
+            "node" => {
+                arity(2, "node name (kind ...)")?;
+                let name = args[0].symbol()?;
+                if self.names.iter().any(|n| n == name) {
+                    return Err(args[0].error(format!("`{}` is already defined", name)));
+                }
+
+                let kind = NodeKind::parse(&args[1])?;
+                self.names.push(name.into());
+                self.nodes.push(kind);
+            }
+            "connect" => {
+                arity(2, "connect from to")?;
+                let from = self.reference(&args[0])?;
+                let to = self.reference(&args[1])?;
+                self.statements.push(Statement::Connect(from, to));
+            }
+            "modulate" => {
+                arity(3, "modulate from (node parameter) depth")?;
+                let from = self.reference(&args[0])?;
+                let to = self.reference(&args[1])?;
+                let depth = args[2].number()?;
+                self.statements.push(Statement::Modulate(from, to, depth));
+            }
+            "automate" => {
+                if args.len() < 3 {
+                    return Err(form.error("expected (automate node parameter (time value) ...)"));
+                }
+
+                let mut target = self.reference(&args[0])?;
+                target.selector = selector(&args[1])?;
+                let points = args[2..]
+                    .iter()
+                    .map(|point| match point.list()? {
+                        [time, value] => Ok((time.number()?, value.number()?)),
+                        _ => Err(point.error("expected (time value)")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.statements.push(Statement::Automate(target, points));
+            }
+            "output" => {
+                arity(1, "output node")?;
+                self.output = Some(self.reference(&args[0])?);
+            }
+            statement => {
+                return Err(name.error(format!("unknown statement `{}`", statement)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolves `node` or `(node selector)`.
+    fn reference(&self, expr: &Expr) -> Result<Reference, PatchError> {
+        let (name, selector) = match &expr.value {
+            Value::Symbol(name) => (name.as_str(), Selector::Index(0)),
+            Value::List(list) => match list.as_slice() {
+                [name, port] => (name.symbol()?, selector(port)?),
+                _ => return Err(expr.error("expected node or (node port)")),
+            },
+            Value::Number(_) => return Err(expr.error("expected node or (node port)")),
+        };
+
+        let node = self
+            .names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| expr.error(format!("unknown node `{}`", name)))?;
+
+        Ok(Reference {
+            node,
+            selector,
+            expr: expr.clone(),
+        })
+    }
+
+    fn output_index(&self, reference: &Reference, sample_rate: f32) -> Result<usize, PatchError> {
+        let node = self.nodes[reference.node].make(sample_rate);
+        let names: Vec<&str> = node.outputs().iter().map(|p| p.name).collect();
+        self.resolve(reference, &names, "output")
+    }
+
+    fn input_index(&self, reference: &Reference, sample_rate: f32) -> Result<usize, PatchError> {
+        let node = self.nodes[reference.node].make(sample_rate);
+        let names: Vec<&str> = node.inputs().iter().map(|p| p.name).collect();
+        self.resolve(reference, &names, "input")
+    }
+
+    fn parameter_index(
+        &self,
+        reference: &Reference,
+        sample_rate: f32,
+    ) -> Result<usize, PatchError> {
+        let node = self.nodes[reference.node].make(sample_rate);
+        let names: Vec<&str> = node.parameters().iter().map(|p| p.name).collect();
+        self.resolve(reference, &names, "parameter")
+    }
+
+    fn resolve(
+        &self,
+        reference: &Reference,
+        names: &[&str],
+        what: &str,
+    ) -> Result<usize, PatchError> {
+        let node = &self.names[reference.node];
+        match &reference.selector {
+            Selector::Index(index) if *index < names.len() => Ok(*index),
+            Selector::Name(name) => names.iter().position(|n| n == name).ok_or_else(|| {
+                reference
+                    .expr
+                    .error(format!("`{}` has no {} named `{}`", node, what, name))
+            }),
+            Selector::Index(index) => Err(reference
+                .expr
+                .error(format!("`{}` has no {} {}", node, what, index))),
+        }
+    }
+}
+
+fn selector(expr: &Expr) -> Result<Selector, PatchError> {
+    match &expr.value {
+        Value::Number(n) if *n >= 0. && n.fract() == 0. => Ok(Selector::Index(*n as usize)),
+        Value::Symbol(name) => Ok(Selector::Name(name.clone())),
+        _ => Err(expr.error("expected a port name or index")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::peak;
+
+    const FM_BASS: &str = "
+; A FM bass
+(patch
+  (duration 1)
+  (node modulator (oscillator sine 130))
+  (node depth (gain 8))
+  (node carrier (operator 65))
+  (node amp (envelope 0.1 0.1 0.5 0.2))
+  (node vca (gain 1))
+  (node filter (filter lowpass 2000 0.7))
+  (connect modulator depth)
+  (connect depth carrier)
+  (connect carrier vca)
+  (connect amp (vca gain))
+  (connect vca filter)
+  (modulate amp (filter cutoff) 1000)
+  (automate amp gate (0 1) (0.5 0))
+  (output filter))
+";
+
+    fn syntax_error(source: &str) -> (usize, usize, String) {
+        match Patch::parse(source).unwrap_err() {
+            PatchError::Syntax {
+                line,
+                column,
+                message,
+            } => (line, column, message),
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn renders_patch() {
+        let patch = Patch::parse(FM_BASS).unwrap();
+        let samples = patch.render(8000.).unwrap();
+
+        assert_eq!(8000, samples.len());
+        assert!(samples.iter().any(|s| s.abs() > 0.1));
+        assert!(samples[7900..].iter().all(|s| s.abs() < 0.01));
+    }
+
+    #[test]
+    fn reports_unknown_node_kind_on_its_line() {
+        let source = FM_BASS.replace("(oscillator sine 130)", "(oscilator sine 130)");
+
+        assert_eq!(
+            (5, 20, "unknown node kind `oscilator`".into()),
+            syntax_error(&source)
+        );
+    }
+
+    #[test]
+    fn reports_unknown_references() {
+        let source = FM_BASS.replace("(connect depth carrier)", "(connect depth carier)");
+        assert_eq!(
+            (12, 18, "unknown node `carier`".into()),
+            syntax_error(&source)
+        );
+
+        let source = FM_BASS.replace("(vca gain)", "(vca gian)");
+        assert_eq!(
+            (14, 16, "`vca` has no input named `gian`".into()),
+            syntax_error(&source)
+        );
+    }
+
+    #[test]
+    fn reports_invalid_connections() {
+        let source = FM_BASS.replace("(connect vca filter)", "(connect vca (filter 1))");
+        assert_eq!(
+            (
+                15,
+                16,
+                "cannot connect a Audio output to a Control input".into()
+            ),
+            syntax_error(&source)
+        );
+
+        let source = FM_BASS.replace(
+            "(output filter)",
+            "(connect filter modulator)\n  (output filter)",
+        );
+        assert_eq!(
+            (18, 19, "`modulator` has no input 0".into()),
+            syntax_error(&source)
+        );
+
+        let source = FM_BASS.replace(
+            "(output filter)",
+            "(connect filter depth)\n  (output filter)",
+        );
+        assert_eq!(
+            (18, 19, "this connection creates a cycle".into()),
+            syntax_error(&source)
+        );
+    }
+
+    #[test]
+    fn reports_wrong_arguments() {
+        let source = FM_BASS.replace("(gain 8)", "(gain)");
+
+        assert_eq!(
+            (6, 15, "expected (gain amount)".into()),
+            syntax_error(&source)
+        );
+    }
+
+    #[test]
+    fn reports_out_of_range_numbers() {
+        for rate in ["0", "-8000", "1e39"].iter() {
+            let source = FM_BASS.replace("(duration 1)", &format!("(sample-rate {})", rate));
+            assert_eq!(
+                (4, 16, "the sample rate must be above 0".into()),
+                syntax_error(&source)
+            );
+        }
+
+        for duration in ["-1", "1e39", "3601"].iter() {
+            let source = FM_BASS.replace("(duration 1)", &format!("(duration {})", duration));
+            assert_eq!(
+                (4, 13, "the duration must be 0 to 3600 seconds".into()),
+                syntax_error(&source)
+            );
+        }
+
+        let source = FM_BASS.replace("(gain 8)", "(oversample 1000000 (gain 8))");
+        assert_eq!(
+            (6, 27, "the factor must be a whole number up to 16".into()),
+            syntax_error(&source)
+        );
+
+        for inputs in ["2.5", "-1", "1e9"].iter() {
+            let source = FM_BASS.replace("(gain 8)", &format!("(mix {})", inputs));
+            assert_eq!(
+                (
+                    6,
+                    20,
+                    "the input count must be a whole number up to 64".into()
+                ),
+                syntax_error(&source)
+            );
+        }
+    }
+
+    #[test]
+    fn oversampled_nodes_keep_their_parameters() {
+        let source = FM_BASS.replace(
+            "(filter lowpass 2000 0.7)",
+            "(oversample 2 (filter lowpass 2000 0.7))",
+        );
+        let oversampled = Patch::parse(&source).unwrap().render(8000.).unwrap();
+        let plain = Patch::parse(FM_BASS).unwrap().render(8000.).unwrap();
+        assert!((peak(&oversampled) / peak(&plain) - 1.).abs() < 0.1);
+
+        let source = FM_BASS.replace("(gain 8)", "(oversample 1.5 (gain 8))");
+        assert_eq!(
+            (6, 27, "the factor must be a whole number up to 16".into()),
+            syntax_error(&source)
+        );
+    }
+
+    #[test]
+    fn frequencies_accept_note_names() {
+        let named = FM_BASS.replace("(operator 65)", "(operator A1)");
+        let numbered = FM_BASS.replace("(operator 65)", "(operator 55)");
+        assert_eq!(
+            Patch::parse(&numbered).unwrap().render(8000.).unwrap(),
+            Patch::parse(&named).unwrap().render(8000.).unwrap()
+        );
+
+        let source = FM_BASS.replace("(operator 65)", "(operator H2)");
+        assert_eq!(
+            (7, 27, "`H2` is not a note name".into()),
+            syntax_error(&source)
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use super::PatchError;
+use crate::pitch::{midi_to_hz, parse_note};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f32),
+    Symbol(String),
+    List(Vec<Expr>),
+}
+
+/// A value along with where it starts in the source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    pub value: Value,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Expr {
+    pub fn error<S>(&self, message: S) -> PatchError
+    where
+        S: Into<String>,
+    {
+        PatchError::Syntax {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    pub fn number(&self) -> Result<f32, PatchError> {
+        match &self.value {
+            Value::Number(n) => Ok(*n),
+            _ => Err(self.error("expected a number")),
+        }
+    }
+
+    /// A frequency in Hz, or a note name like `C#3`.
+    pub fn frequency(&self) -> Result<f32, PatchError> {
+        match &self.value {
+            Value::Number(n) => Ok(*n),
+            Value::Symbol(s) => parse_note(s)
+                .map(|note| midi_to_hz(note as f32))
+                .map_err(|e| self.error(e.to_string())),
+            _ => Err(self.error("expected a frequency or note name")),
+        }
+    }
+
+    /// A MIDI note number, or a note name like `C#3`.
+    pub fn note(&self) -> Result<u8, PatchError> {
+        match &self.value {
+            Value::Number(n) => Ok(n.clamp(0., 127.) as u8),
+            Value::Symbol(s) => parse_note(s).map_err(|e| self.error(e.to_string())),
+            _ => Err(self.error("expected a note")),
+        }
+    }
+
+    pub fn symbol(&self) -> Result<&str, PatchError> {
+        match &self.value {
+            Value::Symbol(s) => Ok(s),
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    pub fn list(&self) -> Result<&[Expr], PatchError> {
+        match &self.value {
+            Value::List(l) => Ok(l),
+            _ => Err(self.error("expected a list")),
+        }
+    }
+}
+
+/// Parses s-expressions. `;` starts a comment that runs to the end of the line.
+pub fn parse(source: &str) -> Result<Vec<Expr>, PatchError> {
+    // Lists being built along with where they started
+    let mut stack: Vec<(usize, usize, Vec<Expr>)> = vec![];
+    let mut exprs = vec![];
+    let mut chars = source.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+
+    while let Some(c) = chars.next() {
+        let (start_line, start_column) = (line, column);
+        if c == '\n' {
+            line += 1;
+            column = 1;
+            continue;
+        }
+        column += 1;
+
+        let expr = match c {
+            ';' => {
+                while let Some(c) = chars.peek() {
+                    if *c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+                None
+            }
+            '(' => {
+                stack.push((start_line, start_column, vec![]));
+                None
+            }
+            ')' => match stack.pop() {
+                Some((line, column, list)) => Some(Expr {
+                    value: Value::List(list),
+                    line,
+                    column,
+                }),
+                None => {
+                    return Err(PatchError::Syntax {
+                        line: start_line,
+                        column: start_column,
+                        message: "unexpected `)`".into(),
+                    })
+                }
+            },
+            c if c.is_whitespace() => None,
+            c => {
+                let mut token = c.to_string();
+                while let Some(c) = chars.peek() {
+                    if c.is_whitespace() || *c == '(' || *c == ')' || *c == ';' {
+                        break;
+                    }
+                    token.push(*c);
+                    column += 1;
+                    chars.next();
+                }
+
+                let is_number = token
+                    .trim_start_matches('-')
+                    .starts_with(|c: char| c.is_ascii_digit() || c == '.');
+                let value = if is_number {
+                    match token.parse() {
+                        Ok(n) => Value::Number(n),
+                        Err(_) => {
+                            return Err(PatchError::Syntax {
+                                line: start_line,
+                                column: start_column,
+                                message: format!("`{}` is not a valid number", token),
+                            })
+                        }
+                    }
+                } else {
+                    Value::Symbol(token)
+                };
+
+                Some(Expr {
+                    value,
+                    line: start_line,
+                    column: start_column,
+                })
+            }
+        };
+
+        if let Some(expr) = expr {
+            match stack.last_mut() {
+                Some((_, _, list)) => list.push(expr),
+                None => exprs.push(expr),
+            }
+        }
+    }
+
+    match stack.pop() {
+        Some((line, column, _)) => Err(PatchError::Syntax {
+            line,
+            column,
+            message: "`(` is never closed".into(),
+        }),
+        None => Ok(exprs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_lists_with_positions() {
+        let exprs = parse("; comment\n(a 1.5\n  (b -2))").unwrap();
+
+        assert_eq!(1, exprs.len());
+        assert_eq!((2, 1), (exprs[0].line, exprs[0].column));
+        let list = exprs[0].list().unwrap();
+        assert_eq!("a", list[0].symbol().unwrap());
+        assert_eq!(1.5, list[1].number().unwrap());
+        assert_eq!((3, 3), (list[2].line, list[2].column));
+        assert_eq!(-2., list[2].list().unwrap()[1].number().unwrap());
+    }
+
+    #[test]
+    fn reports_unbalanced_parens() {
+        assert_eq!(
+            Err(PatchError::Syntax {
+                line: 2,
+                column: 2,
+                message: "`(` is never closed".into()
+            }),
+            parse("(a)\n (b (c)\n")
+        );
+        assert_eq!(
+            Err(PatchError::Syntax {
+                line: 1,
+                column: 4,
+                message: "unexpected `)`".into()
+            }),
+            parse("(a))")
+        );
+    }
+
+    #[test]
+    fn reports_bad_numbers() {
+        let error = parse("(a\n 1.2.3)").unwrap_err();
+
+        assert_eq!(
+            PatchError::Syntax {
+                line: 2,
+                column: 2,
+                message: "`1.2.3` is not a valid number".into()
+            },
+            error
+        );
+    }
+}
@@ -0,0 +1,539 @@
+use super::{parse, Expr, PatchError};
+use crate::{
+    chip::{ApuChannel, ApuVoice},
+    envelope::Envelope,
+    oscillator::Oscillator,
+    physical::{ModalVoice, PluckedString},
+    sequencer::{Pattern, Song, Step},
+    voice::{Synth, Voice},
+};
+use std::path::Path;
+
+/// The most steps a beat may be divided into.
+const MAX_STEPS_PER_BEAT: u32 = 64;
+/// The most steps a pattern may have.
+const MAX_PATTERN_LENGTH: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Instrument {
+    Synth {
+        oscillator: Oscillator,
+        attack: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+    },
+    Chip(ApuChannel),
+    Pluck {
+        decay: f32,
+        stretch: f32,
+    },
+    Bar(f32),
+    Membrane(f32),
+}
+
+impl Instrument {
+    fn parse(expr: &Expr) -> Result<Self, PatchError> {
+        let list = expr.list()?;
+        let kind = list
+            .first()
+            .ok_or_else(|| expr.error("expected an instrument"))?;
+        let args = &list[1..];
+
+        let instrument = match kind.symbol()? {
+            "synth" => {
+                if args.len() != 2 {
+                    return Err(expr.error("expected (synth shape (envelope a d s r))"));
+                }
+                let frequency = 440.;
+                let oscillator = match args[0].symbol()? {
+                    "sine" => Oscillator::Sine { frequency },
+                    "square" => Oscillator::Pulse {
+                        frequency,
+                        duty: 0.5,
+                    },
+                    "triangle" => Oscillator::Triangle { frequency },
+                    "saw" => Oscillator::Saw { frequency },
+                    shape => return Err(args[0].error(format!("unknown shape `{}`", shape))),
+                };
+
+                let envelope = args[1].list()?;
+                match envelope.first().map(|e| e.symbol()) {
+                    Some(Ok("envelope")) if envelope.len() == 5 => {}
+                    _ => return Err(args[1].error("expected (envelope a d s r)")),
+                }
+                let n = envelope[1..]
+                    .iter()
+                    .map(|a| a.number())
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Instrument::Synth {
+                    oscillator,
+                    attack: n[0],
+                    decay: n[1],
+                    sustain: n[2],
+                    release: n[3],
+                }
+            }
+            "chip" => {
+                let channel = args
+                    .first()
+                    .ok_or_else(|| expr.error("expected (chip channel ...)"))?;
+                let duty = || match args.get(1) {
+                    Some(duty) => Ok(duty.number()?.clamp(0., 3.) as u8),
+                    None => Ok(2),
+                };
+
+                Instrument::Chip(match channel.symbol()? {
+                    "pulse1" => ApuChannel::Pulse1 { duty: duty()? },
+                    "pulse2" => ApuChannel::Pulse2 { duty: duty()? },
+                    "triangle" => ApuChannel::Triangle,
+                    "noise" => ApuChannel::Noise { short: false },
+                    "metallic" => ApuChannel::Noise { short: true },
+                    name => return Err(channel.error(format!("unknown chip channel `{}`", name))),
+                })
+            }
+            "pluck" => {
+                if args.len() > 2 {
+                    return Err(expr.error("expected (pluck [decay [stretch]])"));
+                }
+                Instrument::Pluck {
+                    decay: args.first().map(|a| a.number()).transpose()?.unwrap_or(3.),
+                    stretch: args.get(1).map(|a| a.number()).transpose()?.unwrap_or(0.),
+                }
+            }
+            "bar" | "membrane" => {
+                if args.len() > 1 {
+                    return Err(expr.error(format!("expected ({} [hardness])", kind.symbol()?)));
+                }
+                let hardness = args.first().map(|a| a.number()).transpose()?.unwrap_or(0.5);
+                match kind.symbol()? {
+                    "bar" => Instrument::Bar(hardness),
+                    _ => Instrument::Membrane(hardness),
+                }
+            }
+            kind => return Err(list[0].error(format!("unknown instrument `{}`", kind))),
+        };
+
+        Ok(instrument)
+    }
+
+    fn make(&self, sample_rate: f32) -> Box<dyn Voice> {
+        match self.clone() {
+            Instrument::Synth {
+                oscillator,
+                attack,
+                decay,
+                sustain,
+                release,
+            } => Box::new(Synth::new(
+                oscillator,
+                Envelope::new(attack, decay, sustain, release, sample_rate),
+                sample_rate,
+            )),
+            Instrument::Chip(channel) => Box::new(ApuVoice::new(channel, sample_rate)),
+            Instrument::Pluck { decay, stretch } => Box::new(
+                PluckedString::new(sample_rate)
+                    .with_decay(decay)
+                    .with_stretch(stretch),
+            ),
+            Instrument::Bar(hardness) => {
+                Box::new(ModalVoice::bar(sample_rate).with_hardness(hardness))
+            }
+            Instrument::Membrane(hardness) => {
+                Box::new(ModalVoice::membrane(sample_rate).with_hardness(hardness))
+            }
+        }
+    }
+}
+
+/// A song described by text, in the same syntax as patches.
+///
+/// ```text
+/// (song
+///   (bpm 140)
+///   (swing 0.2)
+///   (track lead (synth saw (envelope 0.01 0.1 0.7 0.2)) 0.4)
+///   (track bass (chip triangle) 0.8)
+///   ; Also (pluck [decay [stretch]]), (bar [hardness]) and (membrane [hardness])
+///   (track guitar (pluck 2 0.3) 0.6)
+///   ; Steps are (step note [velocity [gate]]), with MIDI notes or names
+///   (pattern a 8
+///     (lead (0 C5) (2 D5 0.8) (4 76 1 2))
+///     (bass (0 36) (4 43)))
+///   (order a a))
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SongFile {
+    bpm: f32,
+    sample_rate: f32,
+    steps_per_beat: u32,
+    swing: f32,
+    tail: f32,
+    tracks: Vec<(String, Instrument, f32)>,
+    patterns: Vec<(String, Pattern)>,
+    order: Vec<usize>,
+}
+
+impl SongFile {
+    pub fn parse(source: &str) -> Result<Self, PatchError> {
+        let exprs = parse(source)?;
+        let root = match exprs.as_slice() {
+            [root] => root,
+            [] => {
+                return Err(PatchError::Syntax {
+                    line: 1,
+                    column: 1,
+                    message: "expected (song ...)".into(),
+                })
+            }
+            [_, extra, ..] => return Err(extra.error("expected a single (song ...)")),
+        };
+
+        let forms = root.list()?;
+        match forms.first().map(|f| f.symbol()) {
+            Some(Ok("song")) => {}
+            _ => return Err(root.error("expected (song ...)")),
+        }
+
+        let mut song = Self {
+            bpm: 120.,
+            sample_rate: 44100.,
+            steps_per_beat: 4,
+            swing: 0.,
+            tail: 1.,
+            tracks: vec![],
+            patterns: vec![],
+            order: vec![],
+        };
+
+        for form in forms[1..].iter() {
+            song.parse_form(form)?;
+        }
+
+        if song.order.is_empty() {
+            return Err(root.error("missing (order pattern ...)"));
+        }
+
+        Ok(song)
+    }
+
+    pub fn load<P>(path: P) -> Result<Self, PatchError>
+    where
+        P: AsRef<Path>,
+    {
+        let source = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            PatchError::Io(format!("could not read {}: {}", path.as_ref().display(), e))
+        })?;
+
+        Self::parse(&source)
+    }
+
+    pub fn bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Builds the song with its instruments, at the given tempo and sample rate.
+    pub fn build(&self, bpm: f32, sample_rate: f32) -> Song {
+        let mut song = Song::new(bpm, sample_rate)
+            .with_steps_per_beat(self.steps_per_beat)
+            .with_swing(self.swing)
+            .with_tail(self.tail);
+
+        for (_, instrument, volume) in self.tracks.iter() {
+            song.add_boxed_track(instrument.make(sample_rate), *volume);
+        }
+        for (_, pattern) in self.patterns.iter() {
+            song.add_pattern(pattern.clone());
+        }
+        song.set_order(self.order.clone());
+
+        song
+    }
+
+    fn parse_form(&mut self, form: &Expr) -> Result<(), PatchError> {
+        let list = form.list()?;
+        let name = list
+            .first()
+            .ok_or_else(|| form.error("expected a statement"))?;
+        let args = &list[1..];
+        let arity = |count: usize, usage: &str| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(form.error(format!("expected ({})", usage)))
+            }
+        };
+
+        match name.symbol()? {
+            "bpm" => {
+                arity(1, "bpm tempo")?;
+                let bpm = args[0].number()?;
+                if !bpm.is_finite() || bpm <= 0. {
+                    return Err(args[0].error("the tempo must be above 0"));
+                }
+                self.bpm = bpm;
+            }
+            "sample-rate" => {
+                arity(1, "sample-rate hz")?;
+                let sample_rate = args[0].number()?;
+                if !sample_rate.is_finite() || sample_rate <= 0. {
+                    return Err(args[0].error("the sample rate must be above 0"));
+                }
+                self.sample_rate = sample_rate;
+            }
+            "steps-per-beat" => {
+                arity(1, "steps-per-beat steps")?;
+                let steps = args[0].number()?;
+                if !(1. ..=MAX_STEPS_PER_BEAT as f32).contains(&steps) || steps.fract() != 0. {
+                    return Err(args[0].error(format!(
+                        "the steps per beat must be a whole number from 1 to {}",
+                        MAX_STEPS_PER_BEAT
+                    )));
+                }
+                self.steps_per_beat = steps as u32;
+            }
+            "swing" => {
+                arity(1, "swing amount")?;
+                self.swing = args[0].number()?;
+            }
+            "tail" => {
+                arity(1, "tail seconds")?;
+                let tail = args[0].number()?;
+                if !tail.is_finite() || tail < 0. {
+                    return Err(args[0].error("the tail must be 0 seconds or more"));
+                }
+                self.tail = tail;
+            }
+            "track" => {
+                arity(3, "track name (instrument ...) volume")?;
+                let name = args[0].symbol()?;
+                if self.tracks.iter().any(|(n, _, _)| n == name) {
+                    return Err(args[0].error(format!("`{}` is already defined", name)));
+                }
+
+                let instrument = Instrument::parse(&args[1])?;
+                self.tracks
+                    .push((name.into(), instrument, args[2].number()?));
+            }
+            "pattern" => {
+                if args.len() < 2 {
+                    return Err(form.error("expected (pattern name length (track steps ...) ...)"));
+                }
+                let name = args[0].symbol()?;
+                if self.patterns.iter().any(|(n, _)| n == name) {
+                    return Err(args[0].error(format!("`{}` is already defined", name)));
+                }
+
+                let length = args[1].number()?;
+                if !(1. ..=MAX_PATTERN_LENGTH as f32).contains(&length) || length.fract() != 0. {
+                    return Err(args[1].error(format!(
+                        "the length must be a whole number from 1 to {}",
+                        MAX_PATTERN_LENGTH
+                    )));
+                }
+
+                let mut pattern = Pattern::new(length as usize);
+                for track in args[2..].iter() {
+                    self.parse_track_steps(track, &mut pattern)?;
+                }
+                self.patterns.push((name.into(), pattern));
+            }
+            "order" => {
+                self.order = args
+                    .iter()
+                    .map(|a| {
+                        let name = a.symbol()?;
+                        self.patterns
+                            .iter()
+                            .position(|(n, _)| n == name)
+                            .ok_or_else(|| a.error(format!("unknown pattern `{}`", name)))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            statement => {
+                return Err(list[0].error(format!("unknown statement `{}`", statement)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses `(track (step note [velocity [gate]]) ...)` into the pattern.
+    fn parse_track_steps(&self, expr: &Expr, pattern: &mut Pattern) -> Result<(), PatchError> {
+        let list = expr.list()?;
+        let name_expr = list
+            .first()
+            .ok_or_else(|| expr.error("expected (track steps ...)"))?;
+        let name = name_expr.symbol()?;
+        let track = self
+            .tracks
+            .iter()
+            .position(|(n, _, _)| n == name)
+            .ok_or_else(|| name_expr.error(format!("unknown track `{}`", name)))?;
+
+        for step in list[1..].iter() {
+            let args = step.list()?;
+            if args.len() < 2 || args.len() > 4 {
+                return Err(step.error("expected (step note [velocity [gate]])"));
+            }
+
+            let index = args[0].number()?.max(0.) as usize;
+            if index >= pattern.len() {
+                return Err(step.error(format!("step {} is past the end of the pattern", index)));
+            }
+
+            let mut value = Step::new(args[1].note()?);
+            if let Some(velocity) = args.get(2) {
+                value = value.with_velocity(velocity.number()?);
+            }
+            if let Some(gate) = args.get(3) {
+                let steps = gate.number()?;
+                // Notes would never be released
+                if !steps.is_finite() || steps < 0. {
+                    return Err(gate.error("the gate must be 0 steps or more"));
+                }
+                value = value.with_gate(steps);
+            }
+            pattern.set(track, index, value);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: &str = "
+(song
+  (bpm 120)
+  (sample-rate 8000)
+  (tail 0)
+  (track lead (synth square (envelope 0 0 1 0)) 0.5)
+  (track bass (chip triangle) 1)
+  (pattern a 4
+    (lead (0 A4) (2 81 0.5 2)))
+  (pattern b 4
+    (bass (0 45 1 4)))
+  (order a b a))
+";
+
+    #[test]
+    fn parses_and_renders_song() {
+        let file = SongFile::parse(SONG).unwrap();
+        assert_eq!(120., file.bpm());
+        assert_eq!(8000., file.sample_rate());
+
+        let mut song = file.build(file.bpm(), file.sample_rate());
+        assert_eq!(12, song.steps());
+        let samples = song.render();
+        assert_eq!(12000, samples.len());
+
+        // The first step plays a half volume 440 Hz square for half a step
+        assert!(samples[..500].iter().all(|s| s.abs() == 0.5));
+        assert!(samples[600..1000].iter().all(|s| *s == 0.));
+    }
+
+    #[test]
+    fn tempo_can_be_overridden() {
+        let file = SongFile::parse(SONG).unwrap();
+        let mut song = file.build(240., 8000.);
+
+        assert_eq!(6000, song.render().len());
+    }
+
+    #[test]
+    fn physical_instruments() {
+        let file = SongFile::parse(
+            "(song (sample-rate 8000) (tail 0)
+               (track guitar (pluck 1 0.2) 1)
+               (track drum (membrane) 1)
+               (pattern a 4 (guitar (0 A3)) (drum (2 45)))
+               (order a))",
+        )
+        .unwrap();
+        let samples = file.build(120., 8000.).render();
+
+        assert!(samples[..1000].iter().any(|s| s.abs() > 0.1));
+        assert!(samples[2000..3000].iter().any(|s| s.abs() > 0.1));
+        assert!(SongFile::parse("(song (track a (bar 1 2) 1) (order))").is_err());
+    }
+
+    #[test]
+    fn reports_out_of_range_settings() {
+        let error = |from: &str, to: &str| SongFile::parse(&SONG.replace(from, to)).unwrap_err();
+        let syntax = |line, column, message: &str| PatchError::Syntax {
+            line,
+            column,
+            message: message.into(),
+        };
+
+        for bpm in ["0", "-90", "1e39"].iter() {
+            assert_eq!(
+                syntax(3, 8, "the tempo must be above 0"),
+                error("(bpm 120)", &format!("(bpm {})", bpm))
+            );
+        }
+        assert_eq!(
+            syntax(4, 16, "the sample rate must be above 0"),
+            error("(sample-rate 8000)", "(sample-rate 0)")
+        );
+        for tail in ["-1", "1e39"].iter() {
+            assert_eq!(
+                syntax(5, 9, "the tail must be 0 seconds or more"),
+                error("(tail 0)", &format!("(tail {})", tail))
+            );
+        }
+        for steps in ["0", "1.5", "1e9"].iter() {
+            assert_eq!(
+                syntax(
+                    5,
+                    19,
+                    "the steps per beat must be a whole number from 1 to 64"
+                ),
+                error("(tail 0)", &format!("(steps-per-beat {})", steps))
+            );
+        }
+        for length in ["0", "-4", "2.5", "1e12"].iter() {
+            assert_eq!(
+                syntax(8, 14, "the length must be a whole number from 1 to 1024"),
+                error("(pattern a 4", &format!("(pattern a {}", length))
+            );
+        }
+        for gate in ["-1", "1e39"].iter() {
+            assert_eq!(
+                syntax(9, 28, "the gate must be 0 steps or more"),
+                error("0.5 2)", &format!("0.5 {})", gate))
+            );
+        }
+    }
+
+    #[test]
+    fn reports_unknown_names_with_position() {
+        let error = SongFile::parse("(song (pattern a 4 (drums (0 36))) (order a))").unwrap_err();
+        assert_eq!(
+            PatchError::Syntax {
+                line: 1,
+                column: 21,
+                message: "unknown track `drums`".into()
+            },
+            error
+        );
+
+        let error = SongFile::parse("(song (order a))").unwrap_err();
+        assert_eq!(
+            PatchError::Syntax {
+                line: 1,
+                column: 14,
+                message: "unknown pattern `a`".into()
+            },
+            error
+        );
+    }
+}
@@ -0,0 +1,391 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{pitch::midi_to_hz, rng::Rng, voice::Voice};
+use alloc::{vec, vec::Vec};
+use core::f32::consts::PI;
+
+/// How long a released string or struck body rings, in seconds.
+const MUTED_DECAY: f32 = 0.08;
+
+/// The decay in amplitude per sample that reaches -60 dB after `decay` seconds.
+fn decay_per_sample(decay: f32, sample_rate: f32) -> f32 {
+    0.001_f32.powf(1. / (decay.max(1e-4) * sample_rate))
+}
+
+/// A plucked string using extended Karplus-Strong: a noise burst circulates through a delay
+/// line and a damping filter, with an allpass for exact tuning.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PluckedString {
+    sample_rate: f32,
+    /// Seconds for the fundamental to fall by 60 dB while held.
+    decay: f32,
+    /// 0 .. 1. Higher stretches how long the upper harmonics ring, for a brighter, more
+    /// metallic string. 0 is the original algorithm's two point average.
+    stretch: f32,
+    /// Where the string is plucked, as a fraction of its length from the bridge.
+    pick_position: f32,
+    seed: u64,
+    rng: Rng,
+    note: Option<u8>,
+    line: Vec<f32>,
+    index: usize,
+    /// Loop gain per pass, from the decay.
+    feedback: f32,
+    /// Weight of the previous sample in the damping filter.
+    average: f32,
+    previous: f32,
+    /// Fractional delay allpass coefficient and state.
+    allpass: f32,
+    allpass_in: f32,
+    allpass_out: f32,
+}
+
+impl PluckedString {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            decay: 3.,
+            stretch: 0.,
+            pick_position: 0.2,
+            seed: 0,
+            rng: Rng::new(0),
+            note: None,
+            line: vec![0.],
+            index: 0,
+            feedback: 0.,
+            average: 0.5,
+            previous: 0.,
+            allpass: 0.,
+            allpass_in: 0.,
+            allpass_out: 0.,
+        }
+    }
+
+    pub fn with_decay(mut self, seconds: f32) -> Self {
+        self.decay = seconds;
+        self
+    }
+
+    pub fn with_stretch(mut self, stretch: f32) -> Self {
+        self.stretch = stretch.clamp(0., 0.99);
+        self
+    }
+
+    pub fn with_pick_position(mut self, position: f32) -> Self {
+        self.pick_position = position.clamp(0., 0.5);
+        self
+    }
+
+    /// Seeds the noise each pluck starts from.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    fn pluck(&mut self, frequency: f32, velocity: f32) {
+        let period = self.sample_rate / frequency.max(1.);
+        // The two point average delays by its weight, and the allpass makes up the fraction
+        self.average = 0.5 * (1. - self.stretch);
+        let length = ((period - self.average - 0.1).floor() as usize).max(1);
+        let fraction = period - self.average - length as f32;
+        self.allpass = (1. - fraction) / (1. + fraction);
+        self.feedback = decay_per_sample(self.decay, self.sample_rate).powf(period);
+
+        // Softer plucks are duller
+        let brightness = 0.1 + 0.9 * velocity.clamp(0., 1.);
+        let mut filtered = 0.;
+        let mut burst: Vec<f32> = (0..length)
+            .map(|_| {
+                filtered += brightness * (self.rng.bipolar() - filtered);
+                filtered
+            })
+            .collect();
+
+        // Plucking away from the end cancels the harmonics with a node at that point
+        let pick = (self.pick_position * length as f32).round() as usize;
+        if pick > 0 {
+            for i in (pick..length).rev() {
+                burst[i] -= burst[i - pick];
+            }
+        }
+
+        let mean = burst.iter().sum::<f32>() / length as f32;
+        let peak = burst.iter().fold(0_f32, |p, s| p.max((s - mean).abs()));
+        let scale = velocity / peak.max(1e-6);
+        self.line = burst.iter().map(|s| (s - mean) * scale).collect();
+        self.index = 0;
+        self.previous = 0.;
+        self.allpass_in = 0.;
+        self.allpass_out = 0.;
+    }
+}
+
+impl Voice for PluckedString {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        self.note = Some(note);
+        self.pluck(midi_to_hz(note as f32), velocity);
+    }
+
+    fn note_off(&mut self, note: u8) {
+        if self.note == Some(note) {
+            self.note = None;
+            let period = self.line.len() as f32 + self.average;
+            self.feedback = decay_per_sample(MUTED_DECAY, self.sample_rate).powf(period);
+        }
+    }
+
+    fn tick(&mut self) -> f32 {
+        let out = self.line[self.index];
+        let damped = self.feedback * ((1. - self.average) * out + self.average * self.previous);
+        self.previous = out;
+
+        let tuned = self.allpass * damped + self.allpass_in - self.allpass * self.allpass_out;
+        self.allpass_in = damped;
+        self.allpass_out = tuned;
+
+        self.line[self.index] = tuned;
+        self.index = (self.index + 1) % self.line.len();
+        out
+    }
+}
+
+/// One resonant mode of a struck body.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mode {
+    /// Frequency relative to the fundamental.
+    pub ratio: f32,
+    pub amplitude: f32,
+    /// Seconds to fall by 60 dB.
+    pub decay: f32,
+}
+
+impl Mode {
+    pub fn new(ratio: f32, amplitude: f32, decay: f32) -> Self {
+        Self {
+            ratio,
+            amplitude,
+            decay,
+        }
+    }
+}
+
+/// A two pole resonator ringing at one mode.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Resonator {
+    mode: Mode,
+    /// Feedback coefficients and input gain.
+    a1: f32,
+    a2: f32,
+    gain: f32,
+    y1: f32,
+    y2: f32,
+}
+
+/// A struck object modelled as a bank of decaying resonant modes, such as a bar or a drum head.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModalVoice {
+    sample_rate: f32,
+    modes: Vec<Mode>,
+    /// 0 .. 1. Harder mallets make a shorter strike that excites more of the upper modes.
+    hardness: f32,
+    note: Option<u8>,
+    resonators: Vec<Resonator>,
+    /// The rest of the mallet strike still to be fed in.
+    strike: Vec<f32>,
+}
+
+impl ModalVoice {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            modes: vec![],
+            hardness: 0.5,
+            note: None,
+            resonators: vec![],
+            strike: vec![],
+        }
+    }
+
+    /// A free bar, like a glockenspiel or marimba without its tuned undercut.
+    pub fn bar(sample_rate: f32) -> Self {
+        // Free-free beam modes, (2n + 1)^2 relative to the first
+        [1., 2.756, 5.404, 8.933, 13.345].iter().enumerate().fold(
+            Self::new(sample_rate),
+            |voice, (i, ratio)| {
+                voice.with_mode(Mode::new(*ratio, 1. / (i + 1) as f32, 2.5 / ratio))
+            },
+        )
+    }
+
+    /// A circular drum head.
+    pub fn membrane(sample_rate: f32) -> Self {
+        // Zeros of the Bessel functions relative to the first
+        let modes = [
+            (1., 1.),
+            (1.594, 0.8),
+            (2.136, 0.6),
+            (2.296, 0.5),
+            (2.653, 0.4),
+            (2.918, 0.35),
+            (3.156, 0.3),
+            (3.501, 0.25),
+        ];
+        modes
+            .iter()
+            .fold(Self::new(sample_rate), |voice, (ratio, amplitude)| {
+                voice.with_mode(Mode::new(*ratio, *amplitude, 0.6 / ratio))
+            })
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.modes.push(mode);
+        self
+    }
+
+    pub fn with_hardness(mut self, hardness: f32) -> Self {
+        self.hardness = hardness.clamp(0., 1.);
+        self
+    }
+
+    /// Tunes the resonators to a fundamental, each decaying over its mode's time or `decay`
+    /// if given.
+    fn tune(&mut self, frequency: f32, decay: Option<f32>) {
+        let nyquist = self.sample_rate / 2.;
+        let sample_rate = self.sample_rate;
+        let previous = core::mem::take(&mut self.resonators);
+
+        self.resonators = self
+            .modes
+            .iter()
+            .filter(|m| m.ratio * frequency < nyquist)
+            .enumerate()
+            .map(|(i, mode)| {
+                let omega = 2. * PI * mode.ratio * frequency / sample_rate;
+                let r = decay_per_sample(decay.unwrap_or(mode.decay), sample_rate);
+                let (y1, y2) = previous.get(i).map_or((0., 0.), |p| (p.y1, p.y2));
+                Resonator {
+                    mode: *mode,
+                    a1: 2. * r * omega.cos(),
+                    a2: -r * r,
+                    // Rings at about the mode's amplitude after a unit impulse
+                    gain: mode.amplitude * omega.sin(),
+                    y1,
+                    y2,
+                }
+            })
+            .collect();
+    }
+}
+
+impl Voice for ModalVoice {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        self.note = Some(note);
+        self.tune(midi_to_hz(note as f32), None);
+
+        // A half sine pulse from 0.2 ms for the hardest mallet to 4 ms for the softest
+        let length = ((0.0002 + 0.0038 * (1. - self.hardness)) * self.sample_rate).max(1.);
+        let length = length as usize;
+        let area = length as f32 * 2. / PI;
+        self.strike = (0..length)
+            .rev()
+            .map(|i| velocity * (PI * (i as f32 + 0.5) / length as f32).sin() / area)
+            .collect();
+    }
+
+    fn note_off(&mut self, note: u8) {
+        if self.note == Some(note) {
+            self.note = None;
+            self.tune(midi_to_hz(note as f32), Some(MUTED_DECAY));
+        }
+    }
+
+    fn tick(&mut self) -> f32 {
+        let input = self.strike.pop().unwrap_or(0.);
+        self.resonators
+            .iter_mut()
+            .map(|r| {
+                let y = r.gain * input + r.a1 * r.y1 + r.a2 * r.y2;
+                r.y2 = r.y1;
+                r.y1 = y;
+                y
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{autocorrelation_pitch, rms, Spectrum, Window};
+
+    const SAMPLE_RATE: f32 = 44100.;
+
+    fn play<V: Voice>(voice: &mut V, note: u8, frames: usize) -> Vec<f32> {
+        voice.note_on(note, 1.);
+        (0..frames).map(|_| voice.tick()).collect()
+    }
+
+    #[test]
+    fn plucked_string_is_in_tune() {
+        for note in [45, 69, 96].iter() {
+            let mut string = PluckedString::new(SAMPLE_RATE);
+            let samples = play(&mut string, *note, 32768);
+            let expected = midi_to_hz(*note as f32);
+            let found = autocorrelation_pitch(&samples[..8192], SAMPLE_RATE, 50., 3000.).unwrap();
+            assert!((found / expected - 1.).abs() < 0.003, "{}: {}", note, found);
+        }
+    }
+
+    #[test]
+    fn string_decays_and_stretch_keeps_harmonics_ringing() {
+        let mut string = PluckedString::new(SAMPLE_RATE).with_decay(0.5);
+        let samples = play(&mut string, 57, 44100);
+        let level = rms(&samples[22050..22491]) / rms(&samples[..441]);
+        // The fundamental is down 60 dB at 0.5 s, and the upper harmonics further still
+        assert!(level < 0.002, "{}", level);
+
+        let harmonic_ratio = |stretch: f32| {
+            let mut string = PluckedString::new(SAMPLE_RATE).with_stretch(stretch);
+            let samples = play(&mut string, 57, 66150);
+            let spectrum = Spectrum::new(&samples[44100..], SAMPLE_RATE, Window::BlackmanHarris);
+            spectrum.magnitude_at(220. * 15.) / spectrum.magnitude_at(220.)
+        };
+        // After a second the 15th harmonic has all but gone from the plain string
+        assert!(harmonic_ratio(0.8) > 10. * harmonic_ratio(0.));
+
+        let mut string = PluckedString::new(SAMPLE_RATE);
+        let held = play(&mut string, 57, 4410);
+        string.note_off(57);
+        let released: Vec<f32> = (0..8820).map(|_| string.tick()).collect();
+        assert!(rms(&released[4410..]) < rms(&held) * 1e-3);
+    }
+
+    #[test]
+    fn modal_voices_ring_at_their_modes() {
+        let mut bar = ModalVoice::bar(SAMPLE_RATE).with_hardness(1.);
+        let samples = play(&mut bar, 69, 32768);
+        let spectrum = Spectrum::new(&samples, SAMPLE_RATE, Window::BlackmanHarris);
+        assert!((spectrum.peak_frequency() - 440.).abs() < 1.);
+        let second = spectrum.magnitude_at(440. * 2.756);
+        assert!(second > 10. * spectrum.magnitude_at(440. * 2.));
+
+        let mut drum = ModalVoice::membrane(SAMPLE_RATE);
+        let samples = play(&mut drum, 45, 32768);
+        let spectrum = Spectrum::new(&samples, SAMPLE_RATE, Window::BlackmanHarris);
+        assert!(spectrum.magnitude_at(110. * 1.594) > 10. * spectrum.magnitude_at(110. * 1.3));
+
+        // Soft mallets excite the upper modes less
+        let brightness = |hardness: f32| {
+            let mut bar = ModalVoice::bar(SAMPLE_RATE).with_hardness(hardness);
+            let samples = play(&mut bar, 69, 8192);
+            let spectrum = Spectrum::new(&samples, SAMPLE_RATE, Window::BlackmanHarris);
+            spectrum.magnitude_at(440. * 8.933) / spectrum.magnitude_at(440.)
+        };
+        assert!(brightness(1.) > 2. * brightness(0.));
+
+        bar.note_off(69);
+        let released: Vec<f32> = (0..8820).map(|_| bar.tick()).collect();
+        assert!(rms(&released[4410..]) < 1e-4);
+    }
+}
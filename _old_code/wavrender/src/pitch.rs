@@ -0,0 +1,671 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use alloc::{format, string::String, vec, vec::Vec};
+use core::{fmt, ops::RangeInclusive};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+/// The MIDI note tuned to the reference frequency, A4.
+const REFERENCE_NOTE: f32 = 69.;
+const REFERENCE_FREQUENCY: f32 = 440.;
+const NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum PitchError {
+    Io(String),
+    /// A note name that couldn't be parsed.
+    InvalidNote(String),
+    /// A malformed Scala file. Lines start at 1.
+    Scala {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for PitchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PitchError::Io(message) => write!(f, "{}", message),
+            PitchError::InvalidNote(name) => write!(f, "`{}` is not a note name", name),
+            PitchError::Scala { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PitchError {}
+
+/// The equal tempered frequency of a MIDI note, which may be fractional.
+pub fn midi_to_hz(note: f32) -> f32 {
+    REFERENCE_FREQUENCY * semitones_to_ratio(note - REFERENCE_NOTE)
+}
+
+pub fn hz_to_midi(frequency: f32) -> f32 {
+    REFERENCE_NOTE + 12. * (frequency / REFERENCE_FREQUENCY).log2()
+}
+
+/// The amount to multiply a frequency by to move it by semitones.
+pub fn semitones_to_ratio(semitones: f32) -> f32 {
+    2_f32.powf(semitones / 12.)
+}
+
+pub fn cents_to_ratio(cents: f32) -> f32 {
+    2_f32.powf(cents / 1200.)
+}
+
+pub fn ratio_to_cents(ratio: f32) -> f32 {
+    1200. * ratio.log2()
+}
+
+pub fn transpose(frequency: f32, semitones: f32) -> f32 {
+    frequency * semitones_to_ratio(semitones)
+}
+
+/// Parses a note name like `C4`, `F#2`, `Bb-1` into a MIDI note, where `C4` is 60.
+pub fn parse_note(name: &str) -> Result<u8, PitchError> {
+    let invalid = || PitchError::InvalidNote(name.into());
+    let mut chars = name.chars();
+    let letter = chars.next().ok_or_else(invalid)?;
+    let mut semitone: i32 = match letter.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return Err(invalid()),
+    };
+
+    let rest = chars.as_str();
+    let octave = rest.trim_start_matches(['#', 'b']);
+    for accidental in rest[..rest.len() - octave.len()].chars() {
+        semitone += if accidental == '#' { 1 } else { -1 };
+    }
+
+    let octave: i32 = octave.parse().map_err(|_| invalid())?;
+    let note = (octave + 1) * 12 + semitone;
+    if (0..=127).contains(&note) {
+        Ok(note as u8)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// The name of a MIDI note using sharps, like `C#4`.
+pub fn note_name(note: u8) -> String {
+    format!("{}{}", NAMES[note as usize % 12], note as i32 / 12 - 1)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScaleKind {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    MelodicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    WholeTone,
+    Chromatic,
+}
+
+impl ScaleKind {
+    /// Semitones above the root for each degree within an octave.
+    pub fn intervals(self) -> &'static [u8] {
+        match self {
+            ScaleKind::Major => &[0, 2, 4, 5, 7, 9, 11],
+            ScaleKind::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+            ScaleKind::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            ScaleKind::MelodicMinor => &[0, 2, 3, 5, 7, 9, 11],
+            ScaleKind::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            ScaleKind::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            ScaleKind::Lydian => &[0, 2, 4, 6, 7, 9, 11],
+            ScaleKind::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            ScaleKind::Locrian => &[0, 1, 3, 5, 6, 8, 10],
+            ScaleKind::MajorPentatonic => &[0, 2, 4, 7, 9],
+            ScaleKind::MinorPentatonic => &[0, 3, 5, 7, 10],
+            ScaleKind::Blues => &[0, 3, 5, 6, 7, 10],
+            ScaleKind::WholeTone => &[0, 2, 4, 6, 8, 10],
+            ScaleKind::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        }
+    }
+}
+
+/// A scale starting on a root note.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Scale {
+    pub root: u8,
+    pub kind: ScaleKind,
+}
+
+impl Scale {
+    pub fn new(root: u8, kind: ScaleKind) -> Self {
+        Self { root, kind }
+    }
+
+    /// The note of a degree counting from 0 at the root. Degrees past the scale continue into
+    /// the next octaves, and negative degrees go below the root.
+    pub fn degree(&self, degree: i32) -> Option<u8> {
+        let intervals = self.kind.intervals();
+        let length = intervals.len() as i32;
+        let octave = degree.div_euclid(length);
+        let note =
+            self.root as i32 + octave * 12 + intervals[degree.rem_euclid(length) as usize] as i32;
+
+        if (0..=127).contains(&note) {
+            Some(note as u8)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains(&self, note: u8) -> bool {
+        let semitone = (note as i32 - self.root as i32).rem_euclid(12) as u8;
+        self.kind.intervals().contains(&semitone)
+    }
+
+    /// The nearest note in the scale, preferring the lower one on ties.
+    pub fn quantize(&self, note: u8) -> u8 {
+        (0..=6)
+            .flat_map(|distance| vec![note as i32 - distance, note as i32 + distance])
+            .filter(|n| (0..=127).contains(n))
+            .map(|n| n as u8)
+            .find(|n| self.contains(*n))
+            .unwrap_or(note)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ChordKind {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Major7,
+    Minor7,
+    Dominant7,
+    Diminished7,
+    HalfDiminished7,
+}
+
+impl ChordKind {
+    /// Semitones above the root for each note.
+    pub fn intervals(self) -> &'static [u8] {
+        match self {
+            ChordKind::Major => &[0, 4, 7],
+            ChordKind::Minor => &[0, 3, 7],
+            ChordKind::Diminished => &[0, 3, 6],
+            ChordKind::Augmented => &[0, 4, 8],
+            ChordKind::Sus2 => &[0, 2, 7],
+            ChordKind::Sus4 => &[0, 5, 7],
+            ChordKind::Major7 => &[0, 4, 7, 11],
+            ChordKind::Minor7 => &[0, 3, 7, 10],
+            ChordKind::Dominant7 => &[0, 4, 7, 10],
+            ChordKind::Diminished7 => &[0, 3, 6, 9],
+            ChordKind::HalfDiminished7 => &[0, 3, 6, 10],
+        }
+    }
+}
+
+/// The notes of a chord on a root, leaving out any above the MIDI range.
+pub fn chord(root: u8, kind: ChordKind) -> Vec<u8> {
+    kind.intervals()
+        .iter()
+        .map(|i| root as u32 + *i as u32)
+        .filter(|n| *n <= 127)
+        .map(|n| n as u8)
+        .collect()
+}
+
+/// Which scale degree each key plays, from a Scala `.kbm` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyboardMapping {
+    first_note: u8,
+    last_note: u8,
+    /// The key where the first entry of the map, and degree 0, fall.
+    middle_note: u8,
+    reference_note: u8,
+    reference_frequency: f32,
+    /// The degree the map repeats at. `None` uses the scale's period.
+    octave_degree: Option<usize>,
+    /// Degrees for each key in a repeat, `None` for keys left silent. Empty maps every key to the
+    /// next degree.
+    map: Vec<Option<usize>>,
+}
+
+impl Default for KeyboardMapping {
+    /// Every key plays the next degree, with degree 0 on middle C and A4 at 440 Hz.
+    fn default() -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: REFERENCE_NOTE as u8,
+            reference_frequency: REFERENCE_FREQUENCY,
+            octave_degree: None,
+            map: vec![],
+        }
+    }
+}
+
+impl KeyboardMapping {
+    pub fn parse(source: &str) -> Result<Self, PitchError> {
+        let mut lines = scala_lines(source);
+        let mut next = |what: &str| {
+            lines.next().ok_or_else(|| PitchError::Scala {
+                line: source.lines().count() + 1,
+                message: format!("expected {}", what),
+            })
+        };
+        // Counts and notes must be whole numbers in range
+        let mut number = |what: &str, range: RangeInclusive<f32>, whole: bool| {
+            let (line, text) = next(what)?;
+            let value = text.split_whitespace().next().unwrap_or("");
+            let number: f32 = value.parse().map_err(|_| PitchError::Scala {
+                line,
+                message: format!("expected {}, found `{}`", what, value),
+            })?;
+            if !range.contains(&number) || (whole && number.fract() != 0.) {
+                return Err(PitchError::Scala {
+                    line,
+                    message: format!("`{}` is out of range for {}", value, what),
+                });
+            }
+            Ok(number)
+        };
+        let count = 0. ..=usize::MAX as f32;
+        let note = 0. ..=127.;
+
+        let size = number("the map size", count.clone(), true)? as usize;
+        let first_note = number("the first note", note.clone(), true)? as u8;
+        let last_note = number("the last note", note.clone(), true)? as u8;
+        let middle_note = number("the middle note", note.clone(), true)? as u8;
+        let reference_note = number("the reference note", note, true)? as u8;
+        let reference_frequency = number(
+            "the reference frequency",
+            f32::MIN_POSITIVE..=f32::MAX,
+            false,
+        )?;
+        let octave_degree = number("the octave degree", count, true)? as usize;
+
+        // The size isn't trusted for an allocation, the entries run out first if it's too big
+        let mut map = vec![];
+        for _ in 0..size {
+            let (line, text) = next("a map entry")?;
+            let value = text.split_whitespace().next().unwrap_or("");
+            map.push(match value {
+                "x" | "X" => None,
+                _ => Some(value.parse().map_err(|_| PitchError::Scala {
+                    line,
+                    message: format!("expected a degree or x, found `{}`", value),
+                })?),
+            });
+        }
+
+        Ok(Self {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_frequency,
+            octave_degree: if size == 0 { None } else { Some(octave_degree) },
+            map,
+        })
+    }
+
+    /// The scale degree a key plays, counting from the middle note.
+    fn degree(&self, note: u8, scale_size: usize) -> Option<i64> {
+        if note < self.first_note || note > self.last_note {
+            return None;
+        }
+
+        let offset = note as i64 - self.middle_note as i64;
+        if self.map.is_empty() {
+            return Some(offset);
+        }
+
+        let size = self.map.len() as i64;
+        let octave = offset.div_euclid(size);
+        let degree = self.map[offset.rem_euclid(size) as usize]? as i64;
+        let octave_degree = self.octave_degree.unwrap_or(scale_size) as i64;
+        Some(octave * octave_degree + degree)
+    }
+}
+
+/// A tuning from a Scala `.scl` scale and an optional keyboard mapping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    description: String,
+    /// Cents above the root for each degree after the root. The last is the period, usually an
+    /// octave.
+    cents: Vec<f64>,
+    mapping: KeyboardMapping,
+}
+
+impl Default for Tuning {
+    /// 12 tone equal temperament with A4 at 440 Hz.
+    fn default() -> Self {
+        Self::equal(12)
+    }
+}
+
+impl Tuning {
+    /// Divides the octave into equal steps.
+    pub fn equal(steps: usize) -> Self {
+        let steps = steps.max(1);
+        Self {
+            description: format!("{} tone equal temperament", steps),
+            cents: (1..=steps)
+                .map(|i| 1200. * i as f64 / steps as f64)
+                .collect(),
+            mapping: KeyboardMapping::default(),
+        }
+    }
+
+    /// Parses a Scala `.scl` file. Pitches with a `.` are in cents, others are ratios like `3/2`.
+    pub fn parse_scala(source: &str) -> Result<Self, PitchError> {
+        let mut lines = scala_lines(source);
+        let end = source.lines().count() + 1;
+        let (_, description) = lines.next().ok_or(PitchError::Scala {
+            line: end,
+            message: "expected a description".into(),
+        })?;
+
+        let (line, count) = lines.next().ok_or(PitchError::Scala {
+            line: end,
+            message: "expected the number of notes".into(),
+        })?;
+        let count: usize = count
+            .split_whitespace()
+            .next()
+            .and_then(|c| c.parse().ok())
+            .ok_or(PitchError::Scala {
+                line,
+                message: "expected the number of notes".into(),
+            })?;
+
+        let mut cents = vec![];
+        for _ in 0..count {
+            let (line, text) = lines.next().ok_or(PitchError::Scala {
+                line: end,
+                message: format!("expected {} notes", count),
+            })?;
+            let value = text.split_whitespace().next().unwrap_or("");
+            let invalid = || PitchError::Scala {
+                line,
+                message: format!("`{}` is not a pitch", value),
+            };
+
+            let pitch = if value.contains('.') {
+                value.parse().map_err(|_| invalid())?
+            } else {
+                let mut parts = value.splitn(2, '/');
+                let numerator: f64 = parts
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(invalid)?;
+                let denominator: f64 = match parts.next() {
+                    Some(d) => d.parse().map_err(|_| invalid())?,
+                    None => 1.,
+                };
+                if numerator <= 0. || denominator <= 0. {
+                    return Err(invalid());
+                }
+                1200. * (numerator / denominator).log2()
+            };
+            cents.push(pitch);
+        }
+
+        if cents.is_empty() {
+            return Err(PitchError::Scala {
+                line,
+                message: "a scale needs at least one note".into(),
+            });
+        }
+
+        Ok(Self {
+            description: description.trim().into(),
+            cents,
+            mapping: KeyboardMapping::default(),
+        })
+    }
+
+    /// Loads a `.scl` file, and a `.kbm` file if given.
+    #[cfg(feature = "std")]
+    pub fn load<P, Q>(scale: P, mapping: Option<Q>) -> Result<Self, PitchError>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let read = |path: &Path| {
+            std::fs::read_to_string(path)
+                .map_err(|e| PitchError::Io(format!("could not read {}: {}", path.display(), e)))
+        };
+
+        let tuning = Self::parse_scala(&read(scale.as_ref())?)?;
+        match mapping {
+            Some(path) => Ok(tuning.with_mapping(KeyboardMapping::parse(&read(path.as_ref())?)?)),
+            None => Ok(tuning),
+        }
+    }
+
+    pub fn with_mapping(mut self, mapping: KeyboardMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// The frequency of a MIDI note, or `None` if the mapping leaves it unplayed.
+    pub fn frequency(&self, note: u8) -> Option<f32> {
+        let degree = self.mapping.degree(note, self.cents.len())?;
+        let reference = self
+            .mapping
+            .degree(self.mapping.reference_note, self.cents.len())
+            .unwrap_or(0);
+
+        let cents = self.degree_cents(degree) - self.degree_cents(reference);
+        Some(self.mapping.reference_frequency * 2_f64.powf(cents / 1200.) as f32)
+    }
+
+    /// Cents above the root of a degree, which may be in another period.
+    fn degree_cents(&self, degree: i64) -> f64 {
+        let size = self.cents.len() as i64;
+        let period = self.cents[self.cents.len() - 1];
+        let index = degree.rem_euclid(size);
+        let within = if index == 0 {
+            0.
+        } else {
+            self.cents[index as usize - 1]
+        };
+
+        degree.div_euclid(size) as f64 * period + within
+    }
+}
+
+/// The lines of a Scala file with their line numbers, skipping `!` comments.
+fn scala_lines(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.starts_with('!'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_notes_and_hz() {
+        assert_eq!(440., midi_to_hz(69.));
+        assert!((midi_to_hz(60.) - 261.6256).abs() < 1e-3);
+        assert!((hz_to_midi(880.) - 81.).abs() < 1e-5);
+        assert!((cents_to_ratio(1200.) - 2.).abs() < 1e-6);
+        assert!((ratio_to_cents(1.5) - 701.955).abs() < 1e-3);
+        assert!((transpose(440., -12.) - 220.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn parses_note_names() {
+        assert_eq!(Ok(60), parse_note("C4"));
+        assert_eq!(Ok(61), parse_note("C#4"));
+        assert_eq!(Ok(70), parse_note("Bb4"));
+        assert_eq!(Ok(0), parse_note("C-1"));
+        assert_eq!(Ok(127), parse_note("G9"));
+        assert_eq!(Ok(59), parse_note("Cb4"));
+        assert_eq!(Err(PitchError::InvalidNote("H2".into())), parse_note("H2"));
+        assert!(parse_note("A").is_err());
+        assert!(parse_note("G#9").is_err());
+
+        assert_eq!("C#4", note_name(61));
+        assert_eq!("C-1", note_name(0));
+    }
+
+    #[test]
+    fn builds_scales_and_chords() {
+        let scale = Scale::new(57, ScaleKind::NaturalMinor);
+
+        assert_eq!(Some(57), scale.degree(0));
+        assert_eq!(Some(60), scale.degree(2));
+        assert_eq!(Some(69), scale.degree(7));
+        assert_eq!(Some(55), scale.degree(-1));
+        assert!(scale.contains(72));
+        assert!(!scale.contains(61));
+        assert_eq!(60, scale.quantize(61));
+
+        assert_eq!(vec![60, 64, 67, 70], chord(60, ChordKind::Dominant7));
+        assert_eq!(vec![125, 127], chord(125, ChordKind::Sus2));
+    }
+
+    #[test]
+    fn default_tuning_is_equal_temperament() {
+        let tuning = Tuning::default();
+        for note in [0, 21, 60, 69, 100, 127].iter() {
+            let expected = midi_to_hz(*note as f32);
+            assert!((tuning.frequency(*note).unwrap() / expected - 1.).abs() < 1e-5);
+        }
+    }
+
+    const JUST: &str = "! just.scl
+!
+5 limit just intonation, major
+ 7
+!
+ 9/8
+ 5/4
+ 4/3
+ 3/2
+ 5/3
+ 15/8
+ 2/1
+";
+
+    #[test]
+    fn scala_scale_with_keyboard_mapping() {
+        let kbm = "! the first four degrees on C, D, E and F, repeating every 7 keys
+7
+0
+127
+60
+60
+261.6256
+7
+0
+x
+1
+x
+2
+3
+x
+";
+        let tuning = Tuning::parse_scala(JUST)
+            .unwrap()
+            .with_mapping(KeyboardMapping::parse(kbm).unwrap());
+        assert_eq!("5 limit just intonation, major", tuning.description());
+
+        let c4 = 261.6256;
+        assert!((tuning.frequency(60).unwrap() - c4).abs() < 1e-3);
+        assert_eq!(None, tuning.frequency(61));
+        assert!((tuning.frequency(62).unwrap() - c4 * 9. / 8.).abs() < 1e-3);
+        assert!((tuning.frequency(64).unwrap() - c4 * 5. / 4.).abs() < 1e-3);
+        assert!((tuning.frequency(65).unwrap() - c4 * 4. / 3.).abs() < 1e-3);
+        // The next repeat starts 7 keys up, an octave higher
+        assert!((tuning.frequency(67).unwrap() - c4 * 2.).abs() < 1e-3);
+        assert!((tuning.frequency(53).unwrap() - c4 / 2.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn scala_cents_and_errors() {
+        let tuning = Tuning::parse_scala("quarter tones\n2\n600.0\n1200.0\n").unwrap();
+        let a4 = tuning.frequency(69).unwrap();
+        assert!((a4 - 440.).abs() < 1e-3);
+        assert!((tuning.frequency(70).unwrap() / a4 - 2_f32.sqrt()).abs() < 1e-4);
+
+        assert_eq!(
+            Err(PitchError::Scala {
+                line: 3,
+                message: "`3/x` is not a pitch".into()
+            }),
+            Tuning::parse_scala("bad\n1\n3/x\n")
+        );
+        assert_eq!(
+            Err(PitchError::Scala {
+                line: 3,
+                message: "expected 2 notes".into()
+            }),
+            Tuning::parse_scala("short\n2\n")
+        );
+        assert_eq!(
+            Err(PitchError::Scala {
+                line: 3,
+                message: "expected 99999999999999999 notes".into()
+            }),
+            Tuning::parse_scala("huge\n99999999999999999\n")
+        );
+    }
+
+    #[test]
+    fn keyboard_mapping_errors() {
+        let error = |kbm: &str| match KeyboardMapping::parse(kbm) {
+            Err(PitchError::Scala { line, message }) => (line, message),
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!(
+            (1, "`1e30` is out of range for the map size".into()),
+            error("1e30\n0\n127\n60\n69\n440\n12\n")
+        );
+        assert_eq!(
+            (8, "expected a map entry".into()),
+            error("1e18\n0\n127\n60\n69\n440\n12\n")
+        );
+        assert_eq!(
+            (2, "`200` is out of range for the first note".into()),
+            error("0\n200\n127\n60\n69\n440\n0\n")
+        );
+        assert_eq!(
+            (1, "`2.5` is out of range for the map size".into()),
+            error("2.5\n0\n127\n60\n69\n440\n0\n")
+        );
+        assert_eq!(
+            (
+                6,
+                "`-440` is out of range for the reference frequency".into()
+            ),
+            error("0\n0\n127\n60\n69\n-440\n0\n")
+        );
+    }
+}
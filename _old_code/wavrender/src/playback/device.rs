@@ -0,0 +1,117 @@
+use super::{ring, Consumer, PlaybackError, Producer, Sink};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Plays audio through the default output device.
+/// Samples are handed to the audio thread through a lock-free ring buffer, so `write` blocks the
+/// control thread, never the audio thread.
+pub struct DeviceSink {
+    // Dropping the stream stops playback, so it is kept for the life of the sink
+    _stream: rodio::OutputStream,
+    producer: Producer,
+    underruns: Arc<AtomicUsize>,
+    sample_rate: u32,
+}
+
+impl DeviceSink {
+    /// Opens the default device. `latency` is the length of the ring buffer in seconds.
+    pub fn open(sample_rate: u32, latency: f32) -> Result<Self, PlaybackError> {
+        let (stream, handle) =
+            rodio::OutputStream::try_default().map_err(|e| PlaybackError::Device(e.to_string()))?;
+
+        let capacity = ((sample_rate as f32 * latency) as usize).max(256);
+        let (producer, consumer) = ring(capacity);
+        let underruns = Arc::new(AtomicUsize::new(0));
+        let source = RingSource {
+            consumer,
+            sample_rate,
+            underruns: underruns.clone(),
+        };
+        handle
+            .play_raw(source)
+            .map_err(|e| PlaybackError::Device(e.to_string()))?;
+
+        Ok(Self {
+            _stream: stream,
+            producer,
+            underruns,
+            sample_rate,
+        })
+    }
+
+    /// The number of samples the device needed that weren't ready in time.
+    /// This includes the silence played before the first write and after the last.
+    pub fn underruns(&self) -> usize {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// How long to wait for the device to make room.
+    fn poll_interval(&self) -> Duration {
+        let quarter = self.producer.capacity() as f64 / self.sample_rate as f64 / 4.;
+        Duration::from_secs_f64(quarter.min(0.01))
+    }
+}
+
+impl Sink for DeviceSink {
+    fn write(&mut self, samples: &[f32]) -> Result<(), PlaybackError> {
+        let mut written = 0;
+        while written < samples.len() {
+            written += self.producer.push(&samples[written..]);
+            if written < samples.len() {
+                thread::sleep(self.poll_interval());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), PlaybackError> {
+        while self.producer.free() < self.producer.capacity() {
+            thread::sleep(self.poll_interval());
+        }
+
+        Ok(())
+    }
+}
+
+/// The audio thread's end of the ring buffer. Plays silence when the control thread falls behind.
+struct RingSource {
+    consumer: Consumer,
+    sample_rate: u32,
+    underruns: Arc<AtomicUsize>,
+}
+
+impl Iterator for RingSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        Some(self.consumer.pop().unwrap_or_else(|| {
+            self.underruns.fetch_add(1, Ordering::Relaxed);
+            0.
+        }))
+    }
+}
+
+impl rodio::Source for RingSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
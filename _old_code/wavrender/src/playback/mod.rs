@@ -0,0 +1,215 @@
+#[cfg(feature = "realtime")]
+mod device;
+mod ring;
+#[cfg(feature = "realtime")]
+pub use device::DeviceSink;
+pub use ring::{ring, Consumer, Producer};
+
+use crate::{
+    graph::{Graph, BLOCK_SIZE},
+    output::{OutputFormat, WavOutput},
+};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    path::Path,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum PlaybackError {
+    /// Writing to a file failed.
+    Io(String),
+    /// The output device could not be opened or stopped working.
+    Device(String),
+}
+
+impl fmt::Display for PlaybackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaybackError::Io(message) => write!(f, "{}", message),
+            PlaybackError::Device(message) => write!(f, "audio device: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PlaybackError {}
+
+impl From<hound::Error> for PlaybackError {
+    fn from(e: hound::Error) -> Self {
+        PlaybackError::Io(e.to_string())
+    }
+}
+
+/// Somewhere rendered mono audio is sent as it is produced.
+pub trait Sink {
+    /// Writes samples. Sinks that play in real time block until there is room.
+    fn write(&mut self, samples: &[f32]) -> Result<(), PlaybackError>;
+
+    /// Waits until everything written has been played or stored.
+    fn flush(&mut self) -> Result<(), PlaybackError>;
+}
+
+/// Discards everything, counting the samples written. Useful for tests and benchmarks.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NullSink {
+    frames: usize,
+    peak: f32,
+}
+
+impl NullSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of samples written.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// The largest absolute sample written.
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+}
+
+impl Sink for NullSink {
+    fn write(&mut self, samples: &[f32]) -> Result<(), PlaybackError> {
+        self.frames += samples.len();
+        self.peak = samples.iter().fold(self.peak, |peak, s| peak.max(s.abs()));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), PlaybackError> {
+        Ok(())
+    }
+}
+
+/// Writes to a WAV file as the audio is produced.
+pub struct FileSink<W>
+where
+    W: Write + Seek,
+{
+    output: WavOutput<W>,
+}
+
+impl FileSink<BufWriter<File>> {
+    pub fn create<P>(path: P, format: OutputFormat) -> Result<Self, PlaybackError>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self::new(WavOutput::create(path, format)?))
+    }
+}
+
+impl<W> FileSink<W>
+where
+    W: Write + Seek,
+{
+    pub fn new(output: WavOutput<W>) -> Self {
+        Self { output }
+    }
+
+    pub fn finalize(self) -> Result<(), PlaybackError> {
+        Ok(self.output.finalize()?)
+    }
+}
+
+impl<W> Sink for FileSink<W>
+where
+    W: Write + Seek,
+{
+    fn write(&mut self, samples: &[f32]) -> Result<(), PlaybackError> {
+        Ok(self.output.write_mono(samples)?)
+    }
+
+    fn flush(&mut self) -> Result<(), PlaybackError> {
+        Ok(self.output.flush()?)
+    }
+}
+
+/// Renders `frames` samples of the graph block by block into the sink.
+/// `control` is called before each block with the graph and its current time, so parameters can
+/// be changed while playing.
+pub fn stream<S, C>(
+    graph: &mut Graph,
+    sink: &mut S,
+    frames: usize,
+    mut control: C,
+) -> Result<(), PlaybackError>
+where
+    S: Sink + ?Sized,
+    C: FnMut(&mut Graph, f32),
+{
+    let mut remaining = frames;
+    while remaining > 0 {
+        control(graph, graph.time());
+        let block = graph.process_block();
+        // Without an output the graph is silent, as in `Graph::render`
+        let block = if block.is_empty() {
+            &[0.; BLOCK_SIZE]
+        } else {
+            block
+        };
+        let count = block.len().min(remaining);
+        sink.write(&block[..count])?;
+        remaining -= count;
+    }
+
+    sink.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graph::OscillatorNode, oscillator::Oscillator, output::SampleFormat};
+    use std::io::Cursor;
+
+    fn sine_graph() -> Graph {
+        let mut graph = Graph::new(1000.);
+        let sine = graph.add(OscillatorNode::new(Oscillator::Sine { frequency: 10. }));
+        graph.set_output(sine, 0).unwrap();
+        graph
+    }
+
+    #[test]
+    fn streams_exact_frame_count_with_control() {
+        let mut graph = sine_graph();
+        let mut sink = NullSink::new();
+        let mut calls = vec![];
+
+        stream(&mut graph, &mut sink, BLOCK_SIZE * 2 + 10, |_, time| {
+            calls.push(time)
+        })
+        .unwrap();
+
+        assert_eq!(BLOCK_SIZE * 2 + 10, sink.frames());
+        assert_eq!(3, calls.len());
+        assert_eq!(BLOCK_SIZE as f32 / 1000., calls[1]);
+        assert!((sink.peak() - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn streams_silence_without_an_output() {
+        let mut graph = Graph::new(1000.);
+        let mut sink = NullSink::new();
+        stream(&mut graph, &mut sink, BLOCK_SIZE + 10, |_, _| {}).unwrap();
+
+        assert_eq!(BLOCK_SIZE + 10, sink.frames());
+        assert_eq!(0., sink.peak());
+    }
+
+    #[test]
+    fn file_sink_matches_offline_render() {
+        let format = OutputFormat::new(1, 1000, SampleFormat::Float32);
+        let mut bytes = Cursor::new(vec![]);
+        let mut sink = FileSink::new(WavOutput::new(&mut bytes, format).unwrap());
+        stream(&mut sine_graph(), &mut sink, 200, |_, _| {}).unwrap();
+        sink.finalize().unwrap();
+
+        let mut reader = hound::WavReader::new(Cursor::new(bytes.into_inner())).unwrap();
+        let written: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+
+        assert_eq!(sine_graph().render(200), written);
+    }
+}
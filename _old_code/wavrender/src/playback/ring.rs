@@ -0,0 +1,142 @@
+use std::sync::{
+    atomic::{AtomicU32, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Samples shared between one producer and one consumer.
+/// `read` and `write` count every sample ever popped and pushed, so the buffer is empty when they
+/// are equal and full when they are `capacity` apart.
+struct Shared {
+    slots: Vec<AtomicU32>,
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+/// Creates a lock-free ring buffer holding up to `capacity` samples.
+/// The producer is used on the control thread and the consumer on the audio thread;
+/// neither ever blocks or allocates.
+pub fn ring(capacity: usize) -> (Producer, Consumer) {
+    let shared = Arc::new(Shared {
+        slots: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+    });
+
+    (
+        Producer {
+            shared: shared.clone(),
+        },
+        Consumer { shared },
+    )
+}
+
+pub struct Producer {
+    shared: Arc<Shared>,
+}
+
+impl Producer {
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+
+    /// The number of samples that can be pushed without overwriting unread ones.
+    pub fn free(&self) -> usize {
+        let read = self.shared.read.load(Ordering::Acquire);
+        let write = self.shared.write.load(Ordering::Relaxed);
+        self.capacity() - write.wrapping_sub(read)
+    }
+
+    /// Pushes as many samples as fit, returning how many were pushed.
+    pub fn push(&mut self, samples: &[f32]) -> usize {
+        let count = samples.len().min(self.free());
+        let write = self.shared.write.load(Ordering::Relaxed);
+        for (i, sample) in samples[..count].iter().enumerate() {
+            let slot = write.wrapping_add(i) % self.capacity();
+            self.shared.slots[slot].store(sample.to_bits(), Ordering::Relaxed);
+        }
+
+        self.shared
+            .write
+            .store(write.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+pub struct Consumer {
+    shared: Arc<Shared>,
+}
+
+impl Consumer {
+    /// The number of samples waiting to be popped.
+    pub fn len(&self) -> usize {
+        let write = self.shared.write.load(Ordering::Acquire);
+        let read = self.shared.read.load(Ordering::Relaxed);
+        write.wrapping_sub(read)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn pop(&mut self) -> Option<f32> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let read = self.shared.read.load(Ordering::Relaxed);
+        let slot = read % self.shared.slots.len();
+        let value = f32::from_bits(self.shared.slots[slot].load(Ordering::Relaxed));
+        self.shared
+            .read
+            .store(read.wrapping_add(1), Ordering::Release);
+
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushes_until_full_and_wraps() {
+        let (mut producer, mut consumer) = ring(4);
+
+        assert_eq!(None, consumer.pop());
+        assert_eq!(3, producer.push(&[1., 2., 3.]));
+        assert_eq!(Some(1.), consumer.pop());
+        assert_eq!(2, producer.push(&[4., 5., 6.]));
+        assert_eq!(0, producer.free());
+
+        let popped: Vec<f32> = std::iter::from_fn(|| consumer.pop()).collect();
+        assert_eq!(vec![2., 3., 4., 5.], popped);
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn keeps_order_across_threads() {
+        let (mut producer, mut consumer) = ring(64);
+        let count = 100_000;
+
+        let writer = std::thread::spawn(move || {
+            let samples: Vec<f32> = (0..count).map(|i| i as f32).collect();
+            let mut sent = 0;
+            while sent < count {
+                sent += producer.push(&samples[sent..(sent + 16).min(count)]);
+                std::thread::yield_now();
+            }
+        });
+
+        let mut expected = 0;
+        while expected < count {
+            if let Some(value) = consumer.pop() {
+                assert_eq!(expected as f32, value);
+                expected += 1;
+            } else {
+                std::thread::yield_now();
+            }
+        }
+
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,413 @@
+use crate::graph::{Context, Node, Parameter, Port, PortKind};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+/// The default filter length, in samples at the lower of the two rates.
+const TAPS: usize = 64;
+/// Stopband attenuation the Kaiser window is designed for, in dB.
+const ATTENUATION: f64 = 100.;
+
+/// Converts between two sample rates with a polyphase windowed sinc filter.
+///
+/// The filter passes up to 45% of the lower rate and attenuates from 55%, so the band between
+/// the lower rate's Nyquist frequency and the edge of the passband is where aliases land.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resampler {
+    /// The upsampling and downsampling factors, `to / from` reduced.
+    up: usize,
+    down: usize,
+    /// One set of coefficients per phase, ordered oldest sample first.
+    phases: Vec<Vec<f32>>,
+    /// The last inputs, stored twice so a phase can always read them as one slice.
+    history: Vec<f32>,
+    write: usize,
+    /// The position of the next output at the upsampled rate, relative to the latest input.
+    next: usize,
+}
+
+impl Resampler {
+    pub fn new(from: u32, to: u32) -> Self {
+        Self::with_taps(from, to, TAPS)
+    }
+
+    /// Uses a filter `taps` samples long at the lower rate. Longer filters have a narrower
+    /// transition band, and more latency.
+    pub fn with_taps(from: u32, to: u32, taps: usize) -> Self {
+        let divisor = gcd(from.max(1), to.max(1));
+        let up = (to.max(1) / divisor) as usize;
+        let down = (from.max(1) / divisor) as usize;
+        let phases = design(up, down, taps.max(2));
+        let length = phases[0].len();
+
+        Self {
+            up,
+            down,
+            phases,
+            history: vec![0.; length * 2],
+            write: 0,
+            next: 0,
+        }
+    }
+
+    /// The delay the filter adds, in output samples.
+    pub fn latency(&self) -> f32 {
+        (self.center() as f64 / self.down as f64) as f32
+    }
+
+    /// Resamples the input, appending to the output. Blocks may be any length, and the
+    /// resampler carries its state across them.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let length = self.phases[0].len();
+        for sample in input.iter() {
+            self.write = (self.write + 1) % length;
+            self.history[self.write] = *sample;
+            self.history[self.write + length] = *sample;
+            let window = &self.history[self.write + 1..=self.write + length];
+
+            while self.next < self.up {
+                let phase = &self.phases[self.next];
+                output.push(phase.iter().zip(window.iter()).map(|(c, s)| c * s).sum());
+                self.next += self.down;
+            }
+            self.next -= self.up;
+        }
+    }
+
+    /// Clears the history.
+    pub fn reset(&mut self) {
+        self.history.iter_mut().for_each(|s| *s = 0.);
+        self.next = 0;
+    }
+
+    /// The middle of the filter, at the upsampled rate.
+    fn center(&self) -> usize {
+        self.phases[0].len() * self.up / 2
+    }
+}
+
+/// Resamples a whole signal without delaying it. The output is `from / to` times as long.
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to {
+        return samples.to_vec();
+    }
+
+    let mut resampler = Resampler::new(from, to);
+    let (up, down) = (resampler.up, resampler.down);
+    let length = (samples.len() * up).div_ceil(down);
+
+    // Starting half a filter late lines each output up with its input instead of the delay
+    resampler.next = resampler.center();
+    let mut output = Vec::with_capacity(length + up);
+    resampler.process(samples, &mut output);
+    resampler.process(&vec![0.; resampler.phases[0].len()], &mut output);
+    output.truncate(length);
+
+    output
+}
+
+/// Designs a Kaiser windowed sinc low pass at `up` times the input rate and splits it into
+/// `up` phases.
+fn design(up: usize, down: usize, taps: usize) -> Vec<Vec<f32>> {
+    let factor = up.max(down);
+    let phase_length = (taps * factor).div_ceil(up);
+    let length = phase_length * up;
+    let center = length as f64 / 2.;
+    let cutoff = 0.5 / factor as f64;
+    let beta = 0.1102 * (ATTENUATION - 8.7);
+
+    let coefficient = |i: usize| {
+        let x = i as f64 - center;
+        let window = bessel_i0(beta * (1. - (x / center).powi(2)).max(0.).sqrt()) / bessel_i0(beta);
+        // Zero stuffing divides the level by `up`, so the filter makes it back
+        up as f64 * 2. * cutoff * sinc(2. * cutoff * x) * window
+    };
+
+    (0..up)
+        .map(|phase| {
+            (0..phase_length)
+                .rev()
+                .map(|k| coefficient(phase + k * up) as f32)
+                .collect()
+        })
+        .collect()
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// The zeroth order modified Bessel function of the first kind, from its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.;
+    let mut term = 1.;
+    for k in 1..50 {
+        term *= (x / (2. * k as f64)).powi(2);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+
+    sum
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Runs a node at a multiple of the graph's sample rate, so nonlinear processing such as
+/// distortion or FM feedback doesn't alias. Audio inputs are upsampled and audio outputs
+/// filtered back down, while control inputs are held and control outputs decimated.
+///
+/// The node should be created for the higher rate, and the filters delay audio by
+/// `latency()` samples.
+pub struct Oversampled<N> {
+    node: N,
+    factor: usize,
+    up: Vec<Option<Resampler>>,
+    down: Vec<Option<Resampler>>,
+    inputs: Vec<Vec<f32>>,
+    outputs: Vec<Vec<f32>>,
+}
+
+impl<N> Oversampled<N>
+where
+    N: Node,
+{
+    /// Oversamples by a whole factor, usually 2 or 4.
+    pub fn new(node: N, factor: usize) -> Self {
+        let factor = factor.max(1);
+        let resampler = |port: &Port, from, to| match port.kind {
+            PortKind::Audio => Some(Resampler::new(from, to)),
+            PortKind::Control => None,
+        };
+        let up = node
+            .inputs()
+            .iter()
+            .map(|p| resampler(p, 1, factor as u32))
+            .collect();
+        let down = node
+            .outputs()
+            .iter()
+            .map(|p| resampler(p, factor as u32, 1))
+            .collect();
+
+        Self {
+            factor,
+            up,
+            down,
+            inputs: vec![vec![]; node.inputs().len()],
+            outputs: vec![vec![]; node.outputs().len()],
+            node,
+        }
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// The delay the filters add, in samples at the graph's rate.
+    pub fn latency(&self) -> f32 {
+        let up = Resampler::new(1, self.factor as u32).latency() / self.factor as f32;
+        up + Resampler::new(self.factor as u32, 1).latency()
+    }
+
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+}
+
+impl<N> Node for Oversampled<N>
+where
+    N: Node,
+{
+    fn inputs(&self) -> &[Port] {
+        self.node.inputs()
+    }
+
+    fn outputs(&self) -> &[Port] {
+        self.node.outputs()
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        self.node.parameters()
+    }
+
+    fn parameter(&self, index: usize) -> f32 {
+        self.node.parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) {
+        self.node.set_parameter(index, value);
+    }
+
+    fn process(&mut self, context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        let factor = self.factor;
+        let inner = Context {
+            sample_rate: context.sample_rate * factor as f32,
+            time: context.time,
+            frames: context.frames * factor,
+        };
+
+        for ((buffer, up), input) in self.inputs.iter_mut().zip(self.up.iter_mut()).zip(inputs) {
+            buffer.clear();
+            match up {
+                Some(resampler) => resampler.process(input, buffer),
+                None => {
+                    for sample in input.iter() {
+                        buffer.resize(buffer.len() + factor, *sample);
+                    }
+                }
+            }
+        }
+        for buffer in self.outputs.iter_mut() {
+            buffer.resize(inner.frames, 0.);
+        }
+
+        let inputs: Vec<&[f32]> = self.inputs.iter().map(|b| b.as_slice()).collect();
+        self.node.process(&inner, &inputs, &mut self.outputs);
+
+        for ((output, down), buffer) in outputs
+            .iter_mut()
+            .zip(self.down.iter_mut())
+            .zip(&self.outputs)
+        {
+            match down {
+                Some(resampler) => {
+                    output.clear();
+                    resampler.process(buffer, output);
+                }
+                None => {
+                    for (out, chunk) in output.iter_mut().zip(buffer.chunks(factor)) {
+                        *out = chunk[chunk.len() - 1];
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::{gain_to_db, peak, Spectrum, Window},
+        effects::{Distortion, Shape},
+        graph::{EffectNode, Gain, Graph, OscillatorNode},
+        oscillator::Oscillator,
+    };
+
+    /// The gain of the resampler's filter at a frequency, as a fraction of the lower rate.
+    fn response(resampler: &Resampler, frequency: f64) -> f64 {
+        let factor = resampler.up.max(resampler.down) as f64;
+        let omega = 2. * PI * frequency / factor;
+        let (mut re, mut im) = (0., 0.);
+        for (phase, coefficients) in resampler.phases.iter().enumerate() {
+            for (k, c) in coefficients.iter().rev().enumerate() {
+                let i = (phase + k * resampler.up) as f64;
+                re += *c as f64 * (omega * i).cos();
+                im -= *c as f64 * (omega * i).sin();
+            }
+        }
+
+        (re * re + im * im).sqrt() / resampler.up as f64
+    }
+
+    #[test]
+    fn passband_is_flat_and_stopband_attenuated() {
+        for (from, to) in [(44100, 48000), (48000, 44100), (1, 4), (4, 1)].iter() {
+            let resampler = Resampler::new(*from, *to);
+            let mut ripple: f64 = 0.;
+            let mut stopband: f64 = 0.;
+            for i in 0..=200 {
+                let passband = response(&resampler, 0.45 * i as f64 / 200.);
+                ripple = ripple.max(gain_to_db(passband as f32).abs() as f64);
+                let rejected = response(&resampler, 0.55 + 1.45 * i as f64 / 200.);
+                stopband = stopband.max(rejected);
+            }
+
+            assert!(ripple < 0.001, "{} -> {}: {} dB ripple", from, to, ripple);
+            let attenuation = -gain_to_db(stopband as f32);
+            assert!(attenuation > 90., "{} -> {}: {} dB", from, to, attenuation);
+        }
+    }
+
+    #[test]
+    fn resamples_without_delay() {
+        let sine = |frequency: f64, rate: f64, length: usize| -> Vec<f32> {
+            (0..length)
+                .map(|i| (2. * PI * frequency * i as f64 / rate).sin() as f32)
+                .collect()
+        };
+
+        let output = resample(&sine(1000., 48000., 4800), 48000, 44100);
+        assert_eq!(4410, output.len());
+        let expected = sine(1000., 44100., 4410);
+        for (out, expected) in output[200..4200].iter().zip(expected[200..4200].iter()) {
+            assert!((out - expected).abs() < 1e-3);
+        }
+
+        // 25 kHz would alias to 19.1 kHz
+        let output = resample(&sine(25000., 96000., 9600), 96000, 44100);
+        assert!(peak(&output[200..4200]) < 1e-4);
+
+        assert_eq!(8820, resample(&vec![0.; 4410], 44100, 88200).len());
+    }
+
+    #[test]
+    fn oversampling_reduces_aliasing() {
+        const SAMPLE_RATE: f32 = 44100.;
+        let render = |factor: usize| {
+            let mut graph = Graph::new(SAMPLE_RATE);
+            let sine = graph.add(OscillatorNode::new(Oscillator::Sine { frequency: 5000. }));
+            let clip = EffectNode::new(Distortion::new(Shape::HardClip, 4.));
+            let clip = if factor == 1 {
+                graph.add(clip)
+            } else {
+                graph.add(Oversampled::new(clip, factor))
+            };
+            graph.connect(sine, 0, clip, 0).unwrap();
+            graph.set_output(clip, 0).unwrap();
+            let samples = graph.render(16384 + 1024);
+            Spectrum::new(&samples[1024..], SAMPLE_RATE, Window::BlackmanHarris)
+        };
+
+        // The 7th harmonic at 35 kHz folds back to 9.1 kHz
+        let plain = render(1);
+        let oversampled = render(4);
+        assert!(plain.magnitude_at(9100.) > 0.05);
+        assert!(oversampled.magnitude_at(9100.) < plain.magnitude_at(9100.) / 100.);
+        let fundamental = oversampled.magnitude_at(5000.);
+        assert!((fundamental / plain.magnitude_at(5000.) - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn oversampling_a_linear_node_only_delays_it() {
+        let mut graph = Graph::new(44100.);
+        let sine = graph.add(OscillatorNode::new(Oscillator::Sine { frequency: 1000. }));
+        let gain = Oversampled::new(Gain::new(0.5), 2);
+        let latency = gain.latency();
+        let gain = graph.add(gain);
+        graph.connect(sine, 0, gain, 0).unwrap();
+        graph.set_output(gain, 0).unwrap();
+        let samples = graph.render(4096);
+
+        assert_eq!(64., latency);
+        let delayed = latency as usize;
+        for (i, sample) in samples.iter().enumerate().skip(256) {
+            let t = (i - delayed) as f32 / 44100.;
+            let expected = 0.5 * (2. * core::f32::consts::PI * 1000. * t).sin();
+            assert!((sample - expected).abs() < 1e-3, "{}: {}", i, sample);
+        }
+    }
+}
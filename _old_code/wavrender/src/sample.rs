@@ -0,0 +1,238 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::resample::resample;
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+#[cfg(feature = "std")]
+use std::{io::Read, path::Path};
+
+/// How values between frames are calculated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Blackman windowed sinc using `taps` frames either side of the position.
+    Sinc {
+        taps: usize,
+    },
+}
+
+/// Audio loaded into memory, stored per channel with values in -1 .. 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    channels: Vec<Vec<f32>>,
+    sample_rate: u32,
+}
+
+impl Sample {
+    /// Creates a sample. Every channel should have the same length.
+    pub fn new(channels: Vec<Vec<f32>>, sample_rate: u32) -> Self {
+        Self {
+            channels,
+            sample_rate,
+        }
+    }
+
+    pub fn from_mono(samples: Vec<f32>, sample_rate: u32) -> Self {
+        Self::new(vec![samples], sample_rate)
+    }
+
+    /// Loads a WAV file.
+    #[cfg(feature = "std")]
+    pub fn load<P>(path: P) -> Result<Self, hound::Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_reader(hound::WavReader::open(path)?)
+    }
+
+    /// Reads WAV data.
+    #[cfg(feature = "std")]
+    pub fn read<R>(reader: R) -> Result<Self, hound::Error>
+    where
+        R: Read,
+    {
+        Self::from_reader(hound::WavReader::new(reader)?)
+    }
+
+    #[cfg(feature = "std")]
+    fn from_reader<R>(mut reader: hound::WavReader<R>) -> Result<Self, hound::Error>
+    where
+        R: Read,
+    {
+        let spec = reader.spec();
+        let interleaved: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 / scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        let count = spec.channels.max(1) as usize;
+        let mut channels = vec![Vec::with_capacity(interleaved.len() / count); count];
+        for frame in interleaved.chunks(count) {
+            for (channel, sample) in channels.iter_mut().zip(frame.iter()) {
+                channel.push(*sample);
+            }
+        }
+
+        Ok(Self::new(channels, spec.sample_rate))
+    }
+
+    /// Converts every channel to another sample rate.
+    pub fn resample(&self, sample_rate: u32) -> Self {
+        let channels = self
+            .channels
+            .iter()
+            .map(|c| resample(c, self.sample_rate, sample_rate))
+            .collect();
+
+        Self::new(channels, sample_rate)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn channel(&self, index: usize) -> &[f32] {
+        &self.channels[index]
+    }
+
+    /// The number of frames.
+    pub fn len(&self) -> usize {
+        self.channels.first().map(|c| c.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The length in seconds.
+    pub fn duration(&self) -> f32 {
+        self.len() as f32 / self.sample_rate as f32
+    }
+
+    /// Returns every channel mixed together at a fractional frame position.
+    /// `step` is the number of frames advanced per output sample and is used to avoid aliasing.
+    pub fn mono_at(&self, position: f64, step: f64, interpolation: Interpolation) -> f32 {
+        let sum: f32 = self
+            .channels
+            .iter()
+            .map(|c| interpolate(c, position, step, interpolation))
+            .sum();
+
+        sum / self.channels.len().max(1) as f32
+    }
+}
+
+/// Returns the value at a fractional position. Positions outside the data are silent.
+/// `step` is the number of frames advanced per output sample; above 1 the sinc filter is narrowed to avoid aliasing.
+pub fn interpolate(data: &[f32], position: f64, step: f64, interpolation: Interpolation) -> f32 {
+    let at = |index: i64| {
+        if index < 0 {
+            0.
+        } else {
+            data.get(index as usize).copied().unwrap_or(0.)
+        }
+    };
+
+    let index = position.floor() as i64;
+    let fraction = position - position.floor();
+
+    match interpolation {
+        Interpolation::Linear => {
+            let a = at(index);
+            let b = at(index + 1);
+            a + (b - a) * fraction as f32
+        }
+        Interpolation::Sinc { taps } => {
+            let taps = taps.max(1) as i64;
+            let cutoff = (1. / step.max(1.)).min(1.);
+            let mut sum = 0.;
+            for k in (1 - taps)..=taps {
+                let x = k as f64 - fraction;
+                let window = blackman(x / taps as f64);
+                sum += at(index + k) as f64 * cutoff * sinc(cutoff * x) * window;
+            }
+
+            sum as f32
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// A Blackman window centered on 0, spanning -1 .. 1.
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1. {
+        return 0.;
+    }
+
+    let n = (x + 1.) / 2.;
+    0.42 - 0.5 * (2. * PI * n).cos() + 0.08 * (4. * PI * n).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use std::io::Cursor;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reads_interleaved_wav() {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut bytes = Cursor::new(vec![]);
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for sample in [16384_i16, -16384, 0, 8192].iter() {
+            writer.write_sample(*sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let sample = Sample::read(Cursor::new(bytes.into_inner())).unwrap();
+
+        assert_eq!(22050, sample.sample_rate());
+        assert_eq!(2, sample.channel_count());
+        assert_eq!(2, sample.len());
+        assert_eq!(&[0.5, 0.], sample.channel(0));
+        assert_eq!(&[-0.5, 0.25], sample.channel(1));
+        assert_eq!(0., sample.mono_at(0., 1., Interpolation::Linear));
+    }
+
+    #[test]
+    fn interpolation_between_frames() {
+        let sine: Vec<f32> = (0..200)
+            .map(|i| (i as f32 * 2. * core::f32::consts::PI / 20.).sin())
+            .collect();
+
+        for interpolation in [Interpolation::Linear, Interpolation::Sinc { taps: 16 }].iter() {
+            assert!((interpolate(&sine, 100., 1., *interpolation) - sine[100]).abs() < 1e-4);
+
+            let expected = (100.5 * 2. * core::f32::consts::PI / 20.).sin();
+            let error = (interpolate(&sine, 100.5, 1., *interpolation) - expected).abs();
+            let tolerance = match interpolation {
+                Interpolation::Linear => 0.02,
+                Interpolation::Sinc { .. } => 0.002,
+            };
+            assert!(error < tolerance, "{:?} {}", interpolation, error);
+        }
+    }
+}
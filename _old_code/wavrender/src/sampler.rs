@@ -0,0 +1,315 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{
+    envelope::Envelope,
+    pitch::semitones_to_ratio,
+    sample::{Interpolation, Sample},
+};
+use alloc::{sync::Arc, vec, vec::Vec};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LoopMode {
+    /// Plays to the end of the sample, ignoring note off.
+    OneShot,
+    /// Repeats the frames from `start` up to `end` until the note is released and faded out.
+    Loop { start: usize, end: usize },
+}
+
+/// A sample played for notes within a velocity range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub sample: Arc<Sample>,
+    /// The MIDI note the sample plays at its original pitch.
+    pub root_note: u8,
+    /// The lowest velocity this layer plays for, 0 .. 1.
+    pub min_velocity: f32,
+    /// The highest velocity this layer plays for, 0 .. 1.
+    pub max_velocity: f32,
+    pub loop_mode: LoopMode,
+}
+
+impl Layer {
+    pub fn new(sample: Arc<Sample>, root_note: u8) -> Self {
+        Self {
+            sample,
+            root_note,
+            min_velocity: 0.,
+            max_velocity: 1.,
+            loop_mode: LoopMode::OneShot,
+        }
+    }
+
+    pub fn with_velocity(mut self, min_velocity: f32, max_velocity: f32) -> Self {
+        self.min_velocity = min_velocity;
+        self.max_velocity = max_velocity;
+        self
+    }
+
+    pub fn with_loop(mut self, loop_mode: LoopMode) -> Self {
+        self.loop_mode = loop_mode;
+        self
+    }
+}
+
+struct Voice {
+    note: u8,
+    layer: usize,
+    position: f64,
+    step: f64,
+    velocity: f32,
+    envelope: Envelope,
+    released: bool,
+    finished: bool,
+}
+
+/// Plays layered samples, pitch shifted by resampling, with any number of overlapping notes.
+pub struct Sampler {
+    layers: Vec<Layer>,
+    voices: Vec<Voice>,
+    max_voices: usize,
+    interpolation: Interpolation,
+    sample_rate: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl Sampler {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            layers: vec![],
+            voices: vec![],
+            max_voices: 32,
+            interpolation: Interpolation::Linear,
+            sample_rate,
+            attack: 0.002,
+            release: 0.05,
+        }
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Sets the number of notes that may play at once. The oldest note is stopped when exceeded.
+    pub fn with_max_voices(mut self, max_voices: usize) -> Self {
+        self.max_voices = max_voices.max(1);
+        self
+    }
+
+    /// Sets the fade in and fade out times, in seconds.
+    pub fn with_envelope(mut self, attack: f32, release: f32) -> Self {
+        self.attack = attack;
+        self.release = release;
+        self
+    }
+
+    pub fn add_layer(&mut self, layer: Layer) {
+        self.layers.push(layer);
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Starts a note. Velocity is 0 .. 1 and picks the layer.
+    pub fn note_on(&mut self, note: u8, velocity: f32) {
+        let velocity = if velocity.is_nan() {
+            0.
+        } else {
+            velocity.clamp(0., 1.)
+        };
+        let layer = match self.layer_for(velocity) {
+            Some(layer) => layer,
+            None => return,
+        };
+
+        if self.voices.len() >= self.max_voices {
+            self.voices.remove(0);
+        }
+
+        let sample = &self.layers[layer].sample;
+        let semitones = note as f64 - self.layers[layer].root_note as f64;
+        let step = semitones_to_ratio(semitones as f32) as f64 * sample.sample_rate() as f64
+            / self.sample_rate as f64;
+
+        let mut envelope = Envelope::new(self.attack, 0., 1., self.release, self.sample_rate);
+        envelope.on();
+
+        self.voices.push(Voice {
+            note,
+            layer,
+            position: 0.,
+            step,
+            velocity,
+            envelope,
+            released: false,
+            finished: false,
+        });
+    }
+
+    /// Releases every voice playing the note.
+    pub fn note_off(&mut self, note: u8) {
+        for voice in self
+            .voices
+            .iter_mut()
+            .filter(|v| v.note == note && !v.released)
+        {
+            voice.released = true;
+            if self.layers[voice.layer].loop_mode != LoopMode::OneShot {
+                voice.envelope.off();
+            }
+        }
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        let mut output = 0.;
+        let layers = &self.layers;
+        let interpolation = self.interpolation;
+
+        for voice in self.voices.iter_mut() {
+            let layer = &layers[voice.layer];
+            let value = layer
+                .sample
+                .mono_at(voice.position, voice.step, interpolation);
+            output += value * voice.envelope.tick() * voice.velocity;
+
+            voice.position += voice.step;
+            match layer.loop_mode {
+                LoopMode::OneShot => {
+                    if voice.position >= layer.sample.len() as f64 {
+                        voice.finished = true;
+                    }
+                }
+                LoopMode::Loop { start, end } => {
+                    let end = end.min(layer.sample.len()) as f64;
+                    let length = end - start as f64;
+                    if length > 0. && voice.position >= end {
+                        voice.position -= length * ((voice.position - end) / length).ceil().max(1.);
+                    }
+                }
+            }
+        }
+
+        self.voices
+            .retain(|voice| !voice.finished && voice.envelope.is_active());
+
+        output
+    }
+
+    /// Picks the layer covering the velocity, or the closest one.
+    fn layer_for(&self, velocity: f32) -> Option<usize> {
+        let distance = |layer: &Layer| {
+            if velocity < layer.min_velocity {
+                layer.min_velocity - velocity
+            } else if velocity > layer.max_velocity {
+                velocity - layer.max_velocity
+            } else {
+                0.
+            }
+        };
+
+        self.layers
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .map(|(index, _)| index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sample whose value is its frame index, so positions can be read back.
+    fn ramp(frames: usize) -> Arc<Sample> {
+        Arc::new(Sample::from_mono(
+            (0..frames).map(|i| i as f32).collect(),
+            100,
+        ))
+    }
+
+    fn sampler() -> Sampler {
+        Sampler::new(100.).with_envelope(0., 0.)
+    }
+
+    #[test]
+    fn octave_up_plays_twice_as_fast() {
+        let mut sampler = sampler();
+        sampler.add_layer(Layer::new(ramp(100), 60));
+        sampler.note_on(72, 1.);
+
+        let samples: Vec<f32> = (0..4).map(|_| sampler.tick()).collect();
+
+        assert_eq!(vec![0., 2., 4., 6.], samples);
+    }
+
+    #[test]
+    fn one_shot_ignores_note_off_and_frees_voice_at_end() {
+        let mut sampler = sampler();
+        sampler.add_layer(Layer::new(ramp(4), 60));
+        sampler.note_on(60, 1.);
+        sampler.note_off(60);
+
+        let samples: Vec<f32> = (0..6).map(|_| sampler.tick()).collect();
+
+        assert_eq!(vec![0., 1., 2., 3., 0., 0.], samples);
+        assert_eq!(0, sampler.active_voices());
+    }
+
+    #[test]
+    fn loops_until_released() {
+        let mut sampler = sampler();
+        let layer = Layer::new(ramp(10), 60).with_loop(LoopMode::Loop { start: 2, end: 5 });
+        sampler.add_layer(layer);
+        sampler.note_on(60, 1.);
+
+        let samples: Vec<f32> = (0..9).map(|_| sampler.tick()).collect();
+        assert_eq!(vec![0., 1., 2., 3., 4., 2., 3., 4., 2.], samples);
+
+        sampler.note_off(60);
+        sampler.tick();
+        assert_eq!(0, sampler.active_voices());
+    }
+
+    #[test]
+    fn velocity_picks_layer() {
+        let mut sampler = sampler();
+        let quiet = Arc::new(Sample::from_mono(vec![1.; 10], 100));
+        let loud = Arc::new(Sample::from_mono(vec![-1.; 10], 100));
+        sampler.add_layer(Layer::new(quiet, 60).with_velocity(0., 0.5));
+        sampler.add_layer(Layer::new(loud, 60).with_velocity(0.5, 1.));
+
+        sampler.note_on(60, 0.25);
+        assert_eq!(0.25, sampler.tick());
+
+        let mut sampler = Sampler::new(100.).with_envelope(0., 0.);
+        sampler.add_layer(Layer::new(ramp(1), 60).with_velocity(0.9, 1.));
+        sampler.note_on(60, 0.1);
+        assert_eq!(1, sampler.active_voices());
+
+        // Treated as silent rather than panicking
+        sampler.note_on(60, f32::NAN);
+        assert_eq!(2, sampler.active_voices());
+        assert!(sampler.tick().is_finite());
+    }
+
+    #[test]
+    fn overlapping_notes_are_summed() {
+        let mut sampler = sampler().with_max_voices(2);
+        let layer = Layer::new(ramp(100), 60).with_loop(LoopMode::Loop { start: 0, end: 100 });
+        sampler.add_layer(layer);
+        sampler.note_on(60, 1.);
+        sampler.tick();
+        sampler.note_on(60, 1.);
+
+        assert_eq!(1., sampler.tick());
+        assert_eq!(2, sampler.active_voices());
+
+        sampler.note_on(72, 1.);
+        assert_eq!(2, sampler.active_voices());
+        sampler.note_off(60);
+        sampler.tick();
+        assert_eq!(1, sampler.active_voices());
+    }
+}
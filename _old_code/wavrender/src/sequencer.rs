@@ -0,0 +1,442 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+#[cfg(feature = "std")]
+use crate::output::{OutputFormat, WavOutput};
+use crate::voice::Voice;
+use alloc::{boxed::Box, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+/// A note played on a step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Step {
+    /// The MIDI note number.
+    pub note: u8,
+    /// 0 .. 1.
+    pub velocity: f32,
+    /// How long the note is held, in steps. Above 1 the note is tied into the following steps.
+    pub gate: f32,
+}
+
+impl Step {
+    pub fn new(note: u8) -> Self {
+        Self {
+            note,
+            velocity: 1.,
+            gate: 0.5,
+        }
+    }
+
+    pub fn with_velocity(mut self, velocity: f32) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn with_gate(mut self, gate: f32) -> Self {
+        self.gate = gate;
+        self
+    }
+}
+
+/// A loop of steps for every track.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    length: usize,
+    /// Steps for each track, indexed by track then step.
+    tracks: Vec<Vec<Option<Step>>>,
+}
+
+impl Pattern {
+    /// Creates an empty pattern `length` steps long.
+    pub fn new(length: usize) -> Self {
+        Self {
+            length,
+            tracks: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Sets the note a track plays on a step. Steps past the end of the pattern are ignored.
+    pub fn set(&mut self, track: usize, step: usize, value: Step) {
+        if step >= self.length {
+            return;
+        }
+
+        if self.tracks.len() <= track {
+            self.tracks.resize(track + 1, vec![]);
+        }
+        let steps = &mut self.tracks[track];
+        steps.resize(self.length, None);
+        steps[step] = Some(value);
+    }
+
+    /// Adds the same note every `interval` steps, starting at `offset`.
+    pub fn fill(&mut self, track: usize, offset: usize, interval: usize, value: Step) {
+        for step in (offset..self.length).step_by(interval.max(1)) {
+            self.set(track, step, value);
+        }
+    }
+
+    pub fn clear(&mut self, track: usize, step: usize) {
+        if let Some(steps) = self.tracks.get_mut(track) {
+            if let Some(value) = steps.get_mut(step) {
+                *value = None;
+            }
+        }
+    }
+
+    pub fn step(&self, track: usize, step: usize) -> Option<Step> {
+        self.tracks.get(track)?.get(step).copied().flatten()
+    }
+}
+
+struct Track {
+    voice: Box<dyn Voice>,
+    volume: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Action {
+    // Offs sort before ons so a note can be retriggered on the frame it ends
+    Off(u8),
+    On(u8, f32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Event {
+    frame: usize,
+    track: usize,
+    action: Action,
+}
+
+/// Patterns played in order, each track driving its own instrument.
+pub struct Song {
+    bpm: f32,
+    steps_per_beat: u32,
+    swing: f32,
+    tail: f32,
+    sample_rate: f32,
+    tracks: Vec<Track>,
+    patterns: Vec<Pattern>,
+    order: Vec<usize>,
+}
+
+impl Song {
+    pub fn new(bpm: f32, sample_rate: f32) -> Self {
+        Self {
+            bpm,
+            steps_per_beat: 4,
+            swing: 0.,
+            tail: 1.,
+            sample_rate,
+            tracks: vec![],
+            patterns: vec![],
+            order: vec![],
+        }
+    }
+
+    /// Sets how many steps make up a beat. The default of 4 makes each step a 16th note.
+    pub fn with_steps_per_beat(mut self, steps_per_beat: u32) -> Self {
+        self.steps_per_beat = steps_per_beat.max(1);
+        self
+    }
+
+    /// Delays every second step by a fraction of a step, 0 .. 1.
+    /// 1/3 gives a triplet shuffle.
+    pub fn with_swing(mut self, swing: f32) -> Self {
+        self.swing = swing.clamp(0., 1.);
+        self
+    }
+
+    /// Sets how many seconds are rendered after the last step to let notes ring out.
+    pub fn with_tail(mut self, tail: f32) -> Self {
+        self.tail = if tail.is_finite() { tail.max(0.) } else { 0. };
+        self
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Adds a track played by the instrument, returning its index in patterns.
+    pub fn add_track<V>(&mut self, voice: V, volume: f32) -> usize
+    where
+        V: Voice + 'static,
+    {
+        self.add_boxed_track(Box::new(voice), volume)
+    }
+
+    pub fn add_boxed_track(&mut self, voice: Box<dyn Voice>, volume: f32) -> usize {
+        self.tracks.push(Track { voice, volume });
+        self.tracks.len() - 1
+    }
+
+    /// Adds a pattern, returning its index for the song order.
+    pub fn add_pattern(&mut self, pattern: Pattern) -> usize {
+        self.patterns.push(pattern);
+        self.patterns.len() - 1
+    }
+
+    pub fn pattern_mut(&mut self, index: usize) -> &mut Pattern {
+        &mut self.patterns[index]
+    }
+
+    /// Sets the order patterns are played in. Patterns may be repeated.
+    pub fn set_order(&mut self, order: Vec<usize>) {
+        self.order = order;
+    }
+
+    /// The length of one step in seconds.
+    pub fn step_length(&self) -> f32 {
+        60. / (self.bpm * self.steps_per_beat as f32)
+    }
+
+    /// The number of steps in the song order.
+    pub fn steps(&self) -> usize {
+        self.order
+            .iter()
+            .filter_map(|index| self.patterns.get(*index))
+            .map(|pattern| pattern.len())
+            .sum()
+    }
+
+    /// The time in seconds a step starts at, counting from the start of the song, including swing.
+    pub fn step_time(&self, step: usize) -> f32 {
+        let swing = if step % 2 == 1 { self.swing } else { 0. };
+        (step as f32 + swing) * self.step_length()
+    }
+
+    /// The length of the render in seconds: the song order and the tail.
+    pub fn duration(&self) -> f32 {
+        self.steps() as f32 * self.step_length() + self.tail
+    }
+
+    /// Renders the song order and the tail.
+    pub fn render(&mut self) -> Vec<f32> {
+        self.render_with_progress(|_| {})
+    }
+
+    /// Renders the song, reporting the fraction done, 0 .. 1, about every tenth of a second of
+    /// audio.
+    pub fn render_with_progress<F>(&mut self, mut progress: F) -> Vec<f32>
+    where
+        F: FnMut(f32),
+    {
+        let events = self.events();
+        let frames = (self.duration() * self.sample_rate).round() as usize;
+
+        let interval = (self.sample_rate as usize / 10).max(1);
+        let mut output = Vec::with_capacity(frames);
+        let mut next = 0;
+        for frame in 0..frames {
+            while let Some(event) = events.get(next).filter(|e| e.frame <= frame) {
+                let voice = &mut self.tracks[event.track].voice;
+                match event.action {
+                    Action::On(note, velocity) => voice.note_on(note, velocity),
+                    Action::Off(note) => voice.note_off(note),
+                }
+                next += 1;
+            }
+
+            let sample = self
+                .tracks
+                .iter_mut()
+                .map(|track| track.voice.tick() * track.volume)
+                .sum();
+            output.push(sample);
+
+            if frame % interval == 0 {
+                progress(frame as f32 / frames as f32);
+            }
+        }
+        progress(1.);
+
+        output
+    }
+
+    /// Renders the song to a WAV file. The sample rate of the format is replaced with the song's.
+    #[cfg(feature = "std")]
+    pub fn export<P>(&mut self, path: P, format: OutputFormat) -> Result<(), hound::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let format = OutputFormat {
+            sample_rate: self.sample_rate as u32,
+            ..format
+        };
+        let samples = self.render();
+
+        let mut output = WavOutput::create(path, format)?;
+        output.write_mono(&samples)?;
+        output.finalize()
+    }
+
+    /// Every note on and off in the song, in order.
+    fn events(&self) -> Vec<Event> {
+        let frame = |seconds: f32| (seconds * self.sample_rate).round() as usize;
+        let mut events = vec![];
+        let mut start = 0;
+
+        for pattern in self.order.iter().filter_map(|i| self.patterns.get(*i)) {
+            for (track, steps) in pattern.tracks.iter().enumerate().take(self.tracks.len()) {
+                for (index, step) in steps.iter().enumerate() {
+                    let step = match step {
+                        Some(step) => step,
+                        None => continue,
+                    };
+
+                    let on = self.step_time(start + index);
+                    let off = on + step.gate * self.step_length();
+                    events.push(Event {
+                        frame: frame(on),
+                        track,
+                        action: Action::On(step.note, step.velocity),
+                    });
+                    events.push(Event {
+                        frame: frame(off),
+                        track,
+                        action: Action::Off(step.note),
+                    });
+                }
+            }
+
+            start += pattern.len();
+        }
+
+        events.sort_by(|a, b| {
+            let on = |e: &Event| matches!(e.action, Action::On(..));
+            (a.frame, on(a)).cmp(&(b.frame, on(b)))
+        });
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    type Log = Rc<RefCell<Vec<(usize, Action)>>>;
+
+    /// Records the frame of every note on and off, and outputs the velocity of the held note.
+    #[derive(Default)]
+    struct Recorder {
+        frame: usize,
+        held: Option<f32>,
+        log: Log,
+    }
+
+    impl Voice for Recorder {
+        fn note_on(&mut self, note: u8, velocity: f32) {
+            self.held = Some(velocity);
+            self.log
+                .borrow_mut()
+                .push((self.frame, Action::On(note, velocity)));
+        }
+
+        fn note_off(&mut self, note: u8) {
+            self.held = None;
+            self.log.borrow_mut().push((self.frame, Action::Off(note)));
+        }
+
+        fn tick(&mut self) -> f32 {
+            self.frame += 1;
+            self.held.unwrap_or(0.)
+        }
+    }
+
+    /// A song at 1000 samples per second where each step is 125 samples long.
+    fn song() -> (Song, Log) {
+        let mut song = Song::new(120., 1000.).with_tail(0.);
+        let recorder = Recorder::default();
+        let log = recorder.log.clone();
+        song.add_track(recorder, 1.);
+        (song, log)
+    }
+
+    #[test]
+    fn steps_trigger_notes_with_gate() {
+        let (mut song, log) = song();
+        let mut pattern = Pattern::new(4);
+        pattern.set(0, 0, Step::new(60));
+        pattern.set(0, 2, Step::new(62).with_velocity(0.5).with_gate(2.));
+        let pattern = song.add_pattern(pattern);
+        song.set_order(vec![pattern]);
+
+        let samples = song.render();
+
+        assert_eq!(500, samples.len());
+        // The tied note is still held when the song ends
+        assert_eq!(
+            vec![
+                (0, Action::On(60, 1.)),
+                (63, Action::Off(60)),
+                (250, Action::On(62, 0.5)),
+            ],
+            *log.borrow()
+        );
+        assert_eq!(0.5, samples[499]);
+    }
+
+    #[test]
+    fn swing_delays_off_beats() {
+        let (song, _) = song();
+        let song = song.with_swing(0.5);
+
+        assert_eq!(0., song.step_time(0));
+        assert_eq!(0.1875, song.step_time(1));
+        assert_eq!(0.25, song.step_time(2));
+    }
+
+    #[test]
+    fn order_chains_patterns() {
+        let (mut song, log) = song();
+        let mut a = Pattern::new(2);
+        a.set(0, 0, Step::new(60).with_gate(1.));
+        let mut b = Pattern::new(4);
+        b.fill(0, 1, 2, Step::new(72).with_gate(1.));
+        let a = song.add_pattern(a);
+        let b = song.add_pattern(b);
+        song.set_order(vec![a, b, a]);
+
+        assert_eq!(8, song.steps());
+        song.render();
+
+        let ons: Vec<(usize, Action)> = log
+            .borrow()
+            .iter()
+            .copied()
+            .filter(|(_, action)| matches!(action, Action::On(..)))
+            .collect();
+        assert_eq!(
+            vec![
+                (0, Action::On(60, 1.)),
+                (375, Action::On(72, 1.)),
+                (625, Action::On(72, 1.)),
+                (750, Action::On(60, 1.)),
+            ],
+            ons
+        );
+    }
+
+    #[test]
+    fn retriggered_notes_turn_off_before_on() {
+        let (mut song, log) = song();
+        let mut pattern = Pattern::new(2);
+        pattern.fill(0, 0, 1, Step::new(60).with_gate(1.));
+        let pattern = song.add_pattern(pattern);
+        song.set_order(vec![pattern]);
+        song.render();
+
+        assert_eq!((125, Action::Off(60)), log.borrow()[1]);
+        assert_eq!((125, Action::On(60, 1.)), log.borrow()[2]);
+    }
+}
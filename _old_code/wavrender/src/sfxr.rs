@@ -0,0 +1,519 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+#[cfg(feature = "std")]
+use crate::output::{OutputFormat, WavOutput};
+use crate::{
+    envelope::{BreakpointEnvelope, Length, Segment},
+    filter::{Biquad, BiquadKind, Filter},
+    oscillator::{NoiseKind, Oscillator},
+    pitch::transpose,
+    rng::Rng,
+};
+use alloc::{vec, vec::Vec};
+use core::f32::consts::FRAC_1_SQRT_2;
+#[cfg(feature = "std")]
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// The kinds of sound effect that can be generated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Category {
+    Coin,
+    Laser,
+    Explosion,
+    PowerUp,
+    Hit,
+    Jump,
+    Blip,
+}
+
+impl Category {
+    pub const ALL: [Category; 7] = [
+        Category::Coin,
+        Category::Laser,
+        Category::Explosion,
+        Category::PowerUp,
+        Category::Hit,
+        Category::Jump,
+        Category::Blip,
+    ];
+
+    /// A lowercase name, used for file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Coin => "coin",
+            Category::Laser => "laser",
+            Category::Explosion => "explosion",
+            Category::PowerUp => "powerup",
+            Category::Hit => "hit",
+            Category::Jump => "jump",
+            Category::Blip => "blip",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Waveform {
+    /// A pulse wave using the duty parameters.
+    Square,
+    Saw,
+    Sine,
+    Triangle,
+    Noise,
+}
+
+/// Everything needed to render a sound effect. Times are in seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SfxParams {
+    pub waveform: Waveform,
+    /// The starting pulse width of the square wave, 0 .. 1.
+    pub duty: f32,
+    /// Change in pulse width per second.
+    pub duty_sweep: f32,
+
+    pub attack: f32,
+    pub sustain: f32,
+    /// How far above full volume the sustain starts, falling back to 1 over the sustain.
+    pub punch: f32,
+    pub decay: f32,
+
+    /// The starting frequency in Hz.
+    pub frequency: f32,
+    /// The sound stops if a slide takes the frequency below this.
+    pub min_frequency: f32,
+    /// Change in frequency, in octaves per second.
+    pub slide: f32,
+    /// Change in slide, in octaves per second per second.
+    pub delta_slide: f32,
+
+    /// In semitones.
+    pub vibrato_depth: f32,
+    /// In Hz.
+    pub vibrato_speed: f32,
+
+    /// The frequency is multiplied by this once `arpeggio_time` has passed.
+    pub arpeggio: f32,
+    pub arpeggio_time: f32,
+
+    /// Restarts the frequency, slide and arpeggio at this interval. 0 disables repeating.
+    pub repeat: f32,
+
+    /// The starting cutoff of the low pass filter in Hz.
+    pub low_pass: Option<f32>,
+    /// Change in low pass cutoff, in octaves per second.
+    pub low_pass_sweep: f32,
+    pub high_pass: Option<f32>,
+
+    pub volume: f32,
+    /// The seed used for noise.
+    pub seed: u64,
+}
+
+impl Default for SfxParams {
+    /// A short square wave beep.
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            duty: 0.5,
+            duty_sweep: 0.,
+            attack: 0.,
+            sustain: 0.1,
+            punch: 0.,
+            decay: 0.2,
+            frequency: 440.,
+            min_frequency: 0.,
+            slide: 0.,
+            delta_slide: 0.,
+            vibrato_depth: 0.,
+            vibrato_speed: 0.,
+            arpeggio: 1.,
+            arpeggio_time: 0.,
+            repeat: 0.,
+            low_pass: None,
+            low_pass_sweep: 0.,
+            high_pass: None,
+            volume: 0.5,
+            seed: 0,
+        }
+    }
+}
+
+impl SfxParams {
+    /// Creates random parameters typical of the category. The same seed always gives the same sound.
+    pub fn generate(category: Category, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut params = Self {
+            seed,
+            ..Self::default()
+        };
+
+        match category {
+            Category::Coin => {
+                params.frequency = rng.range(500., 1400.);
+                params.sustain = rng.range(0.02, 0.1);
+                params.punch = rng.range(0.3, 0.6);
+                params.decay = rng.range(0.1, 0.4);
+                if rng.next_f32() < 0.5 {
+                    params.arpeggio = rng.range(1.2, 1.6);
+                    params.arpeggio_time = rng.range(0.03, 0.1);
+                }
+            }
+            Category::Laser => {
+                params.waveform =
+                    pick(&mut rng, &[Waveform::Square, Waveform::Saw, Waveform::Sine]);
+                params.frequency = rng.range(600., 2500.);
+                params.min_frequency = rng.range(60., 200.);
+                params.slide = -rng.range(3., 10.);
+                params.duty = rng.range(0.1, 0.5);
+                params.duty_sweep = rng.range(-1., 1.);
+                params.sustain = rng.range(0.05, 0.2);
+                params.decay = rng.range(0.05, 0.3);
+                if rng.next_f32() < 0.5 {
+                    params.punch = rng.range(0., 0.3);
+                }
+                if rng.next_f32() < 0.3 {
+                    params.high_pass = Some(rng.range(100., 600.));
+                }
+            }
+            Category::Explosion => {
+                params.waveform = Waveform::Noise;
+                params.frequency = rng.range(40., 400.);
+                params.slide = rng.range(-1.5, 0.5);
+                params.sustain = rng.range(0.1, 0.4);
+                params.punch = rng.range(0.2, 0.8);
+                params.decay = rng.range(0.3, 0.9);
+                if rng.next_f32() < 0.3 {
+                    params.repeat = rng.range(0.1, 0.3);
+                }
+                if rng.next_f32() < 0.5 {
+                    params.vibrato_depth = rng.range(0., 3.);
+                    params.vibrato_speed = rng.range(5., 20.);
+                }
+                params.low_pass = Some(rng.range(2000., 8000.));
+                params.low_pass_sweep = -rng.range(0., 3.);
+            }
+            Category::PowerUp => {
+                params.waveform = pick(&mut rng, &[Waveform::Square, Waveform::Saw]);
+                params.frequency = rng.range(200., 600.);
+                params.duty = rng.range(0.2, 0.5);
+                if rng.next_f32() < 0.5 {
+                    params.slide = rng.range(1., 3.);
+                    params.repeat = rng.range(0.08, 0.25);
+                } else {
+                    params.slide = rng.range(0.5, 2.);
+                    params.vibrato_depth = rng.range(0.2, 1.);
+                    params.vibrato_speed = rng.range(8., 20.);
+                }
+                params.sustain = rng.range(0.05, 0.4);
+                params.decay = rng.range(0.1, 0.5);
+            }
+            Category::Hit => {
+                params.waveform = pick(
+                    &mut rng,
+                    &[Waveform::Square, Waveform::Saw, Waveform::Noise],
+                );
+                params.frequency = rng.range(100., 800.);
+                params.slide = -rng.range(2., 6.);
+                params.sustain = rng.range(0.01, 0.1);
+                params.decay = rng.range(0.05, 0.25);
+                if rng.next_f32() < 0.5 {
+                    params.high_pass = Some(rng.range(100., 1000.));
+                }
+            }
+            Category::Jump => {
+                params.frequency = rng.range(200., 500.);
+                params.duty = rng.range(0.2, 0.5);
+                params.slide = rng.range(1., 3.);
+                params.sustain = rng.range(0.05, 0.25);
+                params.decay = rng.range(0.05, 0.3);
+                if rng.next_f32() < 0.5 {
+                    params.high_pass = Some(rng.range(100., 500.));
+                }
+                if rng.next_f32() < 0.5 {
+                    params.low_pass = Some(rng.range(2000., 10000.));
+                }
+            }
+            Category::Blip => {
+                params.waveform = pick(&mut rng, &[Waveform::Square, Waveform::Saw]);
+                params.frequency = rng.range(400., 1600.);
+                params.duty = rng.range(0.2, 0.5);
+                params.sustain = rng.range(0.02, 0.08);
+                params.decay = rng.range(0.01, 0.05);
+                params.high_pass = Some(100.);
+            }
+        }
+
+        params
+    }
+
+    /// Returns a variation of these parameters. `amount` is roughly 0 .. 1, where 0 leaves them unchanged.
+    pub fn mutate(&self, seed: u64, amount: f32) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut params = self.clone();
+        params.seed = rng.next_u64();
+
+        // Frequencies change by up to an octave, other values by a fraction of their usual range
+        let mut octaves = |value: &mut f32| *value *= 2_f32.powf(rng.bipolar() * amount);
+        octaves(&mut params.frequency);
+        octaves(&mut params.min_frequency);
+        octaves(&mut params.vibrato_speed);
+        octaves(&mut params.arpeggio);
+        if let Some(cutoff) = params.low_pass.as_mut() {
+            octaves(cutoff);
+        }
+        if let Some(cutoff) = params.high_pass.as_mut() {
+            octaves(cutoff);
+        }
+
+        let mut shift = |value: &mut f32, range: f32, min: f32| {
+            *value = (*value + rng.bipolar() * amount * range).max(min)
+        };
+        shift(&mut params.duty, 0.2, 0.05);
+        params.duty = params.duty.min(0.95);
+        shift(&mut params.duty_sweep, 0.5, f32::MIN);
+        shift(&mut params.attack, 0.05, 0.);
+        shift(&mut params.sustain, 0.1, 0.);
+        shift(&mut params.punch, 0.2, 0.);
+        shift(&mut params.decay, 0.1, 0.);
+        shift(&mut params.slide, 1., f32::MIN);
+        shift(&mut params.delta_slide, 1., f32::MIN);
+        shift(&mut params.vibrato_depth, 0.5, 0.);
+        shift(&mut params.arpeggio_time, 0.05, 0.);
+        shift(&mut params.low_pass_sweep, 1., f32::MIN);
+        if params.repeat > 0. {
+            shift(&mut params.repeat, 0.05, 0.01);
+        }
+
+        params
+    }
+
+    /// The length of the envelope. Sounds that slide below the minimum frequency end sooner.
+    pub fn duration(&self) -> f32 {
+        self.attack + self.sustain + self.decay
+    }
+
+    pub fn render(&self, sample_rate: f32) -> Vec<f32> {
+        let mut envelope = BreakpointEnvelope::new(
+            vec![
+                Segment::new(1., Length::Seconds(self.attack)),
+                Segment::new(1. + self.punch, Length::Seconds(0.)),
+                Segment::new(1., Length::Seconds(self.sustain)),
+                Segment::new(0., Length::Seconds(self.decay)),
+            ],
+            sample_rate,
+        );
+        envelope.on();
+
+        let mut low_pass = self
+            .low_pass
+            .map(|cutoff| Biquad::new(BiquadKind::LowPass, cutoff, FRAC_1_SQRT_2, sample_rate));
+        let mut high_pass = self
+            .high_pass
+            .map(|cutoff| Biquad::new(BiquadKind::HighPass, cutoff, FRAC_1_SQRT_2, sample_rate));
+
+        let dt = 1. / sample_rate;
+        let frames = (self.duration() * sample_rate).round() as usize;
+        let mut output = Vec::with_capacity(frames);
+
+        let mut frequency = self.frequency;
+        let mut slide = self.slide;
+        let mut duty = self.duty;
+        let mut since_repeat = 0.;
+        let mut arpeggiated = false;
+        let mut phase = 0_f64;
+
+        for frame in 0..frames {
+            if self.repeat > 0. && since_repeat >= self.repeat {
+                since_repeat -= self.repeat;
+                frequency = self.frequency;
+                slide = self.slide;
+                arpeggiated = false;
+            }
+
+            if !arpeggiated && self.arpeggio_time > 0. && since_repeat >= self.arpeggio_time {
+                frequency *= self.arpeggio;
+                arpeggiated = true;
+            }
+
+            slide += self.delta_slide * dt;
+            frequency *= 2_f32.powf(slide * dt);
+            if frequency < self.min_frequency {
+                break;
+            }
+
+            let t = frame as f32 * dt;
+            let vibrato = self.vibrato_depth
+                * Oscillator::Sine {
+                    frequency: self.vibrato_speed,
+                }
+                .sample(t);
+            let pitch = transpose(frequency, vibrato);
+            duty = (duty + self.duty_sweep * dt).clamp(0.05, 0.95);
+
+            let oscillator = match self.waveform {
+                Waveform::Square => Oscillator::Pulse {
+                    frequency: pitch,
+                    duty,
+                },
+                Waveform::Saw => Oscillator::Saw { frequency: pitch },
+                Waveform::Sine => Oscillator::Sine { frequency: pitch },
+                Waveform::Triangle => Oscillator::Triangle { frequency: pitch },
+                // A new value 32 times per cycle, so the frequency sets the color
+                Waveform::Noise => Oscillator::Noise {
+                    kind: NoiseKind::White,
+                    frequency: pitch * 32.,
+                    seed: self.seed,
+                },
+            };
+
+            let mut value = oscillator.sample_phase(phase as f32);
+            phase = (phase + (oscillator.frequency() * dt) as f64).rem_euclid(oscillator.period());
+
+            if let Some(filter) = low_pass.as_mut() {
+                if self.low_pass_sweep != 0. {
+                    let cutoff = filter.cutoff() * 2_f32.powf(self.low_pass_sweep * dt);
+                    filter.set_cutoff(cutoff);
+                }
+                value = filter.process(value);
+            }
+            if let Some(filter) = high_pass.as_mut() {
+                value = filter.process(value);
+            }
+
+            output.push(value * envelope.tick() * self.volume);
+            since_repeat += dt;
+        }
+
+        output
+    }
+}
+
+/// Picks one of the values at random.
+fn pick<T>(rng: &mut Rng, values: &[T]) -> T
+where
+    T: Copy,
+{
+    let index = (rng.next_f32() * values.len() as f32) as usize;
+    values[index.min(values.len() - 1)]
+}
+
+/// Renders a sound for each seed into `directory`, named like `coin_0003.wav`.
+/// Returns the paths written.
+#[cfg(feature = "std")]
+pub fn export_batch<P>(
+    category: Category,
+    seeds: Range<u64>,
+    directory: P,
+    format: OutputFormat,
+) -> Result<Vec<PathBuf>, hound::Error>
+where
+    P: AsRef<Path>,
+{
+    std::fs::create_dir_all(directory.as_ref())?;
+
+    let mut paths = vec![];
+    for seed in seeds {
+        let path = directory
+            .as_ref()
+            .join(format!("{}_{:04}.wav", category.name(), seed));
+        let samples = SfxParams::generate(category, seed).render(format.sample_rate as f32);
+
+        let mut output = WavOutput::create(&path, format)?;
+        output.write_mono(&samples)?;
+        output.finalize()?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use crate::output::SampleFormat;
+
+    const SAMPLE_RATE: f32 = 22050.;
+
+    #[test]
+    fn same_seed_same_sound() {
+        for category in Category::ALL.iter() {
+            let a = SfxParams::generate(*category, 7);
+            let b = SfxParams::generate(*category, 7);
+            assert_eq!(a, b);
+            assert_eq!(a.render(SAMPLE_RATE), b.render(SAMPLE_RATE));
+
+            assert_ne!(a, SfxParams::generate(*category, 8));
+        }
+    }
+
+    #[test]
+    fn every_category_is_audible_and_bounded() {
+        for category in Category::ALL.iter() {
+            for seed in 0..10 {
+                let params = SfxParams::generate(*category, seed);
+                let samples = params.render(SAMPLE_RATE);
+                let peak = samples.iter().fold(0_f32, |peak, s| peak.max(s.abs()));
+
+                assert!(!samples.is_empty(), "{:?} {}", category, seed);
+                assert!(samples.len() <= (params.duration() * SAMPLE_RATE).round() as usize);
+                assert!(
+                    peak > 0.05 && peak < 1.5,
+                    "{:?} {} {}",
+                    category,
+                    seed,
+                    peak
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mutation_is_gradual() {
+        let params = SfxParams::generate(Category::Jump, 1);
+        let mut unchanged = params.mutate(3, 0.);
+        unchanged.seed = params.seed;
+        assert_eq!(params, unchanged);
+
+        let mutated = params.mutate(3, 0.1);
+        assert_ne!(params, mutated);
+        assert_eq!(params.waveform, mutated.waveform);
+        assert!((mutated.frequency / params.frequency).log2().abs() <= 0.1);
+        assert_eq!(mutated, params.mutate(3, 0.1));
+    }
+
+    #[test]
+    fn slide_below_min_frequency_ends_sound() {
+        let params = SfxParams {
+            frequency: 800.,
+            min_frequency: 400.,
+            slide: -10.,
+            sustain: 1.,
+            ..SfxParams::default()
+        };
+
+        // One octave down at 10 octaves per second takes 0.1 seconds
+        let length = params.render(SAMPLE_RATE).len() as f32 / SAMPLE_RATE;
+        assert!((length - 0.1).abs() < 0.01);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn exports_batch() {
+        let directory = std::env::temp_dir().join("wavrender_sfxr_batch");
+        let format = OutputFormat::new(1, 22050, SampleFormat::Int16);
+        let paths = export_batch(Category::Coin, 0..3, &directory, format).unwrap();
+
+        assert_eq!(3, paths.len());
+        assert!(paths[2].ends_with("coin_0002.wav"));
+        for path in paths.iter() {
+            let reader = hound::WavReader::open(path).unwrap();
+            assert_eq!(22050, reader.spec().sample_rate);
+            assert!(reader.len() > 0);
+        }
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
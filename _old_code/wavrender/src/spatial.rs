@@ -0,0 +1,589 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{
+    effects::DelayLine,
+    graph::{Context, Node, Parameter, Port},
+};
+use alloc::vec::Vec;
+use core::{
+    f32::consts::{FRAC_PI_4, PI},
+    ops::{Add, Mul, Sub},
+};
+
+/// In metres per second, through air at 20 °C.
+pub const SPEED_OF_SOUND: f32 = 343.;
+/// Half the distance between the ears, in metres.
+const HEAD_RADIUS: f32 = 0.0875;
+/// How much quieter the ear facing away from a source is, as a fraction of the level.
+const SHADOW_GAIN: f32 = 0.5;
+/// The head shadow low pass cutoffs for a source straight ahead of an ear and straight behind.
+const SHADOW_OPEN: f32 = 20000.;
+const SHADOW_CLOSED: f32 = 1500.;
+
+/// How the level of each channel follows the pan position.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PanLaw {
+    /// Keeps the total power constant, so the center is 3 dB down in each channel.
+    EqualPower,
+    /// Keeps the sum of the channels constant, so the center is 6 dB down in each channel.
+    ConstantGain,
+}
+
+impl PanLaw {
+    /// The left and right gains for a pan position from -1, left, to 1, right.
+    pub fn gains(self, pan: f32) -> (f32, f32) {
+        let pan = pan.clamp(-1., 1.);
+        match self {
+            PanLaw::EqualPower => {
+                let angle = (pan + 1.) * FRAC_PI_4;
+                (angle.cos(), angle.sin())
+            }
+            PanLaw::ConstantGain => ((1. - pan) / 2., (1. + pan) / 2.),
+        }
+    }
+}
+
+/// Places a mono signal between two channels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Panner {
+    law: PanLaw,
+    pan: f32,
+}
+
+impl Panner {
+    pub fn new(law: PanLaw, pan: f32) -> Self {
+        Self { law, pan }
+    }
+
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan;
+    }
+
+    /// Returns the left and right samples.
+    pub fn process(&self, sample: f32) -> (f32, f32) {
+        let (left, right) = self.law.gains(self.pan);
+        (sample * left, sample * right)
+    }
+}
+
+/// Narrows or widens a stereo signal by scaling its side (left minus right) against its mid.
+/// A width of 0 is mono, 1 leaves the signal unchanged and 2 doubles the side.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StereoWidth {
+    width: f32,
+}
+
+impl StereoWidth {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width: width.max(0.),
+        }
+    }
+
+    pub fn process(&self, left: f32, right: f32) -> (f32, f32) {
+        let mid = (left + right) / 2.;
+        let side = (left - right) / 2. * self.width;
+        (mid + side, mid - side)
+    }
+}
+
+/// Pans its input to `left` and `right` outputs. The `pan` control input is added to the
+/// `pan` parameter.
+pub struct PanNode {
+    panner: Panner,
+}
+
+impl PanNode {
+    pub fn new(panner: Panner) -> Self {
+        Self { panner }
+    }
+
+    const INPUTS: &'static [Port] = &[Port::audio("in"), Port::control("pan", 0.)];
+    const OUTPUTS: &'static [Port] = &[Port::audio("left"), Port::audio("right")];
+    const PARAMETERS: &'static [Parameter] = &[Parameter::new("pan", 0.)];
+}
+
+impl Node for PanNode {
+    fn inputs(&self) -> &[Port] {
+        Self::INPUTS
+    }
+
+    fn outputs(&self) -> &[Port] {
+        Self::OUTPUTS
+    }
+
+    fn parameters(&self) -> &[Parameter] {
+        Self::PARAMETERS
+    }
+
+    fn parameter(&self, _index: usize) -> f32 {
+        self.panner.pan()
+    }
+
+    fn set_parameter(&mut self, _index: usize, value: f32) {
+        self.panner.set_pan(value);
+    }
+
+    fn process(&mut self, _context: &Context, inputs: &[&[f32]], outputs: &mut [Vec<f32>]) {
+        let (left, right) = outputs.split_at_mut(1);
+        let samples = inputs[0].iter().zip(inputs[1].iter());
+        let outs = left[0].iter_mut().zip(right[0].iter_mut());
+        for ((l, r), (sample, pan)) in outs.zip(samples) {
+            let panner = Panner::new(self.panner.law, self.panner.pan + pan);
+            let (left, right) = panner.process(*sample);
+            *l = left;
+            *r = right;
+        }
+    }
+}
+
+/// A point or direction in metres. Like the raytracer, y is up and -z is forward.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Self) -> Self {
+        Self {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
+    pub fn len(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// The vector scaled to a length of 1, or zero if it has no length.
+    pub fn unit_vector(self) -> Self {
+        let len = self.len();
+        if len == 0. {
+            self
+        } else {
+            self * (1. / len)
+        }
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Self;
+
+    fn mul(self, scale: f32) -> Self {
+        Self::new(self.x * scale, self.y * scale, self.z * scale)
+    }
+}
+
+#[cfg(feature = "raytracing")]
+impl From<rraytracing::math::Vec3> for Vec3 {
+    fn from(v: rraytracing::math::Vec3) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+#[cfg(feature = "raytracing")]
+impl From<Vec3> for rraytracing::math::Vec3 {
+    fn from(v: Vec3) -> Self {
+        Self::new(v.x, v.y, v.z)
+    }
+}
+
+/// How quickly a source fades with distance.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rolloff {
+    /// Fades linearly to silence at the maximum distance.
+    Linear,
+    /// Halves the level each time the distance doubles, like a point source in the open.
+    Inverse,
+    /// Falls off as a power of the distance, set by the rolloff factor.
+    Exponential,
+}
+
+/// How a source's level depends on its distance from the listener.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DistanceModel {
+    rolloff: Rolloff,
+    /// Closer than this the level stays at 1.
+    reference_distance: f32,
+    /// Further than this the level stops changing.
+    max_distance: f32,
+    factor: f32,
+}
+
+impl DistanceModel {
+    pub fn new(rolloff: Rolloff) -> Self {
+        Self {
+            rolloff,
+            reference_distance: 1.,
+            max_distance: 100.,
+            factor: 1.,
+        }
+    }
+
+    pub fn with_reference_distance(mut self, distance: f32) -> Self {
+        self.reference_distance = distance.max(1e-3);
+        self
+    }
+
+    pub fn with_max_distance(mut self, distance: f32) -> Self {
+        self.max_distance = distance;
+        self
+    }
+
+    /// Scales how fast the level falls. 1 is the physical rate for `Inverse`.
+    pub fn with_factor(mut self, factor: f32) -> Self {
+        self.factor = factor.max(0.);
+        self
+    }
+
+    pub fn max_distance(&self) -> f32 {
+        self.max_distance
+    }
+
+    /// The gain at a distance in metres.
+    pub fn gain(&self, distance: f32) -> f32 {
+        let reference = self.reference_distance;
+        let max = self.max_distance.max(reference);
+        let distance = distance.clamp(reference, max);
+
+        match self.rolloff {
+            Rolloff::Linear => {
+                if max == reference {
+                    return 1.;
+                }
+                (1. - self.factor * (distance - reference) / (max - reference)).clamp(0., 1.)
+            }
+            Rolloff::Inverse => reference / (reference + self.factor * (distance - reference)),
+            Rolloff::Exponential => (distance / reference).powf(-self.factor),
+        }
+    }
+}
+
+impl Default for DistanceModel {
+    fn default() -> Self {
+        Self::new(Rolloff::Inverse)
+    }
+}
+
+/// Where sound is heard from. Positions and velocities are in metres and metres per second.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Listener {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// The direction the listener faces.
+    pub forward: Vec3,
+    pub up: Vec3,
+}
+
+impl Default for Listener {
+    /// At the origin facing -z, with y up.
+    fn default() -> Self {
+        Self {
+            position: Vec3::default(),
+            velocity: Vec3::default(),
+            forward: Vec3::new(0., 0., -1.),
+            up: Vec3::new(0., 1., 0.),
+        }
+    }
+}
+
+impl Listener {
+    /// Moves the listener along its velocity.
+    pub fn advance(&mut self, seconds: f32) {
+        self.position = self.position + self.velocity * seconds;
+    }
+
+    /// The unit vector pointing out of the right ear.
+    fn right(&self) -> Vec3 {
+        self.forward.cross(self.up).unit_vector()
+    }
+}
+
+/// One ear's delay and head shadow.
+#[derive(Clone, Debug, PartialEq)]
+struct Ear {
+    delay: DelayLine,
+    /// The state of the head shadow low pass.
+    shadow: f32,
+}
+
+impl Ear {
+    fn process(&mut self, delay: f32, gain: f32, cutoff: f32, sample_rate: f32) -> f32 {
+        let sample = self.delay.read(delay);
+        let coefficient = 1. - (-2. * PI * cutoff.min(sample_rate / 2.) / sample_rate).exp();
+        self.shadow += coefficient * (sample - self.shadow);
+        self.shadow * gain
+    }
+}
+
+/// A mono sound placed in 3D space and heard through two ears, without a HRTF.
+///
+/// Each ear hears the source after the time sound takes to reach it, which gives an interaural
+/// time difference and, when the distance changes, doppler shift. The ear facing away from the
+/// source is quieter and duller, for a level difference.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionalSource {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    distance_model: DistanceModel,
+    doppler: bool,
+    sample_rate: f32,
+    left: Ear,
+    right: Ear,
+}
+
+impl PositionalSource {
+    pub fn new(position: Vec3, sample_rate: f32) -> Self {
+        let mut source = Self {
+            position,
+            velocity: Vec3::default(),
+            distance_model: DistanceModel::default(),
+            doppler: true,
+            sample_rate,
+            left: Ear {
+                delay: DelayLine::new(1),
+                shadow: 0.,
+            },
+            right: Ear {
+                delay: DelayLine::new(1),
+                shadow: 0.,
+            },
+        };
+        source.reserve_delay();
+
+        source
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec3) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn with_distance_model(mut self, model: DistanceModel) -> Self {
+        self.distance_model = model;
+        self.reserve_delay();
+        self
+    }
+
+    /// With doppler off the source is heard without the travel time from it to the listener,
+    /// so moving doesn't change its pitch. The difference between the ears is kept.
+    pub fn with_doppler(mut self, doppler: bool) -> Self {
+        self.doppler = doppler;
+        self
+    }
+
+    /// Renders a block heard by the listener into left and right. The source moves along its
+    /// velocity during the block, and the listener is extrapolated along its own.
+    pub fn process(
+        &mut self,
+        listener: &Listener,
+        input: &[f32],
+        left: &mut [f32],
+        right: &mut [f32],
+    ) {
+        let (doppler, sample_rate, velocity) = (self.doppler, self.sample_rate, self.velocity);
+        let dt = 1. / sample_rate;
+        let ear = listener.right() * HEAD_RADIUS;
+        let samples = left.iter_mut().zip(right.iter_mut()).zip(input.iter());
+
+        for (i, ((l, r), sample)) in samples.enumerate() {
+            self.left.delay.push(*sample);
+            self.right.delay.push(*sample);
+
+            let t = i as f32 * dt;
+            let source = self.position + self.velocity * t;
+            let center = listener.position + listener.velocity * t;
+            let distance = (source - center).len();
+            let direction = (source - center).unit_vector();
+            let gain = self.distance_model.gain(distance);
+
+            let hear = |ear: &mut Ear, offset: Vec3| {
+                let path = source - (center + offset);
+                let travel = if doppler {
+                    travel_time(path, velocity)
+                } else {
+                    // Keeps the delay positive for a source right next to the ear
+                    (path.len() - distance + HEAD_RADIUS) / SPEED_OF_SOUND
+                };
+
+                // 1 for a source straight out from the ear, -1 for one on the other side
+                let facing = direction.dot(offset.unit_vector());
+                let shadow = (1. - facing) / 2.;
+                let ear_gain = gain * (1. - SHADOW_GAIN * shadow);
+                let cutoff = SHADOW_OPEN * (SHADOW_CLOSED / SHADOW_OPEN).powf(shadow);
+                let delay = 1. + travel * sample_rate;
+                ear.process(delay, ear_gain, cutoff, sample_rate)
+            };
+
+            *l = hear(&mut self.left, ear * -1.);
+            *r = hear(&mut self.right, ear);
+        }
+
+        self.position = self.position + self.velocity * (input.len() as f32 * dt);
+    }
+
+    /// Makes the delay lines long enough for a source at the maximum distance.
+    fn reserve_delay(&mut self) {
+        let distance = self.distance_model.max_distance() + 2. * HEAD_RADIUS;
+        let samples = (distance / SPEED_OF_SOUND * self.sample_rate).ceil() as usize + 2;
+        self.left.delay.reserve(samples);
+        self.right.delay.reserve(samples);
+    }
+}
+
+/// The seconds since a source now `offset` from an ear made the sound the ear hears now, when
+/// it has been moving at `velocity`.
+fn travel_time(offset: Vec3, velocity: Vec3) -> f32 {
+    // Solves |offset - velocity * t| = c * t for t
+    let c = SPEED_OF_SOUND;
+    let a = c * c - velocity.dot(velocity);
+    if a <= 0. {
+        // At or above the speed of sound, fall back to the current distance
+        return offset.len() / c;
+    }
+
+    let b = offset.dot(velocity);
+    (-b + (b * b + a * offset.dot(offset)).sqrt()) / a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::zero_crossing_pitch, oscillator::Oscillator};
+    use alloc::vec;
+
+    const SAMPLE_RATE: f32 = 44100.;
+
+    #[test]
+    fn pan_laws_and_width() {
+        let (left, right) = PanLaw::EqualPower.gains(0.);
+        assert!((left - core::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert!((left - right).abs() < 1e-6);
+        for pan in [-1., -0.3, 0.5, 1.].iter() {
+            let (left, right) = PanLaw::EqualPower.gains(*pan);
+            assert!((left * left + right * right - 1.).abs() < 1e-6);
+            let (left, right) = PanLaw::ConstantGain.gains(*pan);
+            assert!((left + right - 1.).abs() < 1e-6);
+        }
+        assert_eq!((0., 1.), Panner::new(PanLaw::ConstantGain, 2.).process(1.));
+
+        assert_eq!((0.5, 0.5), StereoWidth::new(0.).process(1., 0.));
+        assert_eq!((1., 0.), StereoWidth::new(1.).process(1., 0.));
+        assert_eq!((1.5, -0.5), StereoWidth::new(2.).process(1., 0.));
+    }
+
+    #[test]
+    fn distance_curves() {
+        let inverse = DistanceModel::new(Rolloff::Inverse);
+        assert_eq!(1., inverse.gain(0.5));
+        assert_eq!(0.5, inverse.gain(2.));
+        assert_eq!(0.1, inverse.gain(10.));
+
+        let linear = DistanceModel::new(Rolloff::Linear).with_max_distance(11.);
+        assert_eq!(0.5, linear.gain(6.));
+        assert_eq!(0., linear.gain(20.));
+
+        let exponential = DistanceModel::new(Rolloff::Exponential)
+            .with_reference_distance(2.)
+            .with_factor(2.);
+        assert_eq!(0.25, exponential.gain(4.));
+        assert_eq!(exponential.gain(100.), exponential.gain(1000.));
+    }
+
+    #[test]
+    fn source_to_the_right_reaches_the_right_ear_first_and_louder() {
+        let listener = Listener::default();
+        let mut source = PositionalSource::new(Vec3::new(2., 0., 0.), SAMPLE_RATE);
+        let mut impulse = vec![0.; 512];
+        impulse[0] = 1.;
+        let (mut left, mut right) = (vec![0.; 512], vec![0.; 512]);
+        source.process(&listener, &impulse, &mut left, &mut right);
+
+        let arrival = |ear: &[f32]| ear.iter().position(|s| s.abs() > 1e-3).unwrap_or(ear.len());
+        // 2 m away, and the left ear a head width further
+        let expected = (2. - HEAD_RADIUS) / SPEED_OF_SOUND * SAMPLE_RATE;
+        assert!(
+            (arrival(&right) as f32 - expected).abs() <= 1.,
+            "{}",
+            arrival(&right)
+        );
+        let difference = arrival(&left) - arrival(&right);
+        let expected = 2. * HEAD_RADIUS / SPEED_OF_SOUND * SAMPLE_RATE;
+        assert!((difference as f32 - expected).abs() <= 1., "{}", difference);
+
+        let energy = |ear: &[f32]| ear.iter().map(|s| s * s).sum::<f32>();
+        assert!(energy(&right) > 4. * energy(&left));
+    }
+
+    #[test]
+    fn approaching_source_is_doppler_shifted() {
+        let listener = Listener::default();
+        let tone = Oscillator::Sine { frequency: 1000. };
+        let input: Vec<f32> = (0..44100)
+            .map(|i| tone.sample(i as f32 / SAMPLE_RATE))
+            .collect();
+
+        let render = |doppler: bool| {
+            // Straight ahead, approaching at a tenth of the speed of sound
+            let mut source = PositionalSource::new(Vec3::new(0., 0., -60.), SAMPLE_RATE)
+                .with_velocity(Vec3::new(0., 0., SPEED_OF_SOUND / 10.))
+                .with_doppler(doppler);
+            let (mut left, mut right) = (vec![0.; 44100], vec![0.; 44100]);
+            for (i, block) in input.chunks(441).enumerate() {
+                let range = i * 441..(i + 1) * 441;
+                source.process(
+                    &listener,
+                    block,
+                    &mut left[range.clone()],
+                    &mut right[range],
+                );
+            }
+            zero_crossing_pitch(&left[22050..], SAMPLE_RATE).unwrap()
+        };
+
+        let shifted = render(true);
+        assert!((shifted - 1000. / 0.9).abs() < 2., "{}", shifted);
+        assert!((render(false) - 1000.).abs() < 2.);
+    }
+
+    #[cfg(feature = "raytracing")]
+    #[test]
+    fn converts_raytracer_positions() {
+        let position = Vec3::from(rraytracing::math::Vec3::new(1., 2., -3.));
+        assert_eq!(Vec3::new(1., 2., -3.), position);
+
+        let back: rraytracing::math::Vec3 = position.into();
+        assert_eq!(rraytracing::math::Vec3::new(1., 2., -3.), back);
+    }
+}
@@ -0,0 +1,229 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{
+    envelope::Envelope,
+    oscillator::{Oscillator, Partial},
+    pitch::Tuning,
+    sampler::Sampler,
+};
+use alloc::{vec, vec::Vec};
+
+/// An instrument that plays notes, one sample at a time.
+pub trait Voice {
+    /// Starts a note. Velocity is 0 .. 1.
+    fn note_on(&mut self, note: u8, velocity: f32);
+
+    fn note_off(&mut self, note: u8);
+
+    fn tick(&mut self) -> f32;
+}
+
+impl Voice for Sampler {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        Sampler::note_on(self, note, velocity)
+    }
+
+    fn note_off(&mut self, note: u8) {
+        Sampler::note_off(self, note)
+    }
+
+    fn tick(&mut self) -> f32 {
+        Sampler::tick(self)
+    }
+}
+
+/// A monophonic oscillator shaped by an envelope. A new note takes over from the last.
+pub struct Synth {
+    oscillator: Oscillator,
+    /// The oscillator's period, found once since searching for it is slow for additive waves.
+    period: f64,
+    envelope: Envelope,
+    tuning: Tuning,
+    note: Option<u8>,
+    velocity: f32,
+    sample_rate: f32,
+    phase: f64,
+}
+
+impl Synth {
+    pub fn new(oscillator: Oscillator, envelope: Envelope, sample_rate: f32) -> Self {
+        Self {
+            period: oscillator.period(),
+            oscillator,
+            envelope,
+            tuning: Tuning::default(),
+            note: None,
+            velocity: 1.,
+            sample_rate,
+            phase: 0.,
+        }
+    }
+
+    /// Plays notes in another tuning. Notes the tuning leaves unmapped are ignored.
+    pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
+}
+
+impl Voice for Synth {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        let frequency = match self.tuning.frequency(note) {
+            Some(frequency) => frequency,
+            None => return,
+        };
+        self.oscillator.set_frequency(frequency);
+        self.note = Some(note);
+        self.velocity = velocity;
+        self.envelope.on();
+    }
+
+    fn note_off(&mut self, note: u8) {
+        if self.note == Some(note) {
+            self.note = None;
+            self.envelope.off();
+        }
+    }
+
+    fn tick(&mut self) -> f32 {
+        let value = self
+            .oscillator
+            .sample_phase_at(self.phase as f32, self.sample_rate);
+        let delta = self.oscillator.frequency() as f64 / self.sample_rate as f64;
+        self.phase = (self.phase + delta).rem_euclid(self.period);
+
+        value * self.envelope.tick() * self.velocity
+    }
+}
+
+/// Additive synthesis where every partial has its own envelope, so upper partials can fade first.
+pub struct AdditiveSynth {
+    partials: Vec<(Partial, Envelope)>,
+    tuning: Tuning,
+    note: Option<u8>,
+    frequency: f32,
+    velocity: f32,
+    sample_rate: f32,
+    phase: f64,
+}
+
+impl AdditiveSynth {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            partials: vec![],
+            tuning: Tuning::default(),
+            note: None,
+            frequency: 440.,
+            velocity: 1.,
+            sample_rate,
+            phase: 0.,
+        }
+    }
+
+    pub fn with_partial(mut self, partial: Partial, envelope: Envelope) -> Self {
+        self.partials.push((partial, envelope));
+        self
+    }
+
+    /// Plays notes in another tuning. Notes the tuning leaves unmapped are ignored.
+    pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
+}
+
+impl Voice for AdditiveSynth {
+    fn note_on(&mut self, note: u8, velocity: f32) {
+        self.frequency = match self.tuning.frequency(note) {
+            Some(frequency) => frequency,
+            None => return,
+        };
+        self.note = Some(note);
+        self.velocity = velocity;
+        self.partials.iter_mut().for_each(|(_, e)| e.on());
+    }
+
+    fn note_off(&mut self, note: u8) {
+        if self.note == Some(note) {
+            self.note = None;
+            self.partials.iter_mut().for_each(|(_, e)| e.off());
+        }
+    }
+
+    fn tick(&mut self) -> f32 {
+        let nyquist = self.sample_rate / 2.;
+        let (frequency, phase) = (self.frequency, self.phase);
+        let value: f32 = self
+            .partials
+            .iter_mut()
+            .map(|(partial, envelope)| {
+                let level = envelope.tick();
+                // Partials above Nyquist would alias
+                if partial.ratio * frequency >= nyquist {
+                    return 0.;
+                }
+
+                let cycles = (phase * partial.ratio as f64).fract();
+                partial.amplitude * level * (2. * core::f64::consts::PI * cycles).sin() as f32
+            })
+            .sum();
+
+        // Wrapped on whole cycles of the fundamental, where whole number ratios line up again
+        self.phase = (self.phase + (frequency / self.sample_rate) as f64).rem_euclid(1000.);
+        value * self.velocity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Spectrum, Window};
+
+    #[test]
+    fn synth_plays_note_pitch_until_released() {
+        let envelope = Envelope::new(0., 0., 1., 0., 8000.);
+        let mut synth = Synth::new(
+            Oscillator::Pulse {
+                frequency: 1.,
+                duty: 0.5,
+            },
+            envelope,
+            8000.,
+        );
+        synth.note_on(69, 0.5);
+
+        let samples: Vec<f32> = (0..8000).map(|_| synth.tick()).collect();
+        let rising = samples
+            .windows(2)
+            .filter(|w| w[0] < 0. && w[1] > 0.)
+            .count();
+        // Every cycle but the first starts with a rising edge
+        assert_eq!(439, rising);
+        assert!(samples.iter().all(|s| s.abs() == 0.5));
+
+        synth.note_off(60);
+        assert_eq!(0.5, synth.tick().abs());
+        synth.note_off(69);
+        synth.tick();
+        assert_eq!(0., synth.tick());
+    }
+
+    #[test]
+    fn additive_partials_follow_their_envelopes() {
+        let mut synth = AdditiveSynth::new(8000.)
+            .with_partial(Partial::new(1., 1.), Envelope::new(0., 0., 1., 0., 8000.))
+            .with_partial(Partial::new(2., 1.), Envelope::new(0., 0.2, 0., 0., 8000.))
+            .with_partial(Partial::new(20., 1.), Envelope::new(0., 0., 1., 0., 8000.));
+        synth.note_on(57, 1.);
+
+        let samples: Vec<f32> = (0..8000).map(|_| synth.tick()).collect();
+        let early = Spectrum::new(&samples[..800], 8000., Window::BlackmanHarris);
+        let late = Spectrum::new(&samples[4000..], 8000., Window::BlackmanHarris);
+
+        assert!(early.magnitude_at(440.) > 0.2);
+        assert!(late.magnitude_at(440.) < 1e-3);
+        assert!(late.magnitude_at(220.) > 0.9);
+        // The 20th partial is above Nyquist
+        assert!(late.band_power(2000., 4000.) < 1e-4);
+    }
+}
@@ -0,0 +1,262 @@
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{
+    analysis::{fft, ifft, Complex},
+    oscillator::Oscillator,
+    sample::Sample,
+};
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+/// The number of values in each stored cycle.
+pub const TABLE_SIZE: usize = 2048;
+/// One mip-map per octave, from every harmonic down to only the fundamental.
+const LEVELS: usize = 11;
+
+/// Single-cycle waves that an oscillator can morph between, stored as band-limited mip-maps so
+/// high notes don't alias.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wavetable {
+    /// Indexed by level, then frame. Level `l` keeps harmonics up to `TABLE_SIZE / 2 >> l`.
+    /// Each table repeats its first value at the end for interpolation.
+    levels: Vec<Vec<Vec<f32>>>,
+    sample_rate: f32,
+}
+
+impl Wavetable {
+    /// Creates a wavetable from single cycles of any length, for playback at the sample rate.
+    pub fn new(frames: Vec<Vec<f32>>, sample_rate: f32) -> Self {
+        let mut levels = vec![vec![]; LEVELS];
+        for frame in frames.iter().filter(|f| !f.is_empty()) {
+            let mut spectrum: Vec<Complex> = resample(frame)
+                .into_iter()
+                .map(|s| Complex::new(s as f64, 0.))
+                .collect();
+            fft(&mut spectrum);
+
+            for (level, tables) in levels.iter_mut().enumerate() {
+                let harmonics = (TABLE_SIZE / 2) >> level;
+                let mut bins = spectrum.clone();
+                for (bin, value) in bins.iter_mut().enumerate() {
+                    // Bins above the middle are the negative frequencies
+                    let harmonic = bin.min(TABLE_SIZE - bin);
+                    if harmonic > harmonics {
+                        *value = Complex::default();
+                    }
+                }
+                ifft(&mut bins);
+
+                let mut table: Vec<f32> = bins.iter().map(|b| b.re as f32).collect();
+                table.push(table[0]);
+                tables.push(table);
+            }
+        }
+
+        if levels[0].is_empty() {
+            levels
+                .iter_mut()
+                .for_each(|l| l.push(vec![0.; TABLE_SIZE + 1]));
+        }
+
+        Self {
+            levels,
+            sample_rate,
+        }
+    }
+
+    /// Creates a frame from one cycle of each oscillator. Their frequencies are ignored.
+    pub fn from_oscillators(oscillators: &[Oscillator], sample_rate: f32) -> Self {
+        let frames = oscillators
+            .iter()
+            .map(|o| {
+                (0..TABLE_SIZE)
+                    .map(|i| o.sample_phase(i as f32 / TABLE_SIZE as f32))
+                    .collect()
+            })
+            .collect();
+
+        Self::new(frames, sample_rate)
+    }
+
+    /// Splits a sample into frames of `frame_size` values, mixing channels together.
+    pub fn from_sample(sample: &Sample, frame_size: usize, sample_rate: f32) -> Self {
+        let mono: Vec<f32> = (0..sample.len())
+            .map(|i| {
+                let sum: f32 = (0..sample.channel_count())
+                    .map(|c| sample.channel(c)[i])
+                    .sum();
+                sum / sample.channel_count() as f32
+            })
+            .collect();
+        let frames = mono
+            .chunks_exact(frame_size.max(1))
+            .map(|f| f.to_vec())
+            .collect();
+
+        Self::new(frames, sample_rate)
+    }
+
+    /// Loads a WAV file of consecutive single cycles `frame_size` values long.
+    #[cfg(feature = "std")]
+    pub fn load<P>(path: P, frame_size: usize, sample_rate: f32) -> Result<Self, hound::Error>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self::from_sample(
+            &Sample::load(path)?,
+            frame_size,
+            sample_rate,
+        ))
+    }
+
+    pub fn frames(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Samples the table at a phase in cycles. `position` morphs across the frames, 0 .. 1.
+    /// The frequency and the sample rate it's played at pick a mip-map without harmonics above
+    /// Nyquist.
+    pub fn sample(&self, phase: f32, position: f32, frequency: f32, sample_rate: f32) -> f32 {
+        let max_harmonic = sample_rate / 2. / frequency.abs().max(1e-3);
+        let level = ((TABLE_SIZE / 2) as f32 / max_harmonic)
+            .log2()
+            .ceil()
+            .clamp(0., (LEVELS - 1) as f32) as usize;
+        let tables = &self.levels[level];
+
+        let position = position.clamp(0., 1.) * (tables.len() - 1) as f32;
+        let index = position.floor() as usize;
+        let fraction = position - index as f32;
+        let a = lookup(&tables[index], phase);
+        if fraction == 0. {
+            return a;
+        }
+
+        let b = lookup(&tables[(index + 1).min(tables.len() - 1)], phase);
+        a + (b - a) * fraction
+    }
+}
+
+fn lookup(table: &[f32], phase: f32) -> f32 {
+    let x = (phase - phase.floor()) * TABLE_SIZE as f32;
+    let index = (x as usize).min(TABLE_SIZE - 1);
+    let fraction = x - index as f32;
+    table[index] + (table[index + 1] - table[index]) * fraction
+}
+
+/// Stretches a cycle to `TABLE_SIZE` values with linear interpolation, wrapping at the end.
+fn resample(frame: &[f32]) -> Vec<f32> {
+    if frame.len() == TABLE_SIZE {
+        return frame.to_vec();
+    }
+
+    let step = frame.len() as f32 / TABLE_SIZE as f32;
+    (0..TABLE_SIZE)
+        .map(|i| {
+            let x = i as f32 * step;
+            let index = x as usize % frame.len();
+            let next = frame[(index + 1) % frame.len()];
+            frame[index] + (next - frame[index]) * x.fract()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Spectrum, Window};
+    use alloc::sync::Arc;
+
+    const SAMPLE_RATE: f32 = 44100.;
+
+    fn render(oscillator: &Oscillator) -> Vec<f32> {
+        let delta = oscillator.frequency() / SAMPLE_RATE;
+        (0..16384)
+            .map(|i| oscillator.sample_phase((i as f32 * delta).fract()))
+            .collect()
+    }
+
+    #[test]
+    fn mip_maps_remove_harmonics_above_nyquist() {
+        let table = Wavetable::from_oscillators(&[Oscillator::Saw { frequency: 1. }], SAMPLE_RATE);
+        let naive = Oscillator::Saw { frequency: 5000. };
+        let wavetable = Oscillator::Wavetable {
+            table: Arc::new(table),
+            frequency: 5000.,
+            position: 0.,
+        };
+
+        // The 5th harmonic at 25 kHz folds back to 19.1 kHz
+        let spectrum = |oscillator: &Oscillator| {
+            Spectrum::new(&render(oscillator), SAMPLE_RATE, Window::BlackmanHarris)
+        };
+        assert!(spectrum(&naive).magnitude_at(19100.) > 0.05);
+        assert!(spectrum(&wavetable).magnitude_at(19100.) < 1e-3);
+        let fundamental = spectrum(&wavetable).magnitude_at(5000.);
+        assert!((fundamental / spectrum(&naive).magnitude_at(5000.) - 1.).abs() < 0.01);
+    }
+
+    #[test]
+    fn mip_maps_follow_the_playback_rate() {
+        let table = Wavetable::from_oscillators(&[Oscillator::Saw { frequency: 1. }], SAMPLE_RATE);
+        let wavetable = Oscillator::Wavetable {
+            table: Arc::new(table),
+            frequency: 5000.,
+            position: 0.,
+        };
+
+        // Oversampled twice the 5th harmonic at 25 kHz is below Nyquist and kept
+        let rate = SAMPLE_RATE * 2.;
+        let render = |sample_rate: f32| -> Vec<f32> {
+            (0..16384)
+                .map(|i| {
+                    let phase = (i as f32 * 5000. / rate).fract();
+                    wavetable.sample_phase_at(phase, sample_rate)
+                })
+                .collect()
+        };
+        let spectrum = |samples: &[f32]| Spectrum::new(samples, rate, Window::BlackmanHarris);
+        assert!(spectrum(&render(rate)).magnitude_at(25000.) > 0.05);
+        assert!(spectrum(&render(SAMPLE_RATE)).magnitude_at(25000.) < 1e-3);
+    }
+
+    #[test]
+    fn position_morphs_between_frames() {
+        let frames = vec![vec![1.; 64], vec![-1.; 64]];
+        let table = Wavetable::new(frames, SAMPLE_RATE);
+
+        assert_eq!(2, table.frames());
+        assert!((table.sample(0.3, 0., 100., SAMPLE_RATE) - 1.).abs() < 1e-5);
+        assert!((table.sample(0.3, 1., 100., SAMPLE_RATE) + 1.).abs() < 1e-5);
+        assert!(table.sample(0.3, 0.5, 100., SAMPLE_RATE).abs() < 1e-5);
+    }
+
+    #[test]
+    fn frames_split_from_sample() {
+        let cycle = |shape: Oscillator| -> Vec<f32> {
+            (0..256)
+                .map(|i| shape.sample_phase(i as f32 / 256.))
+                .collect()
+        };
+        let mut data = cycle(Oscillator::Sine { frequency: 1. });
+        data.extend(cycle(Oscillator::Triangle { frequency: 1. }));
+        data.extend(vec![0.; 100]);
+        let sample = Sample::from_mono(data, 44100);
+
+        let table = Wavetable::from_sample(&sample, 256, SAMPLE_RATE);
+        let sine = Oscillator::Sine { frequency: 1. };
+
+        assert_eq!(2, table.frames());
+        for phase in [0.1, 0.25, 0.6].iter() {
+            assert!(
+                (table.sample(*phase, 0., 100., SAMPLE_RATE) - sine.sample_phase(*phase)).abs()
+                    < 1e-3
+            );
+        }
+    }
+}